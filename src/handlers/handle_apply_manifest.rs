@@ -0,0 +1,194 @@
+use cli_table::Table;
+use serde::Serialize;
+use std::path::Path;
+use tokio::fs;
+
+use crate::{
+    handlers::{
+        handle_create_service::{create_service_file, render_service_file, verify_service_file},
+        handle_delete_service::handle_delete_service,
+        handle_enable_service::handle_enable_service,
+        handle_restart_service::handle_restart_service,
+        handle_show_status::handle_show_status,
+        handle_start_service::handle_start_service,
+    },
+    utils::{
+        manifest::read_manifest,
+        output::{is_structured_output, print_message, print_rows},
+        schedule::{activate_timer, deactivate_timer},
+        service_names::{
+            get_full_service_name, get_path_unit_file_path, get_servicer_services,
+            get_short_service_name, get_timer_file_path,
+        },
+        systemd::{get_active_state, get_connection, get_unit_file_state, ManagerProxy},
+        watch::{activate_watch, remove_watch},
+    },
+};
+
+#[derive(Table, Serialize, Clone)]
+pub struct PlanEntry {
+    /// The short service name, excluding '.ser.service'
+    name: String,
+
+    /// What `apply` will do with the service
+    action: String,
+}
+
+/// Creates, updates or deletes `.ser.service` units to match a `servicer.toml` manifest. The
+/// plan is printed before any change is made.
+///
+/// Services present on the machine but missing from the manifest are listed as unmanaged. They are
+/// only deleted with `prune`.
+///
+/// # Arguments
+///
+/// * `manifest_path` - Path to the manifest
+/// * `dry_run` - Only print the plan
+/// * `prune` - Delete services missing from the manifest
+///
+pub async fn handle_apply_manifest(
+    manifest_path: &Path,
+    dry_run: bool,
+    prune: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(manifest_path).await?;
+
+    let mut plan = Vec::<PlanEntry>::new();
+    let mut changes = vec![];
+
+    for service in &manifest.services {
+        let options = service.to_create_options(&manifest.dir);
+        let service_file = render_service_file(&options).await?;
+        let full_service_name = get_full_service_name(&service.name);
+        let timer = fs::read_to_string(get_timer_file_path(&full_service_name))
+            .await
+            .ok();
+        let path_unit = fs::read_to_string(get_path_unit_file_path(&full_service_name))
+            .await
+            .ok();
+
+        // Restored if systemd parses the updated service file differently
+        let previous_body = if service_file.path.exists() {
+            Some(fs::read_to_string(&service_file.path).await?)
+        } else {
+            None
+        };
+
+        let action = if previous_body.is_none() {
+            "create"
+        } else if previous_body.as_deref() != Some(service_file.body.as_str())
+            || timer != service_file.timer
+            || path_unit != service_file.path_unit
+        {
+            "update"
+        } else {
+            "unchanged"
+        };
+
+        plan.push(PlanEntry {
+            name: service.name.clone(),
+            action: action.to_string(),
+        });
+        changes.push((options, service_file, action, previous_body));
+    }
+
+    let mut removed_services = Vec::<String>::new();
+    for full_service_name in get_servicer_services(None).await? {
+        let name = get_short_service_name(&full_service_name);
+
+        if !manifest.services.iter().any(|service| service.name == name) {
+            plan.push(PlanEntry {
+                name: name.clone(),
+                action: if prune { "delete" } else { "unmanaged" }.to_string(),
+            });
+
+            if prune {
+                removed_services.push(name);
+            }
+        }
+    }
+
+    print_message(&format!("Plan for {}:", manifest_path.display()));
+    print_rows(&plan)?;
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !removed_services.is_empty() {
+        handle_delete_service(&removed_services, false, true).await?;
+    }
+
+    for (_, service_file, action, _) in &changes {
+        if *action != "unchanged" {
+            create_service_file(service_file).await?;
+            print_message(&format!("Wrote {}", service_file.path.display()));
+        }
+    }
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    // Services that lost their schedule stop being started by their timer, and services that lost
+    // their watched paths stop being restarted
+    for (_, service_file, action, _) in &changes {
+        let full_service_name = get_full_service_name(&service_file.service_name);
+        let timer_file_path = get_timer_file_path(&full_service_name);
+        let path_unit_file_path = get_path_unit_file_path(&full_service_name);
+
+        if *action == "update" && service_file.timer.is_none() && timer_file_path.exists() {
+            deactivate_timer(&manager_proxy, &full_service_name).await?;
+            fs::remove_file(&timer_file_path).await?;
+            print_message(&format!("Deleted {}", timer_file_path.display()));
+        }
+
+        if *action == "update" && service_file.path_unit.is_none() && path_unit_file_path.exists() {
+            remove_watch(&manager_proxy, &full_service_name).await?;
+            print_message(&format!("Deleted {}", path_unit_file_path.display()));
+        }
+    }
+
+    // Reload so that systemd picks up updated unit files
+    manager_proxy.reload().await?;
+
+    for (_, service_file, action, previous_body) in &changes {
+        if *action != "unchanged" {
+            verify_service_file(&connection, service_file, previous_body.as_deref()).await?;
+        }
+    }
+
+    for (options, service_file, action, _) in &changes {
+        let name = &service_file.service_name;
+        let full_service_name = get_full_service_name(name);
+
+        if *action != "unchanged" && service_file.timer.is_some() {
+            activate_timer(&manager_proxy, &full_service_name).await?;
+        }
+
+        if *action != "unchanged" && service_file.path_unit.is_some() {
+            activate_watch(&manager_proxy, &full_service_name).await?;
+        }
+
+        let active_state = get_active_state(&connection, &full_service_name).await;
+        let is_active = active_state == "active" || active_state == "reloading";
+
+        // A running service keeps its old definition till restarted
+        if *action == "update" && is_active {
+            handle_restart_service(std::slice::from_ref(name), false, false).await?;
+        } else if options.start && !is_active {
+            handle_start_service(std::slice::from_ref(name), false).await?;
+        }
+
+        if options.enable && get_unit_file_state(&connection, &full_service_name).await != "enabled"
+        {
+            handle_enable_service(std::slice::from_ref(name), false).await?;
+        }
+    }
+
+    // The plan is the document printed in JSON and YAML modes
+    if !is_structured_output() {
+        handle_show_status(false, None).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use cli_table::Table;
+use serde::Serialize;
+
+use crate::utils::{
+    audit::{audit_service, get_exposure_rating},
+    config::get_config,
+    output::{print_message, print_rows},
+    service_names::{
+        get_full_service_name, get_service_file_path, get_servicer_services,
+        get_short_service_name, resolve_service_names,
+    },
+};
+
+/// Exposure of a service
+#[derive(Table, Serialize)]
+struct AuditRow {
+    /// The short service name
+    name: String,
+
+    /// Score from 0 (safe) to 10 (exposed)
+    #[table(display_fn = "display_exposure")]
+    exposure: f32,
+
+    /// Rating of the score, eg. `MEDIUM`
+    rating: String,
+
+    /// Number of failed checks
+    #[table(title = "failed checks")]
+    failed_checks: usize,
+}
+
+/// A sandboxing directive missing from a service
+#[derive(Table, Serialize)]
+struct FindingRow {
+    /// The short service name
+    name: String,
+
+    /// The directive checked, eg. `ProtectSystem`
+    directive: String,
+
+    /// Share of the exposure score lost by the service
+    weight: u32,
+
+    /// Line to add to pass the check
+    suggestion: String,
+}
+
+/// Scores the sandboxing of services offline, like `systemd-analyze security`. Prints a table of
+/// all services, or the missing directives of the passed services. Fails if a service is more
+/// exposed than the threshold.
+///
+/// # Arguments
+///
+/// * `names` - Service names, `all`, `@tag` or glob patterns. Empty for all services.
+/// * `threshold` - Highest accepted exposure. Falls back to `audit_threshold` in the config.
+///
+pub async fn handle_audit(
+    names: &[String],
+    threshold: Option<f32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let show_findings = !names.is_empty();
+
+    let names: Vec<String> = if show_findings {
+        resolve_service_names(names).await?
+    } else {
+        get_servicer_services(None)
+            .await?
+            .iter()
+            .map(|full_service_name| get_short_service_name(full_service_name))
+            .collect()
+    };
+
+    let mut audit_rows = vec![];
+    let mut finding_rows = vec![];
+
+    for name in &names {
+        let full_service_name = get_full_service_name(name);
+
+        if !get_service_file_path(&full_service_name).exists() {
+            return Err(format!("No such service {full_service_name}").into());
+        }
+
+        let audit = audit_service(&full_service_name).await?;
+
+        audit_rows.push(AuditRow {
+            name: name.clone(),
+            exposure: audit.exposure,
+            rating: get_exposure_rating(audit.exposure).to_string(),
+            failed_checks: audit.findings.len(),
+        });
+
+        finding_rows.extend(audit.findings.into_iter().map(|finding| FindingRow {
+            name: name.clone(),
+            directive: finding.directive,
+            weight: finding.weight,
+            suggestion: finding.suggestion,
+        }));
+    }
+
+    if show_findings {
+        print_rows(&finding_rows)?;
+
+        for row in &audit_rows {
+            print_message(&format!(
+                "{}: exposure {:.1} {}",
+                row.name, row.exposure, row.rating
+            ));
+        }
+    } else {
+        print_rows(&audit_rows)?;
+    }
+
+    if let Some(threshold) = threshold.or(get_config().audit_threshold) {
+        let exposed: Vec<&str> = audit_rows
+            .iter()
+            .filter(|row| row.exposure > threshold)
+            .map(|row| row.name.as_str())
+            .collect();
+
+        if !exposed.is_empty() {
+            return Err(format!(
+                "{} above the exposure threshold {threshold:.1}",
+                exposed.join(", ")
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Displays an exposure score with one decimal, eg. `9.0`
+///
+/// # Arguments
+///
+/// * `exposure`
+///
+fn display_exposure(exposure: &f32) -> String {
+    format!("{exposure:.1}")
+}
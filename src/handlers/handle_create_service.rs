@@ -1,58 +1,165 @@
 use indoc::formatdoc;
-use std::{env, path::PathBuf};
-use tokio::fs;
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+use tokio::{fs, process::Command};
+use zbus::Connection;
 
 use crate::{
     handlers::{
-        handle_enable_service::handle_enable_service, handle_show_status::handle_show_status,
-        handle_start_service::handle_start_service,
+        handle_enable_service::handle_enable_service, handle_secret::set_secret,
+        handle_show_status::handle_show_action_result, handle_start_service::handle_start_service,
     },
     utils::{
-        find_binary_path::find_binary_path,
-        service_names::{get_full_service_name, get_service_file_path},
+        credentials::{read_secret_value, validate_secret_name},
+        env_file::{get_env_file_path, get_environment_file_value, merge_env_vars},
+        find_binary_path::{find_binary_path, get_path_env},
+        hardening::{get_hardening_directives, Hardening},
+        interpreter::{detect_interpreter, find_project_interpreter, Interpreter},
+        limits::ResourceLimits,
+        output::{is_structured_output, print_message, ActionResult},
+        restart::{supports_backoff, RestartOptions, RestartPolicy, BACKOFF_MIN_VERSION},
+        schedule::{activate_timer, get_timer_file_body, parse_schedule},
+        scope::{
+            get_default_target, get_service_user, get_user_account, is_lingering, is_user_scope,
+        },
+        service_names::{
+            get_drop_in_folder, get_full_service_name, get_full_timer_name,
+            get_path_unit_file_path, get_restarter_file_path, get_service_file_path,
+            get_timer_file_path,
+        },
+        systemd::{get_connection, get_exec_settings, ManagerProxy},
+        unit_file::{
+            escape_specifiers, quote_environment, quote_exec_args, validate_tag, TAGS_KEY,
+        },
+        watch::{activate_watch, get_path_unit_body, resolve_watch_paths, write_watch_units},
     },
 };
 
+/// Options to create a service. Built from the `create` command or from an entry in a
+/// `servicer.toml` manifest.
+pub struct CreateServiceOptions {
+    /// Create service for a file at this path. Either this or `command` is set.
+    pub path: Option<PathBuf>,
+
+    /// Create service for a command line, eg. `npm run start`
+    pub command: Option<String>,
+
+    /// Folder the command runs in. Defaults to the current folder. Files run in their own folder.
+    pub working_directory: Option<PathBuf>,
+
+    /// Optional custom name for the service. Defaults to the file name. Required for commands
+    pub name: Option<String>,
+
+    /// User the service runs as. Defaults to the invoking user
+    pub user: Option<String>,
+
+    /// Start the service after creating it
+    pub start: bool,
+
+    /// Enable the service to start on boot
+    pub enable: bool,
+
+    /// Restart policy, delays and start limits
+    pub restart: RestartOptions,
+
+    /// Optional custom interpreter command, eg. `java -jar`. Detected from the file extension or the
+    /// shebang line if not provided
+    pub interpreter: Option<String>,
+
+    /// Environment variables as key and value pairs
+    pub env_vars: Vec<(String, String)>,
+
+    /// File to load environment variables from. Defaults to `.env` in the working directory
+    pub env_file: Option<PathBuf>,
+
+    /// Names of secrets to prompt for and store encrypted, eg. `API_KEY`
+    pub secrets: Vec<String>,
+
+    /// Resource limits like `MemoryMax`
+    pub limits: ResourceLimits,
+
+    /// Sandboxing preset
+    pub hardening: Option<Hardening>,
+
+    /// Run the service on a schedule with a timer, eg. `daily`, `*-*-* 03:00:00` or `0 3 * * *`
+    pub schedule: Option<String>,
+
+    /// Files or folders restarting the service when they change. Relative paths start from the
+    /// current folder
+    pub watch: Vec<PathBuf>,
+
+    /// Tags to act on a group of services, eg. `backend`
+    pub tags: Vec<String>,
+
+    /// Args passed to the file or appended to the command
+    pub internal_args: Vec<String>,
+}
+
+/// The command run by a service
+struct ServiceCommand {
+    /// Folder the command runs in
+    working_directory: String,
+
+    /// Absolute path of the executable
+    program: String,
+
+    /// Args passed to the executable
+    args: Vec<String>,
+
+    /// `PATH` for the service, so that the program's siblings like `node` next to `npm` are found
+    path_env: Option<String>,
+
+    /// Comment placed at the top of the unit, eg. the chosen interpreter
+    comment: String,
+}
+
+/// A rendered service file, ready to be written to disk
+pub struct ServiceFile {
+    /// Name of the service without '.ser.service' in the end
+    pub service_name: String,
+
+    /// Path where the service file will be written
+    pub path: PathBuf,
+
+    /// Contents of the .service file
+    pub body: String,
+
+    /// Contents of the .timer file of a scheduled service
+    pub timer: Option<String>,
+
+    /// Contents of the .path unit of a watched service
+    pub path_unit: Option<String>,
+
+    /// The program and args that systemd should parse from `ExecStart=`
+    pub argv: Vec<String>,
+
+    /// `KEY=VALUE` pairs that systemd should parse from `Environment=`
+    pub environment: Vec<String>,
+}
+
 /// Creates a new systemd service file.
 ///
 /// # Arguments
 ///
-/// * `path` - Create service for a file at this path
-/// * `custom_name`
-/// * `custom_interpreter`
-/// * `env_vars`
-/// * `internal_args`
+/// * `options` - Options for the new service
 ///
 pub async fn handle_create_service(
-    path: PathBuf,
-    custom_name: Option<String>,
-    start: bool,
-    enable: bool,
-    auto_restart: bool,
-    custom_interpreter: Option<String>,
-    env_vars: Option<String>,
-    internal_args: Vec<String>,
+    options: CreateServiceOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if !path.is_file() {
-        return Err(format!("{} is not a file", path.to_str().unwrap()).into());
+    if options.secrets.len() > 1 && !io::stdin().is_terminal() {
+        return Err(
+            "Secrets can only be piped one at a time. Add the rest with `ser secret set`".into(),
+        );
     }
 
-    // The file name including extension, eg. index.js
-    let file_name = path
-        .file_name()
-        .expect("Failed to get file name")
-        .to_str()
-        .expect("Failed to stringify file name")
-        .to_string();
+    let service_file = render_service_file(&options).await?;
+    let service_name = service_file.service_name.clone();
+    let service_file_path_str = service_file.path.to_str().unwrap().to_string();
 
-    let service_name = custom_name.unwrap_or_else(|| file_name.to_string());
-    let full_service_name = get_full_service_name(&service_name);
-
-    // Create file if it doesn't exist
-    let service_file_path = get_service_file_path(&full_service_name);
-    let service_file_path_str = service_file_path.to_str().unwrap().to_string();
-
-    if service_file_path.exists() {
+    if service_file.path.exists() {
         panic!(
             "Service {} already exists at {}. Provide a custom name with --name or delete the existing service with `ser delete {}",
             service_name,
@@ -60,163 +167,647 @@ pub async fn handle_create_service(
             service_name
         );
     } else {
-        let interpreter = match custom_interpreter {
-            Some(_) => custom_interpreter,
-            None => get_interpreter(path.extension()),
-        };
-
-        // Handle case `ser create index.js` where relative path lacks ./
-        let mut parent_path = path.parent().unwrap();
-        let current_dir = env::current_dir().unwrap();
-        if parent_path.to_str() == Some("") {
-            parent_path = &current_dir;
-        }
-        let working_directory = fs::canonicalize(parent_path)
-            .await
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-
-        create_service_file(
-            &service_file_path_str,
-            &working_directory,
-            auto_restart,
-            interpreter,
-            env_vars,
-            internal_args,
-            &file_name,
-        )
-        .await
-        .unwrap();
+        // Read before writing anything, so that a failed prompt doesn't leave a half-made service
+        let mut secret_values = vec![];
+        for secret in &options.secrets {
+            secret_values.push((secret, read_secret_value(secret)?));
+        }
 
-        println!("Service {service_name} created at {service_file_path_str}. To start run `ser start {service_name}`");
+        create_service_file(&service_file).await?;
 
-        if start {
-            handle_start_service(&service_name, false).await.unwrap();
+        let full_service_name = get_full_service_name(&service_name);
+        for (secret, value) in secret_values {
+            if let Err(error) = set_secret(&full_service_name, secret, &value).await {
+                remove_service_files(&full_service_name).await?;
+                return Err(format!("{error}. The service wasn't created").into());
+            }
         }
-        if enable {
-            handle_enable_service(&service_name, false).await.unwrap();
+
+        let connection = get_connection().await?;
+        verify_service_file(&connection, &service_file, None).await?;
+
+        if service_file.timer.is_some() {
+            activate_timer(&ManagerProxy::new(&connection).await?, &full_service_name).await?;
+
+            print_message(&format!("Service {service_name} created at {service_file_path_str} and scheduled with {}. To run it now run `ser run-now {service_name}`", get_full_timer_name(&full_service_name)));
+        } else {
+            print_message(&format!("Service {service_name} created at {service_file_path_str}. To start run `ser start {service_name}`"));
+        }
+
+        if service_file.path_unit.is_some() {
+            activate_watch(&ManagerProxy::new(&connection).await?, &full_service_name).await?;
+
+            print_message(&format!(
+                "{service_name} restarts when its watched paths change"
+            ));
         }
 
-        handle_show_status().await?;
+        if is_user_scope() {
+            offer_lingering().await?;
+        }
+
+        if options.start {
+            handle_start_service(std::slice::from_ref(&service_name), false)
+                .await
+                .unwrap();
+        }
+        if options.enable {
+            handle_enable_service(std::slice::from_ref(&service_name), false)
+                .await
+                .unwrap();
+        }
+
+        handle_show_action_result(ActionResult::new(&service_name, "create", "done")).await?;
     }
 
     Ok(())
 }
 
-/// Find the interpreter needed to execute a file with the given extension
+/// Renders the systemd service file for the given options without writing it. Used by `create`
+/// and by `apply` to compare the desired unit against the one on disk.
 ///
 /// # Arguments
 ///
-/// * `extension`: The file extension
+/// * `options` - Options for the service
 ///
-fn get_interpreter(extension: Option<&std::ffi::OsStr>) -> Option<String> {
-    match extension {
-        Some(extension_os_str) => {
-            let extension_str = extension_os_str
-                .to_str()
-                .expect("failed to stringify extension");
+pub async fn render_service_file(
+    options: &CreateServiceOptions,
+) -> Result<ServiceFile, Box<dyn std::error::Error>> {
+    for tag in &options.tags {
+        validate_tag(tag)?;
+    }
 
-            let interpreter = match extension_str {
-              "js" => "node",
-              "py" => "python3",
-              _ => panic!("No interpeter found for extension {}. Please provide a custom interpeter and try again.", extension_str)
-          };
+    for secret in &options.secrets {
+        validate_secret_name(secret)?;
+    }
+
+    options.limits.validate()?;
+    options.restart.validate()?;
+
+    let user = match &options.user {
+        Some(user) => user.clone(),
+        None => get_service_user()?,
+    };
 
-            Some(interpreter.to_string())
+    let (default_name, mut command) = match (&options.path, &options.command) {
+        (Some(path), None) => {
+            let (file_name, command) =
+                get_file_command(path, options.interpreter.as_deref(), &user).await?;
+
+            (Some(file_name), command)
+        }
+        (None, Some(command)) => {
+            let command =
+                get_command_line(command, options.working_directory.as_deref(), &user).await?;
+
+            (None, command)
+        }
+        _ => return Err("Provide either a file path or --cmd".into()),
+    };
+
+    command.args.extend(options.internal_args.iter().cloned());
+
+    let service_name = options
+        .name
+        .clone()
+        .or(default_name)
+        .ok_or("Provide a service name with --name")?;
+    let full_service_name = get_full_service_name(&service_name);
+    let service_file_path = get_service_file_path(&full_service_name);
+
+    // Set before the user's variables so that they can override PATH
+    let env_vars = merge_env_vars(
+        command
+            .path_env
+            .iter()
+            .map(|path_env| ("PATH".to_string(), path_env.clone()))
+            .chain(options.env_vars.iter().cloned()),
+    );
+
+    let mut env_files = vec![];
+
+    match &options.env_file {
+        Some(env_file) => {
+            let env_file = fs::canonicalize(env_file)
+                .await
+                .map_err(|error| format!("Invalid env file {}: {error}", env_file.display()))?;
+
+            env_files.push(get_environment_file_value(&env_file, false));
+        }
+        None => {
+            let env_file = Path::new(&command.working_directory).join(".env");
+
+            if env_file.is_file() {
+                print_message(&format!("Loading environment from {}", env_file.display()));
+                env_files.push(get_environment_file_value(&env_file, true));
+            }
         }
-        None => None,
     }
+
+    // Loaded last so that variables set with `ser env` take priority
+    env_files.push(get_environment_file_value(
+        &get_env_file_path(&service_name),
+        true,
+    ));
+
+    // Only asked when needed, since `apply` renders services before connecting
+    let supports_backoff = if options.restart.has_backoff() {
+        let connection = get_connection().await?;
+        let version = ManagerProxy::new(&connection).await?.version().await?;
+        let supports_backoff = supports_backoff(&version);
+
+        if !supports_backoff {
+            print_message(&format!(
+                "Skipping --restart-steps and --restart-max-delay. systemd {version} doesn't support them, they need systemd {BACKOFF_MIN_VERSION} or newer"
+            ));
+        }
+
+        supports_backoff
+    } else {
+        false
+    };
+
+    let mut directives = options.restart.get_service_directives(supports_backoff);
+    directives.extend(options.limits.get_directives());
+
+    if let Some(hardening) = options.hardening {
+        directives.extend(get_hardening_directives(
+            hardening,
+            &command.working_directory,
+            &command.program,
+        )?);
+    }
+
+    let mut unit_directives = options.restart.get_unit_directives();
+
+    if !options.tags.is_empty() {
+        unit_directives.push((TAGS_KEY.to_string(), options.tags.join(" ")));
+    }
+
+    let timer = match &options.schedule {
+        Some(schedule) => {
+            // systemd refuses to restart one-shot services after a clean exit
+            if options.restart.policy == Some(RestartPolicy::Always) {
+                return Err(
+                    "Scheduled services can't always restart. Use `--restart on-failure`".into(),
+                );
+            }
+
+            if options.enable {
+                return Err(
+                    "Scheduled services are started by their timer and can't be enabled".into(),
+                );
+            }
+
+            let on_calendar = parse_schedule(schedule).await?;
+            Some(get_timer_file_body(&full_service_name, &on_calendar))
+        }
+        None => None,
+    };
+
+    let path_unit = if options.watch.is_empty() {
+        None
+    } else {
+        if timer.is_some() {
+            return Err("Scheduled services can't be watched. They run on every start".into());
+        }
+
+        let paths = resolve_watch_paths(&options.watch, &env::current_dir()?).await?;
+        Some(get_path_unit_body(&full_service_name, &paths))
+    };
+
+    let body = get_service_file_body(
+        &user,
+        &command,
+        &unit_directives,
+        &env_vars,
+        &env_files,
+        &directives,
+        timer.is_some(),
+    );
+
+    let mut argv = vec![command.program];
+    argv.extend(command.args);
+
+    Ok(ServiceFile {
+        service_name,
+        path: service_file_path,
+        body,
+        timer,
+        path_unit,
+        argv,
+        environment: env_vars
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect(),
+    })
 }
 
-/// Creates a systemd service file at `/etc/systemd/system/{}.ser.service` and returns the unit name
+/// Builds the command to run a file with its interpreter. Returns the file name with the command.
 ///
 /// # Arguments
 ///
-/// * `service_name`- Name of the service without '.ser.service' in the end
-/// * `service_file_path` - Path where the service file will be written
-/// * `working_directory` - Working directory of the file to execute
-/// * `auto_restart` - Auto restart the service on error
-/// * `interpreter` - The executable used to run the app, eg. `node` or `python3`. The executable
-/// must be visible from path for a sudo user. Note that the app itself does not run in sudo.
-/// * `env_vars` - Environment variables
-/// * `internal_args` - Args passed to the file
-/// * `file_name` - Name of the file to run
-///
-async fn create_service_file(
-    service_file_path: &str,
-    working_directory: &str,
-    auto_restart: bool,
-    interpreter: Option<String>,
-    env_vars: Option<String>,
-    internal_args: Vec<String>,
-    file_name: &str,
-) -> std::io::Result<()> {
-    // This gets `root` instead of `hp` if sudo is used
-    let user =
-        env::var("SUDO_USER").expect("Must be in sudo mode. ENV variable $SUDO_USER not found");
-    let mut exec_start = match interpreter {
+/// * `path` - The file to run
+/// * `custom_interpreter` - Interpreter passed with `--interpreter`. Detected if `None`.
+/// * `user` - The user running the service
+///
+async fn get_file_command(
+    path: &Path,
+    custom_interpreter: Option<&str>,
+    user: &str,
+) -> Result<(String, ServiceCommand), Box<dyn std::error::Error>> {
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.to_str().unwrap()).into());
+    }
+
+    // The file name including extension, eg. index.js
+    let file_name = path
+        .file_name()
+        .expect("Failed to get file name")
+        .to_str()
+        .expect("Failed to stringify file name")
+        .to_string();
+
+    let interpreter = match custom_interpreter {
+        Some(command) => Some(Interpreter::parse(command)?),
+        None => detect_interpreter(path).await?,
+    };
+
+    // Handle case `ser create index.js` where relative path lacks ./
+    let mut parent_path = path.parent().unwrap();
+    let current_dir = env::current_dir().unwrap();
+    if parent_path.to_str() == Some("") {
+        parent_path = &current_dir;
+    }
+    let working_directory = fs::canonicalize(parent_path)
+        .await?
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // Prefer the project's virtualenv or pinned version over the user's default interpreter
+    let interpreter = match interpreter {
+        Some(interpreter) if custom_interpreter.is_none() => {
+            let account = get_user_account(user).ok_or(format!("User {user} not found"))?;
+
+            Some(
+                find_project_interpreter(
+                    &interpreter,
+                    Path::new(&working_directory),
+                    &account.home,
+                )
+                .unwrap_or(interpreter),
+            )
+        }
+        interpreter => interpreter,
+    };
+
+    let command = match interpreter {
         Some(interpreter) => {
-            let interpreter_path = find_binary_path(&interpreter, &user)
+            let interpreter_path = find_binary_path(&interpreter.program, user)
                 .await
-                .unwrap()
-                .trim_end_matches("\n")
-                .to_string();
+                .map_err(|error| error.to_string())?;
 
-            println!("got path {}", interpreter_path);
+            print_message(&format!("got path {}", interpreter_path.display()));
 
-            format!("{} {}", interpreter_path, file_name)
+            let comment = match &interpreter.origin {
+                Some(origin) => {
+                    format!(
+                        "# Interpreter: {} (from {origin})",
+                        interpreter_path.display()
+                    )
+                }
+                None => format!("# Interpreter: {}", interpreter_path.display()),
+            };
+
+            let mut args = interpreter.args;
+            args.push(file_name.clone());
+
+            ServiceCommand {
+                working_directory,
+                program: interpreter_path.display().to_string(),
+                args,
+                path_env: get_path_env(&interpreter_path),
+                comment,
+            }
         }
-        None => file_name.to_string(),
+        // Executables run by themselves. systemd needs an absolute path.
+        None => ServiceCommand {
+            program: format!("{working_directory}/{file_name}"),
+            working_directory,
+            args: vec![],
+            path_env: None,
+            comment: "".to_string(),
+        },
     };
 
-    for arg in internal_args {
-        exec_start = format!("{} {}", exec_start, arg);
+    Ok((file_name, command))
+}
+
+/// Builds the command for a command line like `gunicorn app:app -w 4`. The program is resolved to
+/// an absolute path.
+///
+/// # Arguments
+///
+/// * `command_line` - Words are split like a shell does, so quoted args may have spaces
+/// * `working_directory` - Folder the command runs in. Defaults to the current folder.
+/// * `user` - The user running the service
+///
+async fn get_command_line(
+    command_line: &str,
+    working_directory: Option<&Path>,
+    user: &str,
+) -> Result<ServiceCommand, Box<dyn std::error::Error>> {
+    let mut words = shell_words::split(command_line)
+        .map_err(|error| format!("Invalid command `{command_line}`: {error}"))?
+        .into_iter();
+
+    let program = words.next().ok_or("The command can't be empty")?;
+
+    let working_directory = match working_directory {
+        Some(working_directory) => fs::canonicalize(working_directory)
+            .await
+            .map_err(|error| format!("Invalid --cwd {}: {error}", working_directory.display()))?,
+        None => env::current_dir()?,
+    };
+
+    // Relative paths like `./bin/server` are resolved from the working directory
+    let is_path = program.contains('/');
+    let program = if is_path {
+        working_directory
+            .join(program.trim_start_matches("./"))
+            .display()
+            .to_string()
+    } else {
+        program
+    };
+
+    let program_path = find_binary_path(&program, user)
+        .await
+        .map_err(|error| error.to_string())?;
+
+    print_message(&format!("got path {}", program_path.display()));
+
+    Ok(ServiceCommand {
+        working_directory: working_directory.to_str().unwrap().to_string(),
+        program: program_path.display().to_string(),
+        args: words.collect(),
+        path_env: if is_path {
+            None
+        } else {
+            get_path_env(&program_path)
+        },
+        comment: "".to_string(),
+    })
+}
+
+/// Writes a rendered systemd service file, usually at `/etc/systemd/system/{}.ser.service`, or at
+/// `~/.config/systemd/user/{}.ser.service` for user services. The timer of a scheduled service, or
+/// the path unit of a watched service, is written next to it.
+///
+/// # Arguments
+///
+/// * `service_file` - The rendered service file
+///
+pub async fn create_service_file(service_file: &ServiceFile) -> std::io::Result<()> {
+    // The user unit folder may not exist yet
+    if let Some(unit_folder) = service_file.path.parent() {
+        fs::create_dir_all(unit_folder).await?;
+    }
+
+    fs::write(&service_file.path, service_file.body.as_bytes()).await?;
+
+    let full_service_name = get_full_service_name(&service_file.service_name);
+
+    if let Some(timer) = &service_file.timer {
+        fs::write(get_timer_file_path(&full_service_name), timer.as_bytes()).await?;
+    }
+
+    if let Some(path_unit) = &service_file.path_unit {
+        write_watch_units(&full_service_name, path_unit).await?;
     }
 
-    let env_vars_formatted = match env_vars {
-        Some(vars) => {
-            // Split the input string by whitespace
-            let pairs: Vec<&str> = vars.split_whitespace().collect();
+    Ok(())
+}
+
+/// Generates the contents of a systemd service file
+///
+/// # Arguments
+///
+/// * `user` - The user running the service
+/// * `command` - The command run by the service
+/// * `unit_directives` - Other `[Unit]` directives as key and value pairs, eg. start limits and tags
+/// * `env_vars` - Environment variables as key and value pairs
+/// * `env_files` - Values for `EnvironmentFile=`
+/// * `directives` - Other `[Service]` directives as key and value pairs, eg. limits and sandboxing
+/// * `scheduled` - Whether the service is started by a timer. Scheduled services run once per start
+///   and aren't started on boot.
+///
+fn get_service_file_body(
+    user: &str,
+    command: &ServiceCommand,
+    unit_directives: &[(String, String)],
+    env_vars: &[(String, String)],
+    env_files: &[String],
+    directives: &[(String, String)],
+    scheduled: bool,
+) -> String {
+    let mut argv = vec![command.program.clone()];
+    argv.extend(command.args.iter().cloned());
+    let exec_start = quote_exec_args(&argv);
 
-            // Format each pair as "Environment=key=value"
-            let formatted_pairs: Vec<String> = pairs
+    let env_vars_formatted = env_vars
+        .iter()
+        .map(|(key, value)| format!("Environment={}", quote_environment(key, value)))
+        .chain(
+            env_files
                 .iter()
-                .map(|pair| format!("Environment={}", pair))
-                .collect();
+                .map(|env_file| format!("EnvironmentFile={env_file}")),
+        )
+        .collect::<Vec<String>>()
+        .join("\n");
 
-            // Join the formatted pairs with newlines
-            let result = formatted_pairs.join("\n");
+    let unit_directives_formatted = unit_directives
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<String>>()
+        .join("\n");
 
-            result
-        }
-        None => "".to_string(),
+    let directives_formatted = directives
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    // User services always run as the user owning the service manager
+    let user_directive = if is_user_scope() {
+        "".to_string()
+    } else {
+        format!("User={user}")
+    };
+    // The timer is started on boot instead
+    let (service_type, install_section) = if scheduled {
+        ("oneshot", "".to_string())
+    } else {
+        (
+            "simple",
+            format!("\n[Install]\nWantedBy={}", get_default_target()),
+        )
     };
 
-    let restart_policy = if auto_restart { "Restart=always" } else { "" };
+    let comment = &command.comment;
+    let working_directory = escape_specifiers(&command.working_directory);
 
     // Replacement for format!(). This proc macro removes spaces produced by indentation.
-    let service_body = formatdoc! {
+    formatdoc! {
         r#"
       # Generated with Servicer
+      {comment}
       [Unit]
       After=network.target
+      {unit_directives_formatted}
 
       [Service]
-      Type=simple
-      User={user}
+      Type={service_type}
+      {user_directive}
 
       WorkingDirectory={working_directory}
       ExecStart={exec_start}
-      {restart_policy}
       {env_vars_formatted}
-
-      [Install]
-      WantedBy=multi-user.target
+      {directives_formatted}
+      {install_section}
       "#
+    }
+}
+
+/// Checks that systemd parses the command line and environment of a written service file exactly
+/// as passed by the user. Reloads the service manager to pick up the file. On a mismatch the
+/// previous service file is restored, or a new service is removed, so that no broken unit is left
+/// behind.
+///
+/// # Arguments
+///
+/// * `connection` - zbus connection
+/// * `service_file` - The written service file
+/// * `previous_body` - Contents of the service file before it was written. `None` for new services
+///
+pub async fn verify_service_file(
+    connection: &Connection,
+    service_file: &ServiceFile,
+    previous_body: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manager_proxy = ManagerProxy::new(connection).await?;
+    manager_proxy.reload().await?;
+
+    let full_service_name = get_full_service_name(&service_file.service_name);
+    let exec_settings = get_exec_settings(connection, &full_service_name).await?;
+
+    // `$$` is only unescaped when the command runs
+    let argv: Vec<Vec<String>> = exec_settings
+        .argv
+        .iter()
+        .map(|args| args.iter().map(|arg| arg.replace("$$", "$")).collect())
+        .collect();
+
+    let mismatch = if argv != [service_file.argv.clone()] {
+        format!(
+            "systemd parsed ExecStart of {} as {:?} instead of {:?}",
+            service_file.path.display(),
+            argv,
+            service_file.argv
+        )
+    } else if exec_settings.environment != service_file.environment {
+        format!(
+            "systemd parsed Environment of {} as {:?} instead of {:?}",
+            service_file.path.display(),
+            exec_settings.environment,
+            service_file.environment
+        )
+    } else {
+        return Ok(());
     };
 
-    // Create the service file and write the content
-    fs::write(service_file_path, service_body.as_bytes()).await
+    match previous_body {
+        Some(previous_body) => {
+            fs::write(&service_file.path, previous_body).await?;
+            manager_proxy.reload().await?;
+
+            Err(format!("{mismatch}. The previous service file was restored").into())
+        }
+        None => {
+            remove_service_files(&full_service_name).await?;
+            manager_proxy.reload().await?;
+
+            Err(format!("{mismatch}. The service wasn't created").into())
+        }
+    }
+}
+
+/// Removes the files of a service that was just written: the service file, its drop-ins, timer
+/// and path unit
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+async fn remove_service_files(full_service_name: &str) -> io::Result<()> {
+    for path in [
+        get_service_file_path(full_service_name),
+        get_timer_file_path(full_service_name),
+        get_path_unit_file_path(full_service_name),
+        get_restarter_file_path(full_service_name),
+    ] {
+        if path.exists() {
+            fs::remove_file(&path).await?;
+        }
+    }
+
+    let drop_in_folder = get_drop_in_folder(full_service_name);
+    if drop_in_folder.exists() {
+        fs::remove_dir_all(&drop_in_folder).await?;
+    }
+
+    Ok(())
+}
+
+/// User services are stopped on logout and don't start on boot unless lingering is enabled for the
+/// user. Offers to enable lingering if it is off. Only a hint is printed when the prompt can't be
+/// answered, i.e. with structured output or without a terminal.
+pub async fn offer_lingering() -> Result<(), Box<dyn std::error::Error>> {
+    let user = get_service_user()?;
+
+    if is_lingering(&user) {
+        return Ok(());
+    }
+
+    let warning = format!(
+        "Lingering is disabled for {user}. Services stop on logout and won't start on boot"
+    );
+
+    if is_structured_output() || !io::stdin().is_terminal() {
+        eprintln!("{warning}. Enable it with `loginctl enable-linger {user}`");
+        return Ok(());
+    }
+
+    eprint!("{warning}. Enable lingering? [y/N] ");
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    if answer.trim().eq_ignore_ascii_case("y") {
+        let status = Command::new("loginctl")
+            .arg("enable-linger")
+            .arg(&user)
+            .status()
+            .await?;
+
+        if status.success() {
+            print_message(&format!("Enabled lingering for {user}"));
+        } else {
+            eprintln!("Failed to enable lingering. Run `loginctl enable-linger {user}` manually");
+        }
+    }
+
+    Ok(())
 }
@@ -1,35 +1,147 @@
-use crate::utils::service_names::{get_full_service_name, get_service_file_path};
+use futures::future::join_all;
+use std::io::{self, IsTerminal, Write};
 
-use super::{
-    handle_disable_service::handle_disable_service, handle_show_status::handle_show_status,
-    handle_stop_service::handle_stop_service,
+use crate::utils::{
+    env_file::get_env_file_path,
+    output::{is_structured_output, print_message, ActionResult},
+    schedule::deactivate_timer,
+    service_actions::{disable_service, stop_service},
+    service_names::{
+        get_drop_in_folder, get_full_service_name, get_path_unit_file_path, get_service_file_path,
+        get_timer_file_path, resolve_service_names,
+    },
+    systemd::{get_connection, ManagerProxy},
+    watch::remove_watch,
 };
 
-/// Deletes a service, stopping and disabling it if necessary and removing the .service file
+use super::handle_show_status::handle_action_results;
+
+/// Deletes services concurrently, stopping and disabling them if necessary and removing the
+/// .service files, their timers, path units, drop-ins and env files. When more than one service
+/// matches, they are listed and deleted after confirming, or right away with `yes`.
 ///
 /// # Arguments
 ///
-/// * `name`- Name of the service to stop
+/// * `names`- Service names, `all` or glob patterns
+/// * `show_status` - Display the results and the status table
+/// * `yes` - Delete several services without asking
 ///
 pub async fn handle_delete_service(
-    name: &String,
+    names: &[String],
     show_status: bool,
+    yes: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    handle_stop_service(&name, false).await?;
-    handle_disable_service(&name, false).await?;
+    let names = resolve_service_names(names).await?;
+
+    if names.len() > 1 && !yes && !confirm_delete(&names)? {
+        eprintln!("Delete operation canceled. No services were deleted.");
+        return Ok(());
+    }
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    let manager_proxy = &manager_proxy;
+    let results = join_all(names.iter().map(|name| async move {
+        let full_service_name = get_full_service_name(name);
+
+        // Stop the timer first so that it doesn't start the service again
+        let timer_file_path = get_timer_file_path(&full_service_name);
+        if timer_file_path.exists() {
+            if let Err(error) = deactivate_timer(manager_proxy, &full_service_name).await {
+                eprintln!("Failed to stop the timer of {name}: {error}");
+                return ActionResult::new(name, "delete", "error");
+            }
+
+            if let Err(error) = tokio::fs::remove_file(&timer_file_path).await {
+                eprintln!("Failed to delete {}: {error}", timer_file_path.display());
+                return ActionResult::new(name, "delete", "error");
+            }
+            print_message(&format!("Deleted {}", timer_file_path.display()));
+        }
+
+        // Stop watching first so that a change doesn't restart the service
+        if get_path_unit_file_path(&full_service_name).exists() {
+            if let Err(error) = remove_watch(manager_proxy, &full_service_name).await {
+                eprintln!("Failed to stop watching {name}: {error}");
+                return ActionResult::new(name, "delete", "error");
+            }
+        }
+
+        match stop_service(manager_proxy, &full_service_name).await {
+            Ok(result) if result == "done" => {}
+            Ok(result) => return ActionResult::new(name, "delete", &result),
+            Err(error) => {
+                eprintln!("Failed to stop {name}: {error}");
+                return ActionResult::new(name, "delete", "error");
+            }
+        }
+
+        if let Err(error) = disable_service(manager_proxy, &full_service_name).await {
+            eprintln!(
+                "Failed to disable service {full_service_name}: {error}. Retry in sudo mode."
+            );
+            return ActionResult::new(name, "delete", "error");
+        }
+
+        // Delete .service file
+        let service_file_path = get_service_file_path(&full_service_name);
+
+        if let Err(error) = tokio::fs::remove_file(&service_file_path).await {
+            eprintln!("Failed to delete {}: {error}", service_file_path.display());
+            return ActionResult::new(name, "delete", "error");
+        }
+        print_message(&format!("Deleted {}", service_file_path.display()));
 
-    let full_service_name = get_full_service_name(&name);
-    let service_file_path = get_service_file_path(&full_service_name);
-    let service_file_path_str = service_file_path.to_str().unwrap().to_string();
+        // Delete drop-ins and the env file
+        let drop_in_folder = get_drop_in_folder(&full_service_name);
+        if drop_in_folder.exists() {
+            if let Err(error) = tokio::fs::remove_dir_all(&drop_in_folder).await {
+                eprintln!("Failed to delete {}: {error}", drop_in_folder.display());
+                return ActionResult::new(name, "delete", "error");
+            }
+        }
 
-    // Delete .service file
-    tokio::fs::remove_file(&service_file_path).await?;
+        let env_file_path = get_env_file_path(name);
+        if env_file_path.exists() {
+            if let Err(error) = tokio::fs::remove_file(&env_file_path).await {
+                eprintln!("Failed to delete {}: {error}", env_file_path.display());
+                return ActionResult::new(name, "delete", "error");
+            }
+        }
 
-    println!("Deleted {service_file_path_str}");
+        ActionResult::new(name, "delete", "done")
+    }))
+    .await;
 
-    if show_status {
-        handle_show_status().await?;
+    // Reload so that systemd forgets the deleted units
+    manager_proxy.reload().await?;
+
+    handle_action_results(&results, show_status).await
+}
+
+/// Lists the services about to be deleted and asks for confirmation. Errors if the prompt can't be
+/// answered, i.e. with structured output or without a terminal.
+///
+/// # Arguments
+///
+/// * `names` - The matched service names
+///
+fn confirm_delete(names: &[String]) -> Result<bool, Box<dyn std::error::Error>> {
+    eprintln!("Matched {} services:", names.len());
+    for name in names {
+        eprintln!("  {name}");
+    }
+
+    if is_structured_output() || !io::stdin().is_terminal() {
+        return Err(format!("Pass --yes to delete {} services", names.len()).into());
     }
 
-    Ok(())
+    eprint!("Delete {} services? [y/N] ", names.len());
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
 }
@@ -1,32 +1,50 @@
-use crate::handlers::handle_show_status::handle_show_status;
+use futures::future::join_all;
+
+use crate::handlers::handle_show_status::handle_action_results;
+use crate::utils::output::{print_message, ActionResult};
 use crate::utils::service_actions::disable_service;
-use crate::utils::{service_names::get_full_service_name, systemd::ManagerProxy};
+use crate::utils::{
+    service_names::{get_full_service_name, resolve_service_names},
+    systemd::{get_connection, ManagerProxy},
+};
 
-/// Disables a service from starting on boot
+/// Disables services from starting on boot
 ///
 /// # Arguments
 ///
-/// * `name`- Name of the service to disable
+/// * `names`- Service names, `all` or glob patterns
+/// * `show_status` - Display the results and the status table
 ///
 pub async fn handle_disable_service(
-    name: &String,
+    names: &[String],
     show_status: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let full_service_name = get_full_service_name(&name);
+    let names = resolve_service_names(names).await?;
 
-    let connection = zbus::Connection::system().await?;
+    let connection = get_connection().await?;
     let manager_proxy = ManagerProxy::new(&connection).await?;
 
-    disable_service(&manager_proxy, &full_service_name).await;
+    let manager_proxy = &manager_proxy;
+    let results = join_all(names.iter().map(|name| async move {
+        let full_service_name = get_full_service_name(name);
+
+        match disable_service(manager_proxy, &full_service_name).await {
+            Ok(_) => {
+                print_message(&format!("Disabled {name}"));
+                ActionResult::new(name, "disable", "done")
+            }
+            Err(error) => {
+                eprintln!(
+                    "Failed to disable service {full_service_name}: {error}. Retry in sudo mode."
+                );
+                ActionResult::new(name, "disable", "error")
+            }
+        }
+    }))
+    .await;
 
     // Reload necessary for UnitFileState to update
     manager_proxy.reload().await?;
 
-    println!("Disabled {name}");
-
-    if show_status {
-        handle_show_status().await?;
-    }
-
-    Ok(())
+    handle_action_results(&results, show_status).await
 }
@@ -1,6 +1,12 @@
-use std::path::PathBuf;
-
-use crate::utils::service_names::{get_full_service_name, get_service_file_path};
+use std::path::{Path, PathBuf};
+
+use crate::utils::{
+    scope::get_default_target,
+    service_names::{
+        check_service_exists, get_drop_in_folder, get_drop_in_paths, get_full_service_name,
+        get_service_file_path,
+    },
+};
 use tempfile::Builder;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
@@ -20,21 +26,34 @@ Restart=always
 # ExecReload=
 
 [Install]
-WantedBy=multi-user.target
+WantedBy={default_target}
 "#;
 
-/// Opens an text editor to create or update a service file
+const DROP_IN_TEMPLATE: &str = r#"
+# Generated with servicer
+# Settings here override the service file
+[Service]
+"#;
+
+/// Opens an text editor to create or update a service file, or one of its drop-ins
 ///
 /// # Arguments
 ///
 /// * `name`- Name of the service to edit
 /// * `editor` - Name of editor. The editor must be visible in path
+/// * `drop_in` - Edit this drop-in of the service instead, eg. `limits.conf`. Created if missing
 ///
 pub async fn handle_edit_service_file(
-    name: &String,
-    editor: &String,
+    name: &str,
+    editor: &str,
+    drop_in: Option<&str>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let full_service_name = get_full_service_name(name);
+
+    if let Some(drop_in) = drop_in {
+        return edit_drop_in(&full_service_name, editor, drop_in).await;
+    }
+
     let service_file_path = get_service_file_path(&full_service_name);
 
     if service_file_path.exists() {
@@ -48,21 +67,14 @@ pub async fn handle_edit_service_file(
         } else {
             eprintln!("Edit operation canceled. No changes were saved.");
         }
-    } else {
-        // Write the template content to a temporary file
-        let temp_file = Builder::new().prefix(&full_service_name).tempfile()?;
-        let temp_file_path = temp_file.path().to_owned();
-
-        let mut file = fs::File::create(&temp_file_path).await?;
-        file.write_all(SERVICE_TEMPLATE.as_bytes()).await?;
 
-        // Prompt user to edit
-        let edit_success = edit_file(&editor, &temp_file_path).await?;
+        print_drop_ins(name, &full_service_name).await?;
+    } else {
+        let template = SERVICE_TEMPLATE.replace("{default_target}", get_default_target());
+        let edit_success =
+            edit_new_file(editor, &full_service_name, &service_file_path, &template).await?;
 
         if edit_success {
-            // Copy the content of the temporary file to the target location
-            fs::copy(&temp_file_path, &service_file_path).await?;
-
             println!(
                 "Service file {} created.",
                 service_file_path.to_str().unwrap()
@@ -70,14 +82,120 @@ pub async fn handle_edit_service_file(
         } else {
             eprintln!("Create operation canceled. No changes were saved.");
         }
+    }
+
+    Ok(())
+}
+
+/// Opens an editor for a drop-in of a service, eg. the `secrets.conf`, `env.conf` or `limits.conf`
+/// drop-ins written by servicer
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `editor`
+/// * `drop_in` - File name of the drop-in. `.conf` is added if missing
+///
+async fn edit_drop_in(
+    full_service_name: &str,
+    editor: &str,
+    drop_in: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_service_exists(full_service_name)?;
+
+    if drop_in.is_empty() || drop_in.contains('/') {
+        return Err(format!("Invalid drop-in {drop_in}. Pass a file name like limits.conf").into());
+    }
+
+    let file_name = if drop_in.ends_with(".conf") {
+        drop_in.to_string()
+    } else {
+        format!("{drop_in}.conf")
+    };
+    let drop_in_folder = get_drop_in_folder(full_service_name);
+    let drop_in_path = drop_in_folder.join(&file_name);
+
+    if drop_in_path.exists() {
+        if edit_file(editor, &drop_in_path).await? {
+            println!("Drop-in {} edited successfully.", drop_in_path.display());
+        } else {
+            eprintln!("Edit operation canceled. No changes were saved.");
+        }
+    } else if edit_new_file(editor, &file_name, &drop_in_path, DROP_IN_TEMPLATE).await? {
+        println!("Drop-in {} created.", drop_in_path.display());
+    } else {
+        eprintln!("Create operation canceled. No changes were saved.");
+    }
 
-        // Remove the temporary file
-        fs::remove_file(&temp_file_path).await?;
+    Ok(())
+}
+
+/// Prints the drop-ins overriding a service file, so that edits to the service file aren't
+/// silently overridden
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+async fn print_drop_ins(name: &str, full_service_name: &str) -> std::io::Result<()> {
+    let drop_ins: Vec<String> = get_drop_in_paths(full_service_name)
+        .await?
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .collect();
+
+    if !drop_ins.is_empty() {
+        eprintln!(
+            "Drop-ins override the service file: {}. Edit them with `ser edit {name} --drop-in <file>`",
+            drop_ins.join(", ")
+        );
     }
 
     Ok(())
 }
 
+/// Prompts the user to edit a template in a temporary file, and copies it to `path` if it was
+/// edited. Returns true if the file was created.
+///
+/// # Args
+///
+/// * `editor`
+/// * `prefix` - Prefix of the temporary file name
+/// * `path` - Where the file is created
+/// * `template` - Initial contents of the file
+///
+async fn edit_new_file(
+    editor: &str,
+    prefix: &str,
+    path: &Path,
+    template: &str,
+) -> Result<bool, std::io::Error> {
+    // Write the template content to a temporary file
+    let temp_file = Builder::new().prefix(prefix).tempfile()?;
+    let temp_file_path = temp_file.path().to_owned();
+
+    let mut file = fs::File::create(&temp_file_path).await?;
+    file.write_all(template.as_bytes()).await?;
+
+    // Prompt user to edit
+    let edit_success = edit_file(editor, &temp_file_path).await?;
+
+    if edit_success {
+        // Copy the content of the temporary file to the target location
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder).await?;
+        }
+        fs::copy(&temp_file_path, path).await?;
+    }
+
+    // Remove the temporary file
+    fs::remove_file(&temp_file_path).await?;
+
+    Ok(edit_success)
+}
+
 /// Prompt the user to edit the file. Returns true if the file editor command exits successfully
 /// and the file's `modified` time updates.
 ///
@@ -88,7 +206,7 @@ pub async fn handle_edit_service_file(
 ///
 async fn edit_file(editor: &str, path: &PathBuf) -> Result<bool, std::io::Error> {
     let orig_mod_time = fs::metadata(path).await?.modified()?;
-    let edit_status = tokio::process::Command::new(&editor)
+    let edit_status = tokio::process::Command::new(editor)
         .arg(path)
         .status()
         .await?;
@@ -1,32 +1,50 @@
-use crate::handlers::handle_show_status::handle_show_status;
+use futures::future::join_all;
+
+use crate::handlers::handle_show_status::handle_action_results;
+use crate::utils::output::{print_message, ActionResult};
 use crate::utils::service_actions::enable_service;
-use crate::utils::{service_names::get_full_service_name, systemd::ManagerProxy};
+use crate::utils::{
+    service_names::{get_full_service_name, resolve_service_names},
+    systemd::{get_connection, ManagerProxy},
+};
 
-/// Enables a service to start on boot
+/// Enables services to start on boot
 ///
 /// # Arguments
 ///
-/// * `name`- Name of the service to stop
+/// * `names`- Service names, `all` or glob patterns
+/// * `show_status` - Display the results and the status table
 ///
 pub async fn handle_enable_service(
-    name: &String,
+    names: &[String],
     show_status: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let full_service_name = get_full_service_name(&name);
+    let names = resolve_service_names(names).await?;
 
-    let connection = zbus::Connection::system().await?;
+    let connection = get_connection().await?;
     let manager_proxy = ManagerProxy::new(&connection).await?;
 
-    enable_service(&manager_proxy, &full_service_name).await;
+    let manager_proxy = &manager_proxy;
+    let results = join_all(names.iter().map(|name| async move {
+        let full_service_name = get_full_service_name(name);
+
+        match enable_service(manager_proxy, &full_service_name).await {
+            Ok(_) => {
+                print_message(&format!("Enabled {name}"));
+                ActionResult::new(name, "enable", "done")
+            }
+            Err(error) => {
+                eprintln!(
+                    "Failed to enable service {full_service_name}: {error}. Retry in sudo mode."
+                );
+                ActionResult::new(name, "enable", "error")
+            }
+        }
+    }))
+    .await;
 
     // Reload necessary for UnitFileState to update
     manager_proxy.reload().await?;
 
-    println!("Enabled {name}");
-
-    if show_status {
-        handle_show_status().await?;
-    }
-
-    Ok(())
+    handle_action_results(&results, show_status).await
 }
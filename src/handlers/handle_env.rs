@@ -0,0 +1,129 @@
+use cli_table::Table;
+use serde::Serialize;
+
+use crate::utils::{
+    env_file::{ensure_env_file_loaded, get_env_file_path, read_env_file, write_env_file},
+    output::{print_message, print_rows},
+    service_names::{check_service_exists, get_full_service_name},
+    systemd::{get_connection, ManagerProxy},
+};
+
+use super::handle_restart_service::handle_offer_restart;
+
+/// A variable in the env file of a service
+#[derive(Table, Serialize)]
+struct EnvVar {
+    /// The variable name
+    key: String,
+
+    /// The variable value
+    value: String,
+}
+
+/// Prints the variables in the env file of a service
+///
+/// # Arguments
+///
+/// * `name` - The service name
+///
+pub async fn handle_env_list(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    check_service_exists(&get_full_service_name(name))?;
+
+    let rows: Vec<EnvVar> = read_env_file(&get_env_file_path(name))
+        .await?
+        .into_iter()
+        .map(|(key, value)| EnvVar { key, value })
+        .collect();
+
+    print_rows(&rows)
+}
+
+/// Sets variables in the env file of a service. Existing keys are overwritten.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `vars` - Key and value pairs
+/// * `restart` - Restart a running service without asking
+///
+pub async fn handle_env_set(
+    name: &str,
+    vars: &[(String, String)],
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_service_exists(&get_full_service_name(name))?;
+
+    let env_file_path = get_env_file_path(name);
+    let mut current_vars = read_env_file(&env_file_path).await?;
+
+    for (key, value) in vars {
+        match current_vars
+            .iter_mut()
+            .find(|(current_key, _)| current_key == key)
+        {
+            Some(current_var) => current_var.1 = value.clone(),
+            None => current_vars.push((key.clone(), value.clone())),
+        }
+    }
+
+    write_env_file(&env_file_path, &current_vars).await?;
+    print_message(&format!("Updated {}", env_file_path.display()));
+
+    apply_env_file(name, restart).await
+}
+
+/// Removes variables from the env file of a service
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `keys` - Keys to remove
+/// * `restart` - Restart a running service without asking
+///
+pub async fn handle_env_unset(
+    name: &str,
+    keys: &[String],
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_service_exists(&get_full_service_name(name))?;
+
+    let env_file_path = get_env_file_path(name);
+    let mut current_vars = read_env_file(&env_file_path).await?;
+
+    for key in keys {
+        if !current_vars
+            .iter()
+            .any(|(current_key, _)| current_key == key)
+        {
+            eprintln!("{key} is not set for {name}");
+        }
+    }
+    current_vars.retain(|(key, _)| !keys.contains(key));
+
+    write_env_file(&env_file_path, &current_vars).await?;
+    print_message(&format!("Updated {}", env_file_path.display()));
+
+    apply_env_file(name, restart).await
+}
+
+/// Makes the service load its updated env file. A running service only reads the file on start,
+/// so it is restarted if `restart` is set or the user agrees.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `restart` - Restart a running service without asking
+///
+async fn apply_env_file(name: &str, restart: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+
+    let drop_in_written = ensure_env_file_loaded(name, &full_service_name).await?;
+
+    let connection = get_connection().await?;
+
+    if drop_in_written {
+        ManagerProxy::new(&connection).await?.reload().await?;
+    }
+
+    handle_offer_restart(name, restart).await
+}
@@ -0,0 +1,316 @@
+use futures::{
+    stream::{BoxStream, SelectAll},
+    StreamExt,
+};
+use serde::Serialize;
+use std::{collections::HashMap, process::Stdio, time::SystemTime};
+use tokio::process::Command;
+use zbus::Connection;
+
+use crate::utils::{
+    output::{get_output_format, print_message, OutputFormat},
+    service_names::{
+        get_full_service_name, get_servicer_services, get_short_service_name, is_full_name,
+        resolve_service_names,
+    },
+    systemd::{
+        get_active_state, get_connection, get_service_state, receive_unit_state_changes,
+        ManagerProxy, UnitStateChange,
+    },
+};
+
+/// An event in the life of a service
+#[derive(Serialize)]
+struct ServiceEvent {
+    /// When the event was received, in RFC 3339 format
+    timestamp: String,
+
+    /// The short service name, excluding '.ser.service'
+    name: String,
+
+    /// `state` when the active or sub-state changes, `job-new` when a job is queued and
+    /// `job-removed` when a job completes
+    event: String,
+
+    /// Active state after the event, eg. `active` or `failed`
+    active_state: String,
+
+    /// Sub-state after the event, eg. `running` or `auto-restart`
+    sub_state: String,
+
+    /// ID of the job for `job-new` and `job-removed` events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<u32>,
+
+    /// Result of the job for `job-removed` events, eg. `done` or `failed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_result: Option<String>,
+}
+
+/// A D-Bus signal concerning a unit
+enum UnitSignal {
+    State {
+        full_service_name: String,
+        change: UnitStateChange,
+    },
+    JobNew {
+        full_service_name: String,
+        id: u32,
+    },
+    JobRemoved {
+        full_service_name: String,
+        id: u32,
+        result: String,
+    },
+}
+
+/// Streams state changes and jobs of services till interrupted. Prints a line per event, or a JSON
+/// or YAML document per event.
+///
+/// # Arguments
+///
+/// * `names` - Service names, `all`, `@tag` or glob patterns. Watches every service, including
+///   services created later, if empty.
+/// * `exec` - Command run with `sh -c` on every event. The event is passed in `SER_*` environment
+///   variables.
+///
+pub async fn handle_events(
+    names: &[String],
+    exec: Option<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let watch_new_services = names.is_empty();
+
+    let full_service_names: Vec<String> = if watch_new_services {
+        get_servicer_services(None).await?
+    } else {
+        resolve_service_names(names)
+            .await?
+            .iter()
+            .map(|name| get_full_service_name(name))
+            .collect()
+    };
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    let mut signals: SelectAll<BoxStream<'static, UnitSignal>> = SelectAll::new();
+
+    signals.push(
+        manager_proxy
+            .receive_job_new()
+            .await?
+            .filter_map(|signal| async move {
+                let args = signal.args().ok()?;
+
+                Some(UnitSignal::JobNew {
+                    full_service_name: args.unit().to_string(),
+                    id: *args.id(),
+                })
+            })
+            .boxed(),
+    );
+
+    signals.push(
+        manager_proxy
+            .receive_job_removed()
+            .await?
+            .filter_map(|signal| async move {
+                let args = signal.args().ok()?;
+
+                Some(UnitSignal::JobRemoved {
+                    full_service_name: args.unit().to_string(),
+                    id: *args.id(),
+                    result: args.result().to_string(),
+                })
+            })
+            .boxed(),
+    );
+
+    // Active and sub-state of each watched service
+    let mut states = HashMap::<String, (String, String)>::new();
+
+    for full_service_name in full_service_names {
+        watch_service(&connection, &mut signals, &mut states, full_service_name).await?;
+    }
+
+    // systemd only emits signals once a client subscribes
+    manager_proxy.subscribe().await?;
+
+    print_message(&format!(
+        "Watching {} services. Press Ctrl+C to stop",
+        states.len()
+    ));
+
+    while let Some(signal) = signals.next().await {
+        let (full_service_name, event, job_id, job_result) = match signal {
+            UnitSignal::State {
+                full_service_name,
+                change,
+            } => {
+                let Some(state) = states.get_mut(&full_service_name) else {
+                    continue;
+                };
+
+                let new_state = (
+                    change.active_state.unwrap_or_else(|| state.0.clone()),
+                    change.sub_state.unwrap_or_else(|| state.1.clone()),
+                );
+
+                // systemd also signals changes to other properties, like timestamps
+                if new_state == *state {
+                    continue;
+                }
+                *state = new_state;
+
+                (full_service_name, "state", None, None)
+            }
+            UnitSignal::JobNew {
+                full_service_name,
+                id,
+            } => (full_service_name, "job-new", Some(id), None),
+            UnitSignal::JobRemoved {
+                full_service_name,
+                id,
+                result,
+            } => (full_service_name, "job-removed", Some(id), Some(result)),
+        };
+
+        if !states.contains_key(&full_service_name) {
+            // Services created after the command started are picked up on their first job
+            if !watch_new_services || !is_full_name(&full_service_name) {
+                continue;
+            }
+
+            watch_service(
+                &connection,
+                &mut signals,
+                &mut states,
+                full_service_name.clone(),
+            )
+            .await?;
+        }
+
+        let (active_state, sub_state) = states[&full_service_name].clone();
+
+        let event = ServiceEvent {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            name: get_short_service_name(&full_service_name),
+            event: event.to_string(),
+            active_state,
+            sub_state,
+            job_id,
+            job_result,
+        };
+
+        print_event(&event)?;
+
+        if let Some(command) = &exec {
+            spawn_hook(command, &event);
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts listening to state changes of a service and records its current state
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `signals` - The state change stream is added here
+/// * `states` - The current state is recorded here
+/// * `full_service_name`: Full name of the service, having '.ser.service' at the end
+///
+async fn watch_service(
+    connection: &Connection,
+    signals: &mut SelectAll<BoxStream<'static, UnitSignal>>,
+    states: &mut HashMap<String, (String, String)>,
+    full_service_name: String,
+) -> Result<(), zbus::Error> {
+    let active_state = get_active_state(connection, &full_service_name).await;
+    let sub_state = get_service_state(connection, &full_service_name)
+        .await
+        .map(|state| state.sub_state)
+        .unwrap_or_default();
+
+    let name = full_service_name.clone();
+    signals.push(
+        receive_unit_state_changes(connection, &full_service_name)
+            .await?
+            .map(move |change| UnitSignal::State {
+                full_service_name: name.clone(),
+                change,
+            })
+            .boxed(),
+    );
+
+    states.insert(full_service_name, (active_state, sub_state));
+
+    Ok(())
+}
+
+/// Prints an event as a line, or as a single line JSON object or a YAML document
+///
+/// # Arguments
+///
+/// * `event`
+///
+fn print_event(event: &ServiceEvent) -> Result<(), Box<dyn std::error::Error>> {
+    match get_output_format() {
+        OutputFormat::Table => {
+            let details = match (&event.job_id, &event.job_result) {
+                (Some(job_id), Some(job_result)) => format!("job {job_id} {job_result}"),
+                (Some(job_id), None) => format!("job {job_id}"),
+                _ => format!("{} ({})", event.active_state, event.sub_state),
+            };
+
+            println!(
+                "{} {} {} {details}",
+                event.timestamp, event.name, event.event
+            );
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string(event)?),
+        OutputFormat::Yaml => print!("---\n{}", serde_yaml::to_string(event)?),
+    }
+
+    Ok(())
+}
+
+/// Runs the `--exec` hook for an event in the background. The hook's output goes to stderr so that
+/// stdout only holds events.
+///
+/// # Arguments
+///
+/// * `command` - Run with `sh -c`
+/// * `event`
+///
+fn spawn_hook(command: &str, event: &ServiceEvent) {
+    let mut hook = Command::new("sh");
+    hook.arg("-c")
+        .arg(command)
+        .env("SER_TIMESTAMP", &event.timestamp)
+        .env("SER_SERVICE", &event.name)
+        .env("SER_EVENT", &event.event)
+        .env("SER_ACTIVE_STATE", &event.active_state)
+        .env("SER_SUB_STATE", &event.sub_state)
+        .env(
+            "SER_JOB_ID",
+            event.job_id.map(|id| id.to_string()).unwrap_or_default(),
+        )
+        .env(
+            "SER_JOB_RESULT",
+            event.job_result.clone().unwrap_or_default(),
+        )
+        .stdin(Stdio::null())
+        .stdout(std::io::stderr());
+
+    let command = command.to_string();
+
+    tokio::spawn(async move {
+        match hook.status().await {
+            Ok(status) if !status.success() => eprintln!("Hook `{command}` exited with {status}"),
+            Err(error) => eprintln!("Failed to run hook `{command}`: {error}"),
+            _ => {}
+        }
+    });
+}
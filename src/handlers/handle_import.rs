@@ -0,0 +1,267 @@
+use cli_table::Table;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::{fs, process::Command};
+use zbus::Connection;
+
+use crate::{
+    handlers::handle_create_service::{
+        create_service_file, offer_lingering, render_service_file, verify_service_file,
+        CreateServiceOptions,
+    },
+    utils::{
+        crontab::{parse_crontab, CrontabJob, SkippedLine},
+        limits::ResourceLimits,
+        output::{print_message, print_rows},
+        restart::RestartOptions,
+        schedule::{activate_timer, parse_schedule},
+        scope::{get_current_user, get_service_user, get_user_account, is_user_scope},
+        service_actions::enable_service,
+        service_names::{get_full_service_name, get_service_file_path},
+        systemd::{get_connection, ManagerProxy},
+    },
+};
+
+/// Tag added to imported services, to list them with `ser status --tag cron`
+const IMPORT_TAG: &str = "cron";
+
+/// A crontab job and the service created for it
+#[derive(Table, Serialize)]
+struct ImportRow {
+    /// Line of the job in the crontab
+    line: usize,
+
+    /// Name of the service to create
+    name: String,
+
+    /// The `OnCalendar=` expression of the timer, or `on boot` for `@reboot` jobs
+    schedule: String,
+
+    /// The command line
+    command: String,
+
+    /// What `import` will do with the job, and after importing whether it was created or failed
+    action: String,
+}
+
+/// Converts the jobs of a crontab to scheduled services with timers. `@reboot` jobs become services
+/// enabled on boot. Jobs are named after the program they run, numbered if the name is taken. One
+/// table reporting each job is printed at the end, and lines that can't be converted are reported.
+///
+/// # Arguments
+///
+/// * `crontab_user` - Import the crontab of this user and run the services as them. Defaults to the
+///   user running the services
+/// * `file` - Read a crontab file instead of the user's crontab
+/// * `dry_run` - Only print the preview
+///
+pub async fn handle_import_crontab(
+    crontab_user: Option<&str>,
+    file: Option<&Path>,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let user = match crontab_user {
+        Some(user) if is_user_scope() && get_current_user().as_deref() != Some(user) => {
+            return Err(format!(
+                "User services run as you. Import the crontab of {user} as system services with sudo"
+            )
+            .into())
+        }
+        Some(user) => user.to_string(),
+        None => get_service_user()?,
+    };
+
+    let account =
+        get_user_account(&user).ok_or_else(|| format!("The user {user} doesn't exist"))?;
+
+    let body = match file {
+        Some(file) => fs::read_to_string(file)
+            .await
+            .map_err(|error| format!("Failed to read {}: {error}", file.display()))?,
+        None => read_user_crontab(&user).await?,
+    };
+
+    let crontab = parse_crontab(&body);
+    let mut skipped = crontab.skipped;
+    let mut rows = vec![];
+    let mut imports: Vec<(String, CrontabJob)> = vec![];
+
+    for job in crontab.jobs {
+        let schedule = if job.is_reboot() {
+            "on boot".to_string()
+        } else {
+            match parse_schedule(&job.schedule).await {
+                Ok(on_calendar) => on_calendar,
+                Err(error) => {
+                    skipped.push(SkippedLine {
+                        line_number: job.line_number,
+                        line: format!("{} {}", job.schedule, job.command),
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+            }
+        };
+
+        // Jobs running the same program, or named like an existing service, are numbered, eg.
+        // `backup` and `backup-2`
+        let default_name = job.get_default_name();
+        let mut name = default_name.clone();
+        let mut suffix = 2;
+        while imports.iter().any(|(imported, _)| *imported == name)
+            || get_service_file_path(&get_full_service_name(&name)).exists()
+        {
+            name = format!("{default_name}-{suffix}");
+            suffix += 1;
+        }
+
+        rows.push(ImportRow {
+            line: job.line_number,
+            name: name.clone(),
+            schedule,
+            command: job.command.clone(),
+            action: "create".to_string(),
+        });
+        imports.push((name, job));
+    }
+
+    skipped.sort_by_key(|line| line.line_number);
+    for line in &skipped {
+        eprintln!(
+            "Skipping line {} `{}`: {}",
+            line.line_number, line.line, line.reason
+        );
+    }
+
+    if dry_run || imports.is_empty() {
+        print_message(&format!("Jobs in the crontab of {user}:"));
+        return print_rows(&rows);
+    }
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    // A failing job doesn't stop the import. It is reported in the table.
+    for ((name, job), row) in imports.into_iter().zip(rows.iter_mut()) {
+        row.action =
+            match import_job(&connection, &manager_proxy, name, job, &user, &account.home).await {
+                Ok(()) => "created".to_string(),
+                Err(error) => format!("failed: {error}"),
+            };
+    }
+
+    print_message(&format!("Imported jobs from the crontab of {user}:"));
+    print_rows(&rows)?;
+
+    let created = rows.iter().filter(|row| row.action == "created").count();
+
+    if created > 0 {
+        print_message(&format!(
+            "Imported {created} jobs. Remove them from the crontab with `crontab{} -e` so that they don't run twice",
+            if get_current_user().as_deref() != Some(user.as_str()) {
+                format!(" -u {user}")
+            } else {
+                "".to_string()
+            }
+        ));
+
+        if is_user_scope() {
+            offer_lingering().await?;
+        }
+    }
+
+    if created < rows.len() {
+        return Err(format!("Failed to import {} jobs", rows.len() - created).into());
+    }
+
+    Ok(())
+}
+
+/// Creates the service of a crontab job. Scheduled jobs get an active timer, `@reboot` jobs are
+/// enabled.
+///
+/// # Arguments
+///
+/// * `connection` - zbus connection
+/// * `manager_proxy`: Manager proxy object
+/// * `name` - Name of the service
+/// * `job` - The crontab job
+/// * `user` - The user running the service
+/// * `home` - Home folder of the user, where cron runs jobs
+///
+async fn import_job(
+    connection: &Connection,
+    manager_proxy: &ManagerProxy<'_>,
+    name: String,
+    job: CrontabJob,
+    user: &str,
+    home: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let is_reboot = job.is_reboot();
+
+    let service_file = render_service_file(&CreateServiceOptions {
+        path: None,
+        command: Some(job.shell),
+        working_directory: Some(PathBuf::from(home)),
+        name: Some(name),
+        user: Some(user.to_string()),
+        start: false,
+        enable: is_reboot,
+        restart: RestartOptions::default(),
+        interpreter: None,
+        env_vars: job.env_vars,
+        env_file: None,
+        secrets: vec![],
+        limits: ResourceLimits::default(),
+        hardening: None,
+        schedule: if is_reboot { None } else { Some(job.schedule) },
+        watch: vec![],
+        tags: vec![IMPORT_TAG.to_string()],
+        internal_args: vec!["-c".to_string(), job.command],
+    })
+    .await?;
+
+    create_service_file(&service_file).await?;
+    verify_service_file(connection, &service_file, None).await?;
+
+    let full_service_name = get_full_service_name(&service_file.service_name);
+
+    if is_reboot {
+        enable_service(manager_proxy, &full_service_name).await?;
+    } else {
+        activate_timer(manager_proxy, &full_service_name).await?;
+    }
+
+    Ok(())
+}
+
+/// Reads the crontab of a user with `crontab -l`
+///
+/// # Arguments
+///
+/// * `user`
+///
+async fn read_user_crontab(user: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut command = Command::new("crontab");
+
+    // Reading another user's crontab needs root
+    if get_current_user().as_deref() != Some(user) {
+        command.arg("-u").arg(user);
+    }
+
+    let output = command
+        .arg("-l")
+        .output()
+        .await
+        .map_err(|error| format!("Failed to run crontab: {error}. Pass a file with --file"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read the crontab of {user}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,77 @@
+use cli_table::Table;
+use serde::Serialize;
+
+use crate::utils::{
+    limits::{get_service_limits, write_limits_drop_in, ResourceLimits},
+    output::{print_message, print_rows, ActionResult},
+    service_names::{get_full_service_name, get_service_file_path},
+    systemd::{get_connection, ManagerProxy},
+};
+
+use super::handle_show_status::handle_show_action_result;
+
+/// A resource limit of a service
+#[derive(Table, Serialize)]
+struct LimitRow {
+    /// The unit file key, eg. `MemoryMax`
+    limit: String,
+
+    /// The value, eg. `512M`
+    value: String,
+}
+
+/// Changes resource limits of a service. The limits are persisted in a drop-in, and cgroup limits
+/// also apply right away through `SetUnitProperties`. Prints the current limits if none are passed.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `limits` - Limits to set
+///
+pub async fn handle_limit(
+    name: &str,
+    limits: &ResourceLimits,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+
+    if !get_service_file_path(&full_service_name).exists() {
+        return Err(format!("No such service {full_service_name}").into());
+    }
+
+    if limits.is_empty() {
+        let rows: Vec<LimitRow> = get_service_limits(&full_service_name)
+            .await?
+            .into_iter()
+            .map(|(limit, value)| LimitRow { limit, value })
+            .collect();
+
+        return print_rows(&rows);
+    }
+
+    limits.validate()?;
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    // Persisted first, so that the limits aren't lost if the running service rejects them
+    write_limits_drop_in(&full_service_name, limits).await?;
+    manager_proxy.reload().await?;
+
+    let properties = limits.get_properties()?;
+    if !properties.is_empty() {
+        if let Err(error) = manager_proxy
+            .set_unit_properties(full_service_name.clone(), true, properties)
+            .await
+        {
+            eprintln!("Failed to apply the limits to the running service: {error}. They apply when {name} is next started");
+        }
+    }
+
+    if limits.nice.is_some() || limits.cpu_affinity.is_some() {
+        print_message(&format!(
+            "Nice and CPU affinity apply when {name} is next started"
+        ));
+    }
+
+    handle_show_action_result(ActionResult::new(name, "limit", "done")).await
+}
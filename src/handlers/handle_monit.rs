@@ -0,0 +1,420 @@
+use bytesize::ByteSize;
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::StreamExt;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+use std::{collections::VecDeque, io, process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc::{unbounded_channel, UnboundedSender},
+    task::JoinHandle,
+};
+use zbus::Connection;
+
+use crate::{
+    handlers::{
+        handle_show_logs::get_journalctl_command,
+        handle_show_status::{get_service_statuses, ServiceStatus},
+    },
+    utils::{
+        service_actions::{
+            disable_service, enable_service, restart_service, start_service, stop_service,
+        },
+        service_names::get_full_service_name,
+        systemd::{get_connection, ManagerProxy},
+    },
+};
+
+/// How often the service table is refreshed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of log lines kept for the selected service
+const LOG_LINES: usize = 200;
+
+/// Key bindings shown in the footer
+const HELP: &str = "↑/↓ select  s start  x stop  r restart  e enable  d disable  q quit";
+
+/// An action triggered with a key binding
+#[derive(Clone, Copy)]
+enum MonitorAction {
+    Start,
+    Stop,
+    Restart,
+    Enable,
+    Disable,
+}
+
+impl MonitorAction {
+    fn name(&self) -> &'static str {
+        match self {
+            MonitorAction::Start => "start",
+            MonitorAction::Stop => "stop",
+            MonitorAction::Restart => "restart",
+            MonitorAction::Enable => "enable",
+            MonitorAction::Disable => "disable",
+        }
+    }
+}
+
+/// State of the dashboard
+#[derive(Default)]
+struct Monitor {
+    /// Rows of the service table
+    statuses: Vec<ServiceStatus>,
+
+    /// Selected row
+    table_state: TableState,
+
+    /// Service whose logs are displayed
+    log_service: Option<String>,
+
+    /// Latest log lines of `log_service`
+    logs: VecDeque<String>,
+
+    /// Result of the last action
+    message: String,
+}
+
+impl Monitor {
+    /// Name of the selected service
+    fn selected_name(&self) -> Option<String> {
+        self.table_state
+            .selected()
+            .and_then(|index| self.statuses.get(index))
+            .map(|status| status.name.clone())
+    }
+
+    /// Moves the selection up or down by one row
+    ///
+    /// # Arguments
+    ///
+    /// * `down` - Move down if true, else up
+    ///
+    fn move_selection(&mut self, down: bool) {
+        if self.statuses.is_empty() {
+            return;
+        }
+
+        let last = self.statuses.len() - 1;
+        let selected = match self.table_state.selected() {
+            Some(index) if down => (index + 1).min(last),
+            Some(index) => index.saturating_sub(1),
+            None => 0,
+        };
+
+        self.table_state.select(Some(selected));
+    }
+}
+
+/// Opens a full screen dashboard. The service table refreshes every second and the logs of the
+/// selected service are followed in a pane below. Services can be started, stopped, restarted,
+/// enabled and disabled with key bindings.
+pub async fn handle_monit() -> Result<(), Box<dyn std::error::Error>> {
+    let connection = get_connection().await?;
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let result = run_monitor(&mut terminal, &connection).await;
+
+    // Restore the terminal even if the dashboard failed
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+/// Runs the dashboard's event loop till the user quits
+///
+/// # Arguments
+///
+/// * `terminal`
+/// * `connection`: zbus connection
+///
+async fn run_monitor(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    connection: &Connection,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut monitor = Monitor::default();
+    let mut events = EventStream::new();
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+
+    let (log_sender, mut log_receiver) = unbounded_channel::<(String, String)>();
+    let (message_sender, mut message_receiver) = unbounded_channel::<String>();
+    let mut log_task: Option<JoinHandle<()>> = None;
+
+    loop {
+        terminal.draw(|frame| draw(frame, &mut monitor))?;
+
+        tokio::select! {
+            _ = refresh.tick() => {
+                monitor.statuses = get_service_statuses(connection, None).await?;
+
+                let selected = match monitor.table_state.selected() {
+                    _ if monitor.statuses.is_empty() => None,
+                    Some(index) => Some(index.min(monitor.statuses.len() - 1)),
+                    None => Some(0),
+                };
+                monitor.table_state.select(selected);
+            }
+
+            Some(event) = events.next() => {
+                let Event::Key(key) = event? else {
+                    continue;
+                };
+
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                let action = match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        monitor.move_selection(false);
+                        None
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        monitor.move_selection(true);
+                        None
+                    }
+                    KeyCode::Char('s') => Some(MonitorAction::Start),
+                    KeyCode::Char('x') => Some(MonitorAction::Stop),
+                    KeyCode::Char('r') => Some(MonitorAction::Restart),
+                    KeyCode::Char('e') => Some(MonitorAction::Enable),
+                    KeyCode::Char('d') => Some(MonitorAction::Disable),
+                    _ => None,
+                };
+
+                if let (Some(action), Some(name)) = (action, monitor.selected_name()) {
+                    monitor.message = format!("Running {} on {name}...", action.name());
+                    spawn_action(connection.clone(), name, action, message_sender.clone());
+                }
+            }
+
+            Some((service, line)) = log_receiver.recv() => {
+                if monitor.log_service.as_ref() == Some(&service) {
+                    monitor.logs.push_back(line);
+
+                    if monitor.logs.len() > LOG_LINES {
+                        monitor.logs.pop_front();
+                    }
+                }
+            }
+
+            Some(message) = message_receiver.recv() => {
+                monitor.message = message;
+            }
+        }
+
+        // Follow the logs of the newly selected service
+        let selected = monitor.selected_name();
+        if selected != monitor.log_service {
+            if let Some(task) = log_task.take() {
+                task.abort();
+            }
+
+            monitor.logs.clear();
+            monitor.log_service = selected.clone();
+
+            if let Some(name) = selected {
+                log_task = Some(spawn_log_follower(name, log_sender.clone()));
+            }
+        }
+    }
+
+    if let Some(task) = log_task {
+        task.abort();
+    }
+
+    Ok(())
+}
+
+/// Draws the service table, the log pane and the footer
+///
+/// # Arguments
+///
+/// * `frame`
+/// * `monitor` - State of the dashboard
+///
+fn draw(frame: &mut Frame, monitor: &mut Monitor) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(40),
+            Constraint::Min(5),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    let header = Row::new(vec![
+        "pid",
+        "name",
+        "active",
+        "enable on boot",
+        "cpu %",
+        "memory",
+        "tasks",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows = monitor.statuses.iter().map(|status| {
+        Row::new(vec![
+            status.pid.to_string(),
+            status.name.clone(),
+            status.active.clone(),
+            status.enabled_on_boot.to_string(),
+            format!("{:.1}", status.cpu),
+            ByteSize(status.memory_bytes).to_string(),
+            status.tasks.to_string(),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Min(16),
+        Constraint::Length(12),
+        Constraint::Length(15),
+        Constraint::Length(7),
+        Constraint::Length(10),
+        Constraint::Length(6),
+    ];
+
+    let table = Table::new(rows)
+        .header(header)
+        .widths(&widths)
+        .block(Block::default().borders(Borders::ALL).title("Services"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    frame.render_stateful_widget(table, chunks[0], &mut monitor.table_state);
+
+    // Show the latest lines that fit inside the borders
+    let visible_lines = chunks[1].height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = monitor
+        .logs
+        .iter()
+        .skip(monitor.logs.len().saturating_sub(visible_lines))
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+
+    let log_title = match &monitor.log_service {
+        Some(name) => format!("Logs: {name}"),
+        None => "Logs".to_string(),
+    };
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(log_title)),
+        chunks[1],
+    );
+
+    let footer = if monitor.message.is_empty() {
+        HELP.to_string()
+    } else {
+        format!("{}  |  {HELP}", monitor.message)
+    };
+    frame.render_widget(Paragraph::new(footer), chunks[2]);
+}
+
+/// Follows the journal of a service in the background. Each line is sent with the service name
+/// so that lines of a previously selected service can be ignored.
+///
+/// # Arguments
+///
+/// * `name` - Name of the service in short form (hello-world)
+/// * `sender` - Receives `(name, line)` pairs
+///
+fn spawn_log_follower(name: String, sender: UnboundedSender<(String, String)>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut command =
+            get_journalctl_command(std::slice::from_ref(&name), LOG_LINES as u32, true);
+        command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+
+        let Ok(mut child) = command.spawn() else {
+            return;
+        };
+        let stdout = child.stdout.take().unwrap();
+        let mut lines = BufReader::new(stdout).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            if sender.send((name.clone(), line)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Runs an action in the background so that the dashboard stays responsive while waiting for
+/// the job. The outcome is sent as a message for the footer.
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `name` - Name of the service in short form (hello-world)
+/// * `action`
+/// * `sender` - Receives the outcome
+///
+fn spawn_action(
+    connection: Connection,
+    name: String,
+    action: MonitorAction,
+    sender: UnboundedSender<String>,
+) {
+    tokio::spawn(async move {
+        let message = match run_action(&connection, &name, action).await {
+            Ok(result) => format!("{} {name}: {result}", action.name()),
+            Err(error) => format!("Failed to {} {name}: {error}", action.name()),
+        };
+
+        let _ = sender.send(message);
+    });
+}
+
+/// Runs an action on a service and returns the job result
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `name` - Name of the service in short form (hello-world)
+/// * `action`
+///
+async fn run_action(
+    connection: &Connection,
+    name: &str,
+    action: MonitorAction,
+) -> zbus::Result<String> {
+    let manager_proxy = ManagerProxy::new(connection).await?;
+    let full_service_name = get_full_service_name(name);
+
+    match action {
+        MonitorAction::Start => start_service(&manager_proxy, &full_service_name).await,
+        MonitorAction::Stop => stop_service(&manager_proxy, &full_service_name).await,
+        MonitorAction::Restart => restart_service(&manager_proxy, &full_service_name).await,
+        MonitorAction::Enable => {
+            enable_service(&manager_proxy, &full_service_name).await?;
+
+            // Reload necessary for UnitFileState to update
+            manager_proxy.reload().await?;
+            Ok("done".to_string())
+        }
+        MonitorAction::Disable => {
+            disable_service(&manager_proxy, &full_service_name).await?;
+            manager_proxy.reload().await?;
+            Ok("done".to_string())
+        }
+    }
+}
@@ -1,11 +1,14 @@
-use cli_table::{Table, WithTitle};
+use cli_table::Table;
+use serde::Serialize;
 
 use crate::utils::{
-    service_names::{get_full_service_name, get_service_file_path},
+    output::{print_message, print_rows},
+    service_names::{get_full_service_name, get_path_unit_file_path, get_service_file_path},
     systemd::get_unit_path,
+    watch::get_watched_paths,
 };
 
-#[derive(Table, Clone)]
+#[derive(Table, Serialize, Clone)]
 pub struct PathStatus {
     // The file name
     name: String,
@@ -15,20 +18,20 @@ pub struct PathStatus {
 }
 
 /// Locate files used by a service and print their paths. Displays the .service path and unit path
-/// if the service is enabled
+/// if the service is enabled, and the path unit with the watched paths if the service is watched
 ///
 /// # Arguments
 ///
 /// * `name` - The service name
 ///
-pub async fn handle_print_paths(name: &String) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn handle_print_paths(name: &str) -> Result<(), Box<dyn std::error::Error>> {
     let mut path_details = Vec::<PathStatus>::new();
 
     let full_service_name = get_full_service_name(name);
     let service_file_path = get_service_file_path(&full_service_name);
 
     if service_file_path.exists() {
-        println!("Paths for {}:", full_service_name);
+        print_message(&format!("Paths for {}:", full_service_name));
 
         // 1. Service file path
         path_details.push(PathStatus {
@@ -42,7 +45,23 @@ pub async fn handle_print_paths(name: &String) -> Result<(), Box<dyn std::error:
             path: get_unit_path(&full_service_name),
         });
 
-        cli_table::print_stdout(path_details.with_title())?;
+        // 3. Path unit and the paths restarting the service
+        let path_unit_file_path = get_path_unit_file_path(&full_service_name);
+        if path_unit_file_path.exists() {
+            path_details.push(PathStatus {
+                name: "Path unit".to_string(),
+                path: path_unit_file_path.display().to_string(),
+            });
+
+            for path in get_watched_paths(&full_service_name).await {
+                path_details.push(PathStatus {
+                    name: "Watched path".to_string(),
+                    path,
+                });
+            }
+        }
+
+        print_rows(&path_details)?;
     } else {
         eprintln!("No such service {}", full_service_name);
     }
@@ -1,15 +1,18 @@
 use tokio::{fs, io::AsyncReadExt};
 
-use crate::utils::service_names::{get_full_service_name, get_service_file_path};
+use crate::utils::service_names::{
+    get_drop_in_paths, get_full_service_name, get_path_unit_file_path, get_service_file_path,
+    get_timer_file_path,
+};
 
-/// Print contents of a .service file
+/// Print contents of a .service file, followed by its drop-ins, timer and path unit
 ///
 /// # Arguments
 ///
 /// * `name` - The service name
 ///
-pub async fn handle_print_service_file(name: &String) -> Result<(), Box<dyn std::error::Error>> {
-    let full_service_name = get_full_service_name(&name);
+pub async fn handle_print_service_file(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
     let service_file_path = get_service_file_path(&full_service_name);
 
     if service_file_path.exists() {
@@ -29,6 +32,22 @@ pub async fn handle_print_service_file(name: &String) -> Result<(), Box<dyn std:
             service_file_path.to_str().unwrap(),
             contents
         );
+
+        // Drop-ins override the service file, eg. secrets and env files added later
+        for drop_in in get_drop_in_paths(&full_service_name).await? {
+            let contents = fs::read_to_string(&drop_in).await?;
+            println!("Reading {}:\n{}", drop_in.display(), contents);
+        }
+
+        for unit_file_path in [
+            get_timer_file_path(&full_service_name),
+            get_path_unit_file_path(&full_service_name),
+        ] {
+            if unit_file_path.exists() {
+                let contents = fs::read_to_string(&unit_file_path).await?;
+                println!("Reading {}:\n{}", unit_file_path.display(), contents);
+            }
+        }
     } else {
         eprintln!("{}: No such file", service_file_path.to_str().unwrap());
     }
@@ -1,12 +1,13 @@
 use crate::{
     utils::service_names::get_full_service_name,
     utils::{
+        output::{print_message, ActionResult},
         service_actions::reload_service,
-        systemd::{get_active_state, ManagerProxy},
+        systemd::{get_active_state, get_connection, ManagerProxy},
     },
 };
 
-use super::handle_show_status::handle_show_status;
+use super::handle_show_status::handle_show_action_result;
 
 /// Reloads the unit of a failed service. The service state must be 'failed', otherwise the
 /// systemd dbus API throws an error.
@@ -19,22 +20,24 @@ pub async fn handle_reload_service(
     name: &String,
     show_status: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let connection = zbus::Connection::system().await?;
+    let connection = get_connection().await?;
     let manager_proxy = ManagerProxy::new(&connection).await?;
 
-    let full_service_name = get_full_service_name(&name);
+    let full_service_name = get_full_service_name(name);
 
     let active_state = get_active_state(&connection, &full_service_name).await;
 
-    if active_state == "reloading" {
+    let result = if active_state == "reloading" {
         eprintln!("No-op. Service {full_service_name} is already {active_state}");
+        "no-op"
     } else {
         reload_service(&manager_proxy, &full_service_name).await;
-        println!("service reloaded: {name}");
+        print_message(&format!("service reloaded: {name}"));
+        "done"
     };
 
     if show_status {
-        handle_show_status().await?;
+        handle_show_action_result(ActionResult::new(name, "reload", result)).await?;
     }
 
     Ok(())
@@ -1,13 +1,17 @@
 use crate::{
-    handlers::handle_show_status::handle_show_status,
+    handlers::handle_show_status::handle_show_action_result,
     utils::{
-        service_names::{get_full_service_name, get_service_file_path},
-        systemd::{get_active_state, get_unit_file_state},
+        env_file::{get_env_file_path, get_environment_file_value},
+        output::ActionResult,
+        schedule::{activate_timer, get_service_schedule, get_timer_file_body},
+        service_names::{
+            get_drop_in_folder, get_full_service_name, get_service_file_path, get_timer_file_path,
+        },
+        systemd::{get_active_state, get_connection, get_unit_file_state, ManagerProxy},
+        watch::{activate_watch, get_path_unit_body, get_watched_paths, write_watch_units},
     },
 };
 
-use zbus::Connection;
-
 use super::{
     handle_delete_service::handle_delete_service, handle_enable_service::handle_enable_service,
     handle_start_service::handle_start_service,
@@ -16,7 +20,7 @@ use super::{
 /// Renames a service. A running service will be restarted
 ///
 /// Under the hood the exiting service is stopped and deleted. A new service file
-/// with same contents is created.
+/// with same contents is created. Drop-ins, the env file, the timer and the path unit are moved to the new name.
 ///
 /// # Arguments
 ///
@@ -24,38 +28,94 @@ use super::{
 /// * `new_name` - New name
 ///
 pub async fn handle_rename_service(
-    name: &String,
-    new_name: &String,
+    name: &str,
+    new_name: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Create new service file
-    let full_service_name = get_full_service_name(&name);
+    let full_service_name = get_full_service_name(name);
     let service_file_path = get_service_file_path(&full_service_name);
     let service_file_path_str = service_file_path.to_str().unwrap().to_string();
 
-    let new_full_service_name = get_full_service_name(&new_name);
+    let new_full_service_name = get_full_service_name(new_name);
     let new_service_file_path = get_service_file_path(&new_full_service_name);
     let new_service_file_path_str = new_service_file_path.to_str().unwrap().to_string();
 
-    // Copy .service file
-    tokio::fs::copy(service_file_path_str, new_service_file_path_str).await?;
+    // Copy .service file, pointing it to the renamed env file
+    let old_env_file_path = get_env_file_path(name);
+    let new_env_file_path = get_env_file_path(new_name);
+    let replace_env_file = |body: String| {
+        body.replace(
+            &get_environment_file_value(&old_env_file_path, true),
+            &get_environment_file_value(&new_env_file_path, true),
+        )
+    };
+
+    let body = tokio::fs::read_to_string(service_file_path_str).await?;
+    tokio::fs::write(new_service_file_path_str, replace_env_file(body)).await?;
+
+    // Move drop-ins and the env file before the old service is deleted
+    let old_drop_in_folder = get_drop_in_folder(&full_service_name);
+    if old_drop_in_folder.exists() {
+        let new_drop_in_folder = get_drop_in_folder(&new_full_service_name);
+        tokio::fs::rename(&old_drop_in_folder, &new_drop_in_folder).await?;
+
+        let mut entries = tokio::fs::read_dir(&new_drop_in_folder).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let body = tokio::fs::read_to_string(entry.path()).await?;
+            tokio::fs::write(entry.path(), replace_env_file(body)).await?;
+        }
+    }
+
+    if old_env_file_path.exists() {
+        tokio::fs::rename(&old_env_file_path, &new_env_file_path).await?;
+    }
+
+    // Timers name the service they start, so a new one is generated
+    let schedule = get_service_schedule(&full_service_name).await;
+    if let Some(schedule) = &schedule {
+        tokio::fs::write(
+            get_timer_file_path(&new_full_service_name),
+            get_timer_file_body(&new_full_service_name, schedule),
+        )
+        .await?;
+    }
+
+    let watched_paths = get_watched_paths(&full_service_name).await;
+    if !watched_paths.is_empty() {
+        write_watch_units(
+            &new_full_service_name,
+            &get_path_unit_body(&new_full_service_name, &watched_paths),
+        )
+        .await?;
+    }
 
     // Read active and unit state of current service
-    let connection = Connection::system().await?;
+    let connection = get_connection().await?;
     let active_state: String = get_active_state(&connection, &full_service_name).await;
     let unit_state = get_unit_file_state(&connection, &full_service_name).await;
 
     // Delete existing service
-    handle_delete_service(name, false).await?;
+    handle_delete_service(&[name.to_string()], false, true).await?;
 
     if active_state == "active" {
-        handle_start_service(new_name, false).await?;
+        handle_start_service(&[new_name.to_string()], false).await?;
     }
 
     if unit_state == "enabled" {
-        handle_enable_service(new_name, false).await?;
+        handle_enable_service(&[new_name.to_string()], false).await?;
+    }
+
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    if schedule.is_some() {
+        activate_timer(&manager_proxy, &new_full_service_name).await?;
+    }
+
+    if !watched_paths.is_empty() {
+        activate_watch(&manager_proxy, &new_full_service_name).await?;
     }
 
-    handle_show_status().await?;
+    handle_show_action_result(ActionResult::new(new_name, "rename", "done")).await?;
 
     Ok(())
 }
@@ -0,0 +1,115 @@
+use futures::future::join_all;
+use std::io::{self, IsTerminal, Write};
+
+use crate::{
+    utils::service_names::{get_full_service_name, resolve_service_names},
+    utils::{
+        output::{is_structured_output, print_message, ActionResult},
+        service_actions::{restart_service, try_restart_service},
+        systemd::{get_active_state, get_connection, ManagerProxy},
+    },
+};
+
+use super::handle_show_status::handle_action_results;
+
+/// Restarts systemd services concurrently and waits for them to come back up. Prints the tail of
+/// the journal for services that fail to restart.
+///
+/// # Arguments
+///
+/// * `names` - Service names, `all` or glob patterns
+/// * `try_restart` - Only restart if the service is running. A stopped service stays stopped.
+/// * `show_status` - Display the results and the status table
+///
+pub async fn handle_restart_service(
+    names: &[String],
+    try_restart: bool,
+    show_status: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let names = resolve_service_names(names).await?;
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    let action = if try_restart {
+        "try-restart"
+    } else {
+        "restart"
+    };
+
+    let (connection, manager_proxy) = (&connection, &manager_proxy);
+    let results = join_all(names.iter().map(|name| async move {
+        let full_service_name = get_full_service_name(name);
+
+        let active_state = get_active_state(connection, &full_service_name).await;
+
+        if try_restart && active_state != "active" && active_state != "reloading" {
+            eprintln!("No-op. Service {full_service_name} is {active_state}");
+            return ActionResult::new(name, action, "no-op");
+        }
+
+        let result = if try_restart {
+            try_restart_service(manager_proxy, &full_service_name).await
+        } else {
+            restart_service(manager_proxy, &full_service_name).await
+        };
+
+        match result {
+            Ok(result) => {
+                if result == "done" {
+                    print_message(&format!("Restarted {name}"));
+                }
+                ActionResult::new(name, action, &result)
+            }
+            Err(error) => {
+                eprintln!("Failed to {action} {name}: {error}");
+                ActionResult::new(name, action, "error")
+            }
+        }
+    }))
+    .await;
+
+    handle_action_results(&results, show_status).await
+}
+
+/// Restarts a running service so that it picks up changed settings, like its env file. Asks first
+/// unless `restart` is set, on stderr and only from a terminal. Stopped services pick up the settings
+/// on their next start.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `restart` - Restart without asking
+///
+pub async fn handle_offer_restart(
+    name: &str,
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let connection = get_connection().await?;
+    let active_state = get_active_state(&connection, &get_full_service_name(name)).await;
+
+    if active_state != "active" && active_state != "activating" {
+        return Ok(());
+    }
+
+    let restart = restart || {
+        if io::stdin().is_terminal() && !is_structured_output() {
+            eprint!("{name} is running. Restart it to apply the changes? [y/N] ");
+            io::stderr().flush()?;
+
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+
+            answer.trim().eq_ignore_ascii_case("y")
+        } else {
+            false
+        }
+    };
+
+    if restart {
+        handle_restart_service(&[name.to_string()], false, true).await
+    } else {
+        print_message(&format!("Run `ser restart {name}` to apply the changes"));
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use crate::utils::{
+    output::{print_message, ActionResult},
+    service_actions::start_service,
+    service_names::{get_full_service_name, get_timer_file_path},
+    systemd::{get_connection, ManagerProxy},
+};
+
+use super::handle_show_status::handle_action_results;
+
+/// Runs a scheduled service once without waiting for its timer, and waits for the run to finish.
+/// Prints the tail of the journal if the run fails.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+///
+pub async fn handle_run_now(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+
+    if !get_timer_file_path(&full_service_name).exists() {
+        return Err(format!("{name} isn't scheduled. Use `ser start {name}` to start it").into());
+    }
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    // Oneshot services finish starting once their run ends
+    let result = match start_service(&manager_proxy, &full_service_name).await {
+        Ok(result) => {
+            if result == "done" {
+                print_message(&format!("Ran {name}"));
+            }
+            ActionResult::new(name, "run-now", &result)
+        }
+        Err(error) => {
+            eprintln!("Failed to run {name}: {error}");
+            ActionResult::new(name, "run-now", "error")
+        }
+    };
+
+    handle_action_results(&[result], false).await
+}
@@ -0,0 +1,168 @@
+use cli_table::Table;
+use serde::Serialize;
+
+use crate::utils::{
+    credentials::{
+        encrypt_secret, get_secrets, get_secrets_drop_in_path, read_secret_value,
+        read_secrets_drop_in, validate_secret_name, write_secrets_drop_in,
+    },
+    output::{print_message, print_rows},
+    service_names::{check_service_exists, get_full_service_name},
+    systemd::{get_connection, ManagerProxy},
+};
+
+use super::handle_restart_service::handle_offer_restart;
+
+/// An encrypted credential of a service
+#[derive(Table, Serialize)]
+struct SecretRow {
+    /// The secret name
+    name: String,
+
+    /// The directive passing the credential, eg. `SetCredentialEncrypted`
+    directive: String,
+
+    /// The file declaring the credential
+    path: String,
+}
+
+/// Encrypts a secret with `systemd-creds` and stores it in a drop-in of the service. The value is
+/// read from stdin. The service reads it from `$CREDENTIALS_DIRECTORY/<key>`.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `key` - The secret name, eg. `API_KEY`
+/// * `restart` - Restart the service if it is running, without asking
+///
+pub async fn handle_secret_set(
+    name: &str,
+    key: &str,
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+    check_service_exists(&full_service_name)?;
+    validate_secret_name(key)?;
+
+    let value = read_secret_value(key)?;
+    set_secret(&full_service_name, key, &value).await?;
+
+    print_message(&format!(
+        "Stored {key} in {}",
+        get_secrets_drop_in_path(&full_service_name).display()
+    ));
+
+    reload_and_offer_restart(name, restart).await
+}
+
+/// Encrypts a secret and adds it to the secrets drop-in of a service, replacing a secret with the
+/// same name. The service manager must be reloaded afterwards.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `key` - The secret name
+/// * `value`
+///
+pub async fn set_secret(
+    full_service_name: &str,
+    key: &str,
+    value: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let credential = encrypt_secret(key, value).await?;
+
+    let mut secrets = read_secrets_drop_in(full_service_name).await;
+    match secrets.iter_mut().find(|(name, _)| name == key) {
+        Some(secret) => secret.1 = credential,
+        None => secrets.push((key.to_string(), credential)),
+    }
+
+    write_secrets_drop_in(full_service_name, &secrets).await?;
+
+    Ok(())
+}
+
+/// Lists the encrypted credentials of a service. Values are never printed.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+///
+pub async fn handle_secret_list(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+    check_service_exists(&full_service_name)?;
+
+    let rows: Vec<SecretRow> = get_secrets(&full_service_name)
+        .await?
+        .into_iter()
+        .map(|secret| SecretRow {
+            name: secret.name,
+            directive: secret.directive,
+            path: secret.path.display().to_string(),
+        })
+        .collect();
+
+    print_rows(&rows)
+}
+
+/// Removes secrets from the secrets drop-in of a service
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `keys` - Secret names
+/// * `restart` - Restart the service if it is running, without asking
+///
+pub async fn handle_secret_rm(
+    name: &str,
+    keys: &[String],
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+    check_service_exists(&full_service_name)?;
+
+    let mut secrets = read_secrets_drop_in(&full_service_name).await;
+
+    for key in keys {
+        if !secrets.iter().any(|(name, _)| name == key) {
+            // Secrets written by hand can't be removed safely
+            match get_secrets(&full_service_name)
+                .await?
+                .into_iter()
+                .find(|secret| secret.name == *key)
+            {
+                Some(secret) => {
+                    return Err(format!(
+                        "{key} is declared in {}. Remove it by hand",
+                        secret.path.display()
+                    )
+                    .into())
+                }
+                None => return Err(format!("{name} has no secret {key}").into()),
+            }
+        }
+    }
+    secrets.retain(|(name, _)| !keys.contains(name));
+
+    write_secrets_drop_in(&full_service_name, &secrets).await?;
+    print_message(&format!("Removed {}", keys.join(", ")));
+
+    reload_and_offer_restart(name, restart).await
+}
+
+/// Reloads the service manager to pick up the changed drop-in, then offers to restart the service
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `restart` - Restart the service if it is running, without asking
+///
+async fn reload_and_offer_restart(
+    name: &str,
+    restart: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let connection = get_connection().await?;
+    ManagerProxy::new(&connection).await?.reload().await?;
+
+    handle_offer_restart(name, restart).await
+}
@@ -1,36 +1,31 @@
-use crate::utils::service_names::get_full_service_name;
+use crate::utils::{
+    output::ActionResult,
+    scope::is_user_scope,
+    service_names::{get_full_service_name, resolve_service_names},
+};
 use std::process::Stdio;
 use tokio::io::{self, AsyncBufReadExt};
 use tokio::process::Command;
 
-/// Show logs for a service
+/// Number of log lines printed when a job fails
+const FAILURE_LOG_LINES: u32 = 15;
+
+/// Show logs for one or more services. Logs of multiple services are interleaved by time.
 ///
 /// Proxies to `journalctl`. Consider decoding the journal directly in future.
 ///
 /// # Arguments
 ///
-/// * `name`- Name of the service in short form (hello-world)
+/// * `names`- Service names in short form (hello-world), `all` or glob patterns
 /// * `follow` - Print logs
 ///
 pub async fn handle_show_logs(
-    name: &String,
+    names: &[String],
     lines: u32,
     follow: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let full_name = get_full_service_name(&name);
-
-    let mut command = Command::new("journalctl");
-
-    // Set the journal unit name with -u option
-    command.arg("-u").arg(full_name);
-
-    // Set the number of lines to show with -n option
-    command.arg("-n").arg(lines.to_string());
-
-    if follow {
-        // Enable continuous following with --follow option
-        command.arg("--follow");
-    }
+    let names = resolve_service_names(names).await?;
+    let mut command = get_journalctl_command(&names, lines, follow);
 
     // Set stdout to be captured (piped) so we can read the output
     command.stdout(Stdio::piped());
@@ -55,3 +50,94 @@ pub async fn handle_show_logs(
 
     Ok(())
 }
+
+/// Builds a `journalctl` command to read the logs of one or more services
+///
+/// # Arguments
+///
+/// * `names`- Service names in short form (hello-world)
+/// * `lines` - Number of lines from the end
+/// * `follow` - Follow the logs as they change
+///
+pub fn get_journalctl_command(names: &[String], lines: u32, follow: bool) -> Command {
+    let mut command = Command::new("journalctl");
+
+    if is_user_scope() {
+        command.arg("--user");
+    }
+
+    // Set the journal unit names with -u options
+    for name in names {
+        command.arg("-u").arg(get_full_service_name(name));
+    }
+
+    // Set the number of lines to show with -n option
+    command.arg("-n").arg(lines.to_string());
+
+    if follow {
+        // Enable continuous following with --follow option
+        command.arg("--follow");
+    }
+
+    command
+}
+
+/// Builds a `journalctl` command to read the logs of one run of a service, including the messages
+/// systemd logged about it
+///
+/// # Arguments
+///
+/// * `invocation_id` - ID of the run in hex, from the `InvocationID` property
+/// * `lines` - Number of lines from the end
+///
+pub fn get_invocation_journalctl_command(invocation_id: &str, lines: u32) -> Command {
+    let mut command = Command::new("journalctl");
+
+    // The service manager tags its own messages about the run with a different field
+    let manager_field = if is_user_scope() {
+        command.arg("--user");
+        "USER_INVOCATION_ID"
+    } else {
+        "INVOCATION_ID"
+    };
+
+    command
+        .arg(format!("_SYSTEMD_INVOCATION_ID={invocation_id}"))
+        .arg("+")
+        .arg(format!("{manager_field}={invocation_id}"))
+        .arg("-n")
+        .arg(lines.to_string());
+
+    command
+}
+
+/// Reports a job that did not complete successfully. Prints the tail of the service's journal to
+/// stderr. The journal is skipped if the action couldn't be requested at all.
+///
+/// # Arguments
+///
+/// * `result` - The failed action, having the job result reported by systemd, eg. `failed`,
+///   `timeout` or `dependency`
+///
+pub async fn print_job_failure_logs(result: &ActionResult) -> Result<(), std::io::Error> {
+    let ActionResult {
+        name,
+        action,
+        result,
+    } = result;
+
+    if result == "error" {
+        return Ok(());
+    }
+
+    eprintln!("Failed to {action} {name}: {result}. Last {FAILURE_LOG_LINES} log lines:");
+
+    get_journalctl_command(std::slice::from_ref(name), FAILURE_LOG_LINES, false)
+        .stdout(std::io::stderr())
+        .status()
+        .await?;
+
+    eprintln!("Run `ser why {name}` for a diagnosis");
+
+    Ok(())
+}
@@ -1,18 +1,32 @@
 use crate::{
-    utils::service_names::{get_short_service_name, is_full_name},
+    handlers::handle_show_logs::print_job_failure_logs,
+    utils::service_names::{get_servicer_services, get_short_service_name},
     utils::{
-        process_status::{get_cpu_time, get_memory_usage, get_page_size},
-        systemd::{get_active_state, get_main_pid, get_unit_file_state},
+        output::{is_structured_output, print_message, print_rows, ActionResult},
+        process_status::{get_resource_usage, ResourceUsage},
+        systemd::{
+            get_active_state, get_connection, get_main_pid, get_service_state, get_unit_file_state,
+            ServiceState,
+        },
+        unit_file::get_service_tags,
     },
 };
 use bytesize::ByteSize;
-use cli_table::{Table, WithTitle};
-use futures;
-use std::path::Path;
-use tokio::fs;
+use cli_table::Table;
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use zbus::Connection;
 
-#[derive(Table, Clone)]
+/// Interval over which CPU usage is sampled
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Automatic restarts after which a service that doesn't stay up is crash looping
+const CRASH_LOOP_RESTARTS: u32 = 3;
+
+/// Uptime after which a restarted service is considered stable
+const STABLE_UPTIME_SECS: u64 = 30;
+
+#[derive(Table, Serialize, Clone)]
 pub struct ServiceStatus {
     /// Process ID
     pub pid: u32,
@@ -27,40 +41,196 @@ pub struct ServiceStatus {
     #[table(title = "enable on boot")]
     pub enabled_on_boot: bool,
 
-    /// CPU usage. Formatted string in %
+    /// CPU usage of the service's control group in %
     #[table(title = "cpu %")]
     pub cpu: f32,
 
-    /// RAM usage. Formatted string with MB, KB and other units
-    pub memory: String,
+    /// RAM usage of the service's control group in bytes. Displayed with MB, KB and other units
+    #[table(title = "memory", display_fn = "display_bytes")]
+    pub memory_bytes: u64,
+
+    /// Number of processes and threads in the service's control group
+    pub tasks: u64,
+
+    /// Tags set with `--tag` at create time
+    #[table(display_fn = "display_tags")]
+    pub tags: Vec<String>,
+
+    /// Bytes read from block devices by the service's control group
+    #[table(skip)]
+    pub io_read_bytes: u64,
+
+    /// Bytes written to block devices by the service's control group
+    #[table(skip)]
+    pub io_write_bytes: u64,
+
+    /// Whether the service keeps crashing and restarting, or systemd gave up restarting it
+    #[table(skip)]
+    pub crash_loop: bool,
+
+    /// Low level state, eg. `running`, `auto-restart` or `exited`
+    #[table(skip)]
+    pub sub_state: String,
+
+    /// Seconds since the service became active. `None` if inactive
+    #[table(skip)]
+    pub uptime_secs: Option<u64>,
+
+    /// Number of automatic restarts
+    #[table(skip)]
+    pub restarts: u32,
+
+    /// Result of the last run, eg. `success`, `exit-code`, `signal`, `oom-kill` or `timeout`
+    #[table(skip)]
+    pub result: String,
+
+    /// Exit code or signal number of the main process
+    #[table(skip)]
+    pub exit_status: i32,
 }
 
-/// Display the status of your services
-pub async fn handle_show_status() -> Result<(), Box<dyn std::error::Error>> {
-    let page_size = get_page_size().await?;
-    let services = get_servicer_services().await?;
+/// Status table row with every column, displayed with `ser status --wide`
+#[derive(Table, Serialize)]
+pub struct WideServiceStatus {
+    pub pid: u32,
+
+    pub name: String,
+
+    pub active: String,
+
+    #[table(title = "sub state")]
+    pub sub_state: String,
+
+    #[table(title = "enable on boot")]
+    pub enabled_on_boot: bool,
+
+    #[table(title = "cpu %")]
+    pub cpu: f32,
+
+    #[table(title = "memory", display_fn = "display_bytes")]
+    pub memory_bytes: u64,
+
+    pub tasks: u64,
+
+    #[table(title = "uptime", display_fn = "display_uptime")]
+    pub uptime_secs: Option<u64>,
+
+    pub restarts: u32,
 
-    let connection = Connection::system().await?;
+    pub result: String,
+
+    #[table(title = "exit status")]
+    pub exit_status: i32,
+
+    #[table(display_fn = "display_tags")]
+    pub tags: Vec<String>,
+}
+
+impl From<&ServiceStatus> for WideServiceStatus {
+    fn from(status: &ServiceStatus) -> Self {
+        WideServiceStatus {
+            pid: status.pid,
+            name: status.name.clone(),
+            active: status.active.clone(),
+            sub_state: status.sub_state.clone(),
+            enabled_on_boot: status.enabled_on_boot,
+            cpu: status.cpu,
+            memory_bytes: status.memory_bytes,
+            tasks: status.tasks,
+            uptime_secs: status.uptime_secs,
+            restarts: status.restarts,
+            result: status.result.clone(),
+            exit_status: status.exit_status,
+            tags: status.tags.clone(),
+        }
+    }
+}
+
+/// Display the status of your services. Services are grouped by their tags.
+///
+/// Resource usage is measured for the service's whole control group, so worker processes and
+/// children of shell wrappers are included.
+///
+/// # Arguments
+///
+/// * `wide` - Also display sub-state, uptime, restart count, last result and exit status
+/// * `tag` - Only display services having this tag
+///
+pub async fn handle_show_status(
+    wide: bool,
+    tag: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tag = tag.map(|tag| tag.trim_start_matches('@'));
+
+    let connection = get_connection().await?;
+    let service_statuses = get_service_statuses(&connection, tag).await?;
+
+    // JSON and YAML documents always hold every field
+    if wide && !is_structured_output() {
+        let wide_statuses: Vec<WideServiceStatus> = service_statuses
+            .iter()
+            .map(WideServiceStatus::from)
+            .collect();
+
+        print_rows(&wide_statuses)?;
+    } else {
+        print_rows(&service_statuses)?;
+    }
+
+    for status in service_statuses.iter().filter(|status| status.crash_loop) {
+        print_message(&format!(
+            "{} is crash looping: restarted {} times, last result `{}`. Run `ser why {}`",
+            status.name, status.restarts, status.result, status.name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get the status of every servicer service, sorted by tags and then by name. Takes
+/// [`CPU_SAMPLE_INTERVAL`] to sample CPU usage.
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `tag` - Only get services having this tag
+///
+pub async fn get_service_statuses(
+    connection: &Connection,
+    tag: Option<&str>,
+) -> Result<Vec<ServiceStatus>, Box<dyn std::error::Error>> {
+    let services = get_servicer_services(tag).await?;
 
-    let mut active_process_exists = true;
     let mut service_statuses: Vec<ServiceStatus> = vec![];
 
+    // CPU time consumed by each active service and when it was measured
+    let mut initial_cpu_samples = vec![];
+
     for full_service_name in services {
-        let active_state: String = get_active_state(&connection, &full_service_name).await;
-        let unit_state = get_unit_file_state(&connection, &full_service_name).await;
+        let active_state: String = get_active_state(connection, &full_service_name).await;
+        let unit_state = get_unit_file_state(connection, &full_service_name).await;
 
         let enabled_on_boot = unit_state == "enabled" || unit_state == "enabled-runtime";
+        let state = get_service_state(connection, &full_service_name).await?;
+        let crash_loop = is_crash_looping(&active_state, &state);
 
-        // PID, CPU and memory is 0 for inactive and errored processes
-        let (pid, cpu, memory) = if active_state == "active" {
-            active_process_exists = true;
+        // PID and resource usage is 0 for inactive and errored processes
+        let (pid, usage, uptime_secs) = if active_state == "active" {
+            let pid = get_main_pid(connection, &full_service_name).await?;
+            let usage = get_resource_usage(connection, &full_service_name).await?;
 
-            let pid = get_main_pid(&connection, &full_service_name).await?;
-            let memory = get_memory_usage(pid, page_size as u64).await?;
+            initial_cpu_samples.push((
+                service_statuses.len(),
+                full_service_name.clone(),
+                usage.cpu_usage_nsec,
+                Instant::now(),
+            ));
 
-            (pid, 0f32, ByteSize(memory).to_string())
+            let uptime_secs = get_seconds_since(state.active_enter_timestamp);
+
+            (pid, usage, uptime_secs)
         } else {
-            (0, 0f32, "0".to_string())
+            (0, ResourceUsage::default(), None)
         };
 
         service_statuses.push(ServiceStatus {
@@ -68,86 +238,196 @@ pub async fn handle_show_status() -> Result<(), Box<dyn std::error::Error>> {
             name: get_short_service_name(&full_service_name),
             active: active_state,
             enabled_on_boot,
-            cpu,
-            memory,
+            cpu: 0f32,
+            memory_bytes: usage.memory_bytes,
+            tasks: usage.tasks,
+            tags: get_service_tags(&full_service_name).await,
+            io_read_bytes: usage.io_read_bytes,
+            io_write_bytes: usage.io_write_bytes,
+            crash_loop,
+            sub_state: state.sub_state,
+            uptime_secs,
+            restarts: state.restarts,
+            result: state.result,
+            exit_status: state.exec_main_status,
         });
     }
 
-    // CPU time algorithm- Find the change in CPU time over an interval, then divide by the interval
-    // Source- https://github.com/dalance/procs/blob/ba703e98cd44be46ba32e084f1474d81b9a7f660/src/columns/usage_cpu.rs#L36C57-L36C83
+    // CPU usage algorithm- Find the change in the control group's CPU time over an interval, then
+    // divide by the interval. We only need to sleep once.
+    if !initial_cpu_samples.is_empty() {
+        tokio::time::sleep(CPU_SAMPLE_INTERVAL).await;
 
-    // Sleep duration in ms
-    const SLEEP_DURATION: u32 = 100;
+        for (index, full_service_name, initial_cpu_nsec, measured_at) in initial_cpu_samples {
+            let usage = get_resource_usage(connection, &full_service_name).await?;
+            let elapsed_nsec = measured_at.elapsed().as_nanos() as f32;
 
-    if active_process_exists {
-        // We only need to sleep once with this method
-        let initial_cpu_times = get_cpu_times(service_statuses.clone()).await?;
-        tokio::time::sleep(tokio::time::Duration::from_millis(SLEEP_DURATION as u64)).await;
-        let final_cpu_times = get_cpu_times(service_statuses.clone()).await?;
+            let cpu_nsec = usage.cpu_usage_nsec.saturating_sub(initial_cpu_nsec) as f32;
+            service_statuses[index].cpu = cpu_nsec * 100.0 / elapsed_nsec;
+        }
+    }
 
-        let tps = clock_ticks_per_second();
+    // Services are already sorted by name. A stable sort keeps that order within a group.
+    service_statuses.sort_by(|a, b| a.tags.cmp(&b.tags));
 
-        for i in 0..service_statuses.len() {
-            let initial_time = initial_cpu_times.get(i).unwrap().clone();
-            let final_time = final_cpu_times.get(i).unwrap().clone();
-            let usage_ms = (final_time - initial_time) * 1000 / tps;
-            let cpu_usage = usage_ms as f32 * 100.0 / SLEEP_DURATION as f32;
+    Ok(service_statuses)
+}
 
-            let status = service_statuses.get_mut(i).unwrap();
-            status.cpu = cpu_usage;
-        }
+/// Whether a service keeps crashing. True if systemd gave up restarting it, or if it was restarted
+/// several times and isn't staying up.
+///
+/// # Arguments
+///
+/// * `active_state` - The active state, eg. `activating`
+/// * `state` - State details of the service
+///
+pub fn is_crash_looping(active_state: &str, state: &ServiceState) -> bool {
+    if state.result == "start-limit-hit" {
+        return true;
     }
 
-    cli_table::print_stdout(service_statuses.with_title())?;
+    if state.restarts < CRASH_LOOP_RESTARTS {
+        return false;
+    }
 
-    Ok(())
+    match active_state {
+        "active" => get_seconds_since(state.active_enter_timestamp)
+            .is_some_and(|uptime_secs| uptime_secs < STABLE_UPTIME_SECS),
+        "activating" | "failed" => true,
+        _ => false,
+    }
 }
 
-/// Get systemd services having an extension `.ser.service`. We only monitor services created by this tool
-async fn get_servicer_services() -> Result<Vec<String>, std::io::Error> {
-    let folder_path = "/etc/systemd/system/";
+/// Reports the result of an action run from the command line. Displays the status table, or the
+/// action result as a document when printing JSON or YAML.
+///
+/// # Arguments
+///
+/// * `result` - The action result
+///
+pub async fn handle_show_action_result(
+    result: ActionResult,
+) -> Result<(), Box<dyn std::error::Error>> {
+    handle_show_action_results(&[result]).await
+}
 
-    let folder_path = Path::new(folder_path);
+/// Reports the results of an action run on one or more services. Results are listed above the
+/// status table when there is more than one service.
+///
+/// # Arguments
+///
+/// * `results` - One result per service
+///
+pub async fn handle_show_action_results(
+    results: &[ActionResult],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_structured_output() {
+        return print_rows(results);
+    }
 
-    let mut files = Vec::<String>::new();
-    let mut dir = fs::read_dir(folder_path).await?;
+    if results.len() > 1 {
+        print_rows(results)?;
+    }
 
-    while let Some(entry) = dir.next_entry().await? {
-        let path = entry.path();
+    handle_show_status(false, None).await
+}
 
-        if path.is_file() {
-            let name = path.file_name().unwrap().to_str().unwrap();
-            if is_full_name(name) {
-                files.push(name.to_string());
-            }
-        }
+/// Handles the results of an action run on one or more services. The journal tail of every failed
+/// job is printed once all actions complete, so that logs of concurrent jobs don't interleave.
+/// Returns an error if any action failed.
+///
+/// # Arguments
+///
+/// * `results` - One result per service
+/// * `show_status` - Display the results and the status table
+///
+pub async fn handle_action_results(
+    results: &[ActionResult],
+    show_status: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let failed: Vec<&ActionResult> = results
+        .iter()
+        .filter(|result| result.is_failure())
+        .collect();
+
+    for result in &failed {
+        print_job_failure_logs(result).await?;
+    }
+
+    if show_status {
+        handle_show_action_results(results).await?;
+    }
+
+    match failed.as_slice() {
+        [] => Ok(()),
+        [result] => Err(format!(
+            "{} job for {} finished with result `{}`",
+            result.action, result.name, result.result
+        )
+        .into()),
+        _ => Err(format!("{} of {} actions failed", failed.len(), results.len()).into()),
+    }
+}
+
+/// Seconds elapsed since a systemd timestamp. `None` if the timestamp isn't set.
+///
+/// # Arguments
+///
+/// * `timestamp_usec` - Microseconds since the epoch
+///
+pub fn get_seconds_since(timestamp_usec: u64) -> Option<u64> {
+    if timestamp_usec == 0 {
+        return None;
     }
 
-    Ok(files)
+    let now_usec = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_micros() as u64;
+
+    Some(now_usec.saturating_sub(timestamp_usec) / 1_000_000)
 }
 
-/// Get CPU clock ticks per second. This value is usually 100 on x86_64
-pub fn clock_ticks_per_second() -> u64 {
-    unsafe { libc::sysconf(libc::_SC_CLK_TCK) as u64 }
+/// Formats an uptime in seconds as the two largest units, eg. `3h 12m`
+///
+/// # Arguments
+///
+/// * `uptime_secs`
+///
+pub fn display_uptime(uptime_secs: &Option<u64>) -> String {
+    let Some(secs) = *uptime_secs else {
+        return "-".to_string();
+    };
+
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {}s", secs % 60)
+    } else {
+        format!("{secs}s")
+    }
 }
 
-/// Get CPU time for a vector of services
+/// Formats a byte count with MB, KB and other units
 ///
 /// # Arguments
 ///
-/// * `service_statuses`
-///
-pub async fn get_cpu_times(
-    service_statuses: Vec<ServiceStatus>,
-) -> Result<Vec<u64>, tokio::task::JoinError> {
-    futures::future::try_join_all(service_statuses.into_iter().map(|status| {
-        tokio::spawn(async move {
-            if status.active == "active" {
-                get_cpu_time(status.pid).await.unwrap_or(0)
-            } else {
-                0
-            }
-        })
-    }))
-    .await
+/// * `bytes`
+///
+fn display_bytes(bytes: &u64) -> ByteSize {
+    ByteSize(*bytes)
+}
+
+/// Displays tags separated by commas
+///
+/// # Arguments
+///
+/// * `tags`
+///
+fn display_tags(tags: &[String]) -> String {
+    tags.join(", ")
 }
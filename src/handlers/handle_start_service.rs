@@ -1,41 +1,58 @@
+use futures::future::join_all;
+
 use crate::{
-    utils::service_names::get_full_service_name,
+    utils::service_names::{get_full_service_name, resolve_service_names},
     utils::{
+        output::{print_message, ActionResult},
         service_actions::start_service,
-        systemd::{get_active_state, ManagerProxy},
+        systemd::{get_active_state, get_connection, ManagerProxy},
     },
 };
 
-use super::handle_show_status::handle_show_status;
+use super::handle_show_status::handle_action_results;
 
-/// Starts a systemd service. This is a no-op if the service is already running.
+/// Starts systemd services concurrently and waits for them to come up. This is a no-op for services
+/// that are already running. Prints the tail of the journal for services that fail to start.
 ///
 /// # Arguments
 ///
-/// * `name` - The service name
+/// * `names` - Service names, `all` or glob patterns
+/// * `show_status` - Display the results and the status table
 ///
 pub async fn handle_start_service(
-    name: &String,
+    names: &[String],
     show_status: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let connection = zbus::Connection::system().await?;
-    let manager_proxy = ManagerProxy::new(&connection).await?;
-
-    let full_service_name = get_full_service_name(&name);
-
-    let active_state = get_active_state(&connection, &full_service_name).await;
+    let names = resolve_service_names(names).await?;
 
-    if active_state == "active" || active_state == "reloading" {
-        eprintln!("No-op. Service {full_service_name} is already {active_state}");
-    } else {
-        let start_service_result = start_service(&manager_proxy, &full_service_name).await;
-
-        println!("service started: {start_service_result}");
-    };
-
-    if show_status {
-        handle_show_status().await?;
-    }
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
 
-    Ok(())
+    let (connection, manager_proxy) = (&connection, &manager_proxy);
+    let results = join_all(names.iter().map(|name| async move {
+        let full_service_name = get_full_service_name(name);
+
+        let active_state = get_active_state(connection, &full_service_name).await;
+
+        if active_state == "active" || active_state == "reloading" {
+            eprintln!("No-op. Service {full_service_name} is already {active_state}");
+            return ActionResult::new(name, "start", "no-op");
+        }
+
+        match start_service(manager_proxy, &full_service_name).await {
+            Ok(result) => {
+                if result == "done" {
+                    print_message(&format!("Started {name}"));
+                }
+                ActionResult::new(name, "start", &result)
+            }
+            Err(error) => {
+                eprintln!("Failed to start {name}: {error}");
+                ActionResult::new(name, "start", "error")
+            }
+        }
+    }))
+    .await;
+
+    handle_action_results(&results, show_status).await
 }
@@ -1,33 +1,49 @@
+use futures::future::join_all;
+
 use crate::{
-    handlers::handle_show_status::handle_show_status,
+    handlers::handle_show_status::handle_action_results,
     utils::{
-        service_actions::stop_service, service_names::get_full_service_name, systemd::ManagerProxy,
+        output::{print_message, ActionResult},
+        service_actions::stop_service,
+        service_names::{get_full_service_name, resolve_service_names},
+        systemd::{get_connection, ManagerProxy},
     },
 };
 
-/// Stops a service
-///
-/// TODO support stopping all services with `all`
+/// Stops services concurrently and waits for them to shut down
 ///
 /// # Arguments
 ///
-/// * `name`- Name of the service to stop
+/// * `names`- Service names, `all` or glob patterns
+/// * `show_status` - Display the results and the status table
 ///
 pub async fn handle_stop_service(
-    name: &String,
+    names: &[String],
     show_status: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let full_service_name = get_full_service_name(&name);
+    let names = resolve_service_names(names).await?;
 
-    let connection = zbus::Connection::system().await?;
+    let connection = get_connection().await?;
     let manager_proxy = ManagerProxy::new(&connection).await?;
-    stop_service(&manager_proxy, &full_service_name).await;
 
-    println!("Stopped {name}");
+    let manager_proxy = &manager_proxy;
+    let results = join_all(names.iter().map(|name| async move {
+        let full_service_name = get_full_service_name(name);
 
-    if show_status {
-        handle_show_status().await?;
-    }
+        match stop_service(manager_proxy, &full_service_name).await {
+            Ok(result) => {
+                if result == "done" {
+                    print_message(&format!("Stopped {name}"));
+                }
+                ActionResult::new(name, "stop", &result)
+            }
+            Err(error) => {
+                eprintln!("Failed to stop {name}: {error}");
+                ActionResult::new(name, "stop", "error")
+            }
+        }
+    }))
+    .await;
 
-    Ok(())
+    handle_action_results(&results, show_status).await
 }
@@ -0,0 +1,122 @@
+use cli_table::Table;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    handlers::handle_show_status::{display_uptime, get_seconds_since},
+    utils::{
+        output::print_rows,
+        schedule::get_service_schedule,
+        service_names::{
+            get_full_timer_name, get_servicer_services, get_short_service_name, get_timer_file_path,
+        },
+        systemd::{get_active_state, get_connection, get_service_state, get_timer_state},
+    },
+};
+
+/// Schedule and runs of a scheduled service
+#[derive(Table, Serialize)]
+struct TimerRow {
+    /// The short service name
+    name: String,
+
+    /// The `OnCalendar=` expression, eg. `*-*-* 03:00:00`
+    schedule: String,
+
+    /// Active state of the timer. `active` if the schedule is running
+    timer: String,
+
+    /// Seconds since the last run
+    #[table(title = "last run", display_fn = "display_last_run")]
+    last_run: Option<u64>,
+
+    /// Seconds till the next run
+    #[table(title = "next run", display_fn = "display_next_run")]
+    next_run: Option<u64>,
+
+    /// Result of the last run, eg. `success` or `exit-code`
+    #[table(title = "last result")]
+    last_result: String,
+}
+
+/// Lists scheduled services with their schedule, last and next run, and the result of the last run
+pub async fn handle_timers() -> Result<(), Box<dyn std::error::Error>> {
+    let connection = get_connection().await?;
+    let mut rows = vec![];
+
+    for full_service_name in get_servicer_services(None).await? {
+        if !get_timer_file_path(&full_service_name).exists() {
+            continue;
+        }
+
+        let full_timer_name = get_full_timer_name(&full_service_name);
+        let schedule = get_service_schedule(&full_service_name)
+            .await
+            .unwrap_or_default();
+        let timer_state = get_timer_state(&connection, &full_timer_name).await.ok();
+        let last_result = match get_service_state(&connection, &full_service_name).await {
+            // The result stays `success` until the service first runs
+            Ok(state) if state.invocation_id.is_empty() => "-".to_string(),
+            Ok(state) => state.result,
+            Err(_) => "-".to_string(),
+        };
+
+        rows.push(TimerRow {
+            name: get_short_service_name(&full_service_name),
+            schedule,
+            timer: get_active_state(&connection, &full_timer_name).await,
+            last_run: timer_state
+                .as_ref()
+                .and_then(|state| get_seconds_since(state.last_trigger)),
+            next_run: timer_state.and_then(|state| get_seconds_until(state.next_elapse)),
+            last_result,
+        });
+    }
+
+    print_rows(&rows)
+}
+
+/// Seconds remaining till a systemd timestamp. `None` if the timestamp isn't set.
+///
+/// # Arguments
+///
+/// * `timestamp_usec` - Microseconds since the epoch
+///
+fn get_seconds_until(timestamp_usec: u64) -> Option<u64> {
+    if timestamp_usec == 0 {
+        return None;
+    }
+
+    let now_usec = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_micros() as u64;
+
+    Some(timestamp_usec.saturating_sub(now_usec) / 1_000_000)
+}
+
+/// Displays the time since the last run, eg. `3h 12m ago`
+///
+/// # Arguments
+///
+/// * `last_run`
+///
+fn display_last_run(last_run: &Option<u64>) -> String {
+    match last_run {
+        Some(_) => format!("{} ago", display_uptime(last_run)),
+        None => "-".to_string(),
+    }
+}
+
+/// Displays the time till the next run, eg. `in 5m 3s`
+///
+/// # Arguments
+///
+/// * `next_run`
+///
+fn display_next_run(next_run: &Option<u64>) -> String {
+    match next_run {
+        Some(_) => format!("in {}", display_uptime(next_run)),
+        None => "-".to_string(),
+    }
+}
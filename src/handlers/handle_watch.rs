@@ -0,0 +1,92 @@
+use cli_table::Table;
+use serde::Serialize;
+use std::{env, path::PathBuf};
+
+use crate::utils::{
+    output::{print_message, print_rows, ActionResult},
+    service_names::{
+        get_full_service_name, get_path_unit_file_path, get_service_file_path, get_timer_file_path,
+    },
+    systemd::{get_connection, ManagerProxy},
+    watch::{
+        activate_watch, get_path_unit_body, get_watched_paths, remove_watch, resolve_watch_paths,
+        write_watch_units,
+    },
+};
+
+use super::handle_show_status::handle_show_action_result;
+
+/// A path watched for a service
+#[derive(Table, Serialize)]
+struct WatchRow {
+    /// Absolute path of the file or folder
+    path: String,
+}
+
+/// Restarts a service when files or folders change, using a `.ser.path` unit. The passed paths
+/// replace the watched paths. Prints the watched paths if none are passed.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `paths` - Files or folders to watch. Relative paths start from the current folder
+/// * `stop` - Stop watching the service
+///
+pub async fn handle_watch(
+    name: &str,
+    paths: &[PathBuf],
+    stop: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+
+    if !get_service_file_path(&full_service_name).exists() {
+        return Err(format!("No such service {full_service_name}").into());
+    }
+
+    let is_watched = get_path_unit_file_path(&full_service_name).exists();
+
+    if !stop && paths.is_empty() {
+        let rows: Vec<WatchRow> = get_watched_paths(&full_service_name)
+            .await
+            .into_iter()
+            .map(|path| WatchRow { path })
+            .collect();
+
+        return print_rows(&rows);
+    }
+
+    let connection = get_connection().await?;
+    let manager_proxy = ManagerProxy::new(&connection).await?;
+
+    if stop {
+        if !is_watched {
+            eprintln!("No-op. Service {full_service_name} isn't watched");
+            return handle_show_action_result(ActionResult::new(name, "unwatch", "no-op")).await;
+        }
+
+        remove_watch(&manager_proxy, &full_service_name).await?;
+        manager_proxy.reload().await?;
+
+        return handle_show_action_result(ActionResult::new(name, "unwatch", "done")).await;
+    }
+
+    if get_timer_file_path(&full_service_name).exists() {
+        return Err(format!("{name} is scheduled. Scheduled services run on every start").into());
+    }
+
+    let paths = resolve_watch_paths(paths, &env::current_dir()?).await?;
+    write_watch_units(
+        &full_service_name,
+        &get_path_unit_body(&full_service_name, &paths),
+    )
+    .await?;
+    manager_proxy.reload().await?;
+
+    activate_watch(&manager_proxy, &full_service_name).await?;
+    print_message(&format!(
+        "{name} restarts when {} changes",
+        paths.join(", ")
+    ));
+
+    handle_show_action_result(ActionResult::new(name, "watch", "done")).await
+}
@@ -0,0 +1,400 @@
+use serde::Serialize;
+use std::{
+    ffi::CString,
+    io::{BufRead, BufReader, Read},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+use tokio::fs;
+
+use crate::{
+    handlers::{
+        handle_show_logs::{get_invocation_journalctl_command, get_journalctl_command},
+        handle_show_status::is_crash_looping,
+    },
+    utils::{
+        limits::get_service_limits,
+        output::{is_structured_output, print_document},
+        scope::is_user_scope,
+        service_names::{get_full_service_name, get_service_file_path},
+        systemd::{get_active_state, get_connection, get_exec_settings, get_service_state},
+        unit_file::get_unit_values,
+    },
+};
+
+/// `ExecMainCode` of a main process that exited by itself
+const CLD_EXITED: i32 = 1;
+
+/// Diagnosis of a failing service
+#[derive(Serialize)]
+struct Diagnosis {
+    /// The short service name
+    name: String,
+
+    /// Active state, eg. `failed`
+    active: String,
+
+    /// Low level state, eg. `auto-restart`
+    sub_state: String,
+
+    /// Result of the last run, eg. `exit-code`
+    result: String,
+
+    /// How the main process ended, eg. `exit code 203/EXEC` or `signal 9/SIGKILL`
+    exit: Option<String>,
+
+    /// Number of automatic restarts
+    restarts: u32,
+
+    /// Whether the service keeps crashing and restarting
+    crash_loop: bool,
+
+    /// Whether the service was killed for running out of memory
+    oom_killed: bool,
+
+    /// Likely causes found by inspecting the service
+    causes: Vec<String>,
+
+    /// Journal lines of the last run
+    logs: Vec<String>,
+}
+
+/// Explains why a service failed: how its last run ended, whether it is crash looping or was
+/// OOM-killed, likely causes like a missing program or working directory, and the journal of
+/// the failing run.
+///
+/// # Arguments
+///
+/// * `name` - The service name
+/// * `lines` - Number of journal lines to print
+///
+pub async fn handle_why(name: &str, lines: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let full_service_name = get_full_service_name(name);
+    let service_file_path = get_service_file_path(&full_service_name);
+
+    if !service_file_path.exists() {
+        return Err(format!("No such service {full_service_name}").into());
+    }
+
+    let connection = get_connection().await?;
+    let active_state = get_active_state(&connection, &full_service_name).await;
+    let state = get_service_state(&connection, &full_service_name).await?;
+    let crash_loop = is_crash_looping(&active_state, &state);
+    let oom_killed = state.result == "oom-kill";
+
+    let exit = match state.exec_main_code {
+        0 => None,
+        CLD_EXITED => Some(format!(
+            "exit code {}",
+            describe_exit_code(state.exec_main_status)
+        )),
+        _ => Some(format!(
+            "signal {}",
+            describe_signal(state.exec_main_status)
+        )),
+    };
+
+    let mut causes = vec![];
+
+    if let Some(cause) = explain_result(&state.result) {
+        causes.push(cause.to_string());
+    }
+
+    if state.exec_main_code == CLD_EXITED {
+        if let Some(cause) = explain_exit_code(state.exec_main_status) {
+            causes.push(cause.to_string());
+        }
+    }
+
+    if oom_killed {
+        let memory_max = get_service_limits(&full_service_name)
+            .await?
+            .into_iter()
+            .find(|(limit, _)| limit == "MemoryMax")
+            .map(|(_, value)| value);
+
+        causes.push(match memory_max {
+            Some(memory_max) => format!(
+                "The service used more than its MemoryMax={memory_max}. Raise it with `ser limit {name} --memory-max <size>`"
+            ),
+            None => "The kernel ran out of memory and killed the service. Check the memory use of the machine".to_string(),
+        });
+    }
+
+    let body = fs::read_to_string(&service_file_path).await?;
+    let argv = get_exec_settings(&connection, &full_service_name)
+        .await?
+        .argv
+        .into_iter()
+        .next()
+        .unwrap_or_default();
+    causes.extend(find_setup_problems(&body, &argv));
+
+    if state.result == "start-limit-hit" {
+        causes.push(format!(
+            "systemd gave up restarting the service. After fixing the cause, run `systemctl{} reset-failed {full_service_name}` and `ser start {name}`",
+            if is_user_scope() { " --user" } else { "" }
+        ));
+    }
+
+    let mut command = if state.invocation_id.is_empty() {
+        get_journalctl_command(&[name.to_string()], lines, false)
+    } else {
+        get_invocation_journalctl_command(&state.invocation_id, lines)
+    };
+    let output = command.arg("--no-pager").arg("--quiet").output().await?;
+    let logs: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+
+    let diagnosis = Diagnosis {
+        name: name.to_string(),
+        active: active_state,
+        sub_state: state.sub_state,
+        result: state.result,
+        exit,
+        restarts: state.restarts,
+        crash_loop,
+        oom_killed,
+        causes,
+        logs,
+    };
+
+    if is_structured_output() {
+        return print_document(&diagnosis);
+    }
+
+    print_diagnosis(&diagnosis);
+
+    Ok(())
+}
+
+/// Prints a diagnosis for humans
+///
+/// # Arguments
+///
+/// * `diagnosis`
+///
+fn print_diagnosis(diagnosis: &Diagnosis) {
+    println!(
+        "{} is {} ({}), last result `{}`",
+        diagnosis.name, diagnosis.active, diagnosis.sub_state, diagnosis.result
+    );
+
+    if let Some(exit) = &diagnosis.exit {
+        println!("Main process ended with {exit}");
+    }
+
+    if diagnosis.crash_loop {
+        println!(
+            "Crash looping: restarted {} times without staying up",
+            diagnosis.restarts
+        );
+    } else if diagnosis.restarts > 0 {
+        println!("Restarted {} times", diagnosis.restarts);
+    }
+
+    if !diagnosis.causes.is_empty() {
+        println!("\nLikely causes:");
+        for cause in &diagnosis.causes {
+            println!("  - {cause}");
+        }
+    }
+
+    if !diagnosis.logs.is_empty() {
+        println!("\nLogs of the last run:");
+        for line in &diagnosis.logs {
+            println!("{line}");
+        }
+    }
+}
+
+/// Checks the setup of a service offline: its program, script, working directory, user and env
+/// files
+///
+/// # Arguments
+///
+/// * `body` - Contents of the unit file
+/// * `argv` - Program and args of `ExecStart=` as parsed by systemd
+///
+fn find_setup_problems(body: &str, argv: &[String]) -> Vec<String> {
+    let mut problems = vec![];
+
+    // A leading `-` makes the path optional
+    let working_directory = get_unit_values(body, "WorkingDirectory")
+        .pop()
+        .filter(|value| !value.starts_with('-') && value != "~")
+        .map(|value| value.replace("%%", "%"));
+
+    if let Some(working_directory) = &working_directory {
+        if !Path::new(working_directory).is_dir() {
+            problems.push(format!(
+                "The working directory {working_directory} doesn't exist"
+            ));
+        }
+    }
+
+    if let Some(program) = argv.first().filter(|program| program.starts_with('/')) {
+        let program_path = Path::new(program);
+
+        match program_path.metadata() {
+            Err(_) => problems.push(format!(
+                "The program {program} doesn't exist. If the interpreter was moved or uninstalled, recreate the service or fix ExecStart with `ser edit`"
+            )),
+            Ok(metadata) if metadata.permissions().mode() & 0o111 == 0 => {
+                problems.push(format!("The program {program} isn't executable"))
+            }
+            Ok(_) => {
+                if let Some(interpreter) = get_missing_shebang_interpreter(program_path) {
+                    problems.push(format!(
+                        "The interpreter {interpreter} in the #! line of {program} doesn't exist"
+                    ));
+                }
+            }
+        }
+    }
+
+    // Files run by servicer are passed to their interpreter as the first arg
+    if let (Some(script), Some(working_directory)) = (argv.get(1), &working_directory) {
+        let script_path = Path::new(working_directory).join(script);
+        let looks_like_file = !script.starts_with('-') && Path::new(script).extension().is_some();
+
+        if looks_like_file && !script_path.exists() {
+            problems.push(format!("The file {} doesn't exist", script_path.display()));
+        }
+    }
+
+    if let Some(user) = get_unit_values(body, "User").pop() {
+        let exists = CString::new(user.as_str())
+            .map(|user| unsafe { !libc::getpwnam(user.as_ptr()).is_null() })
+            .unwrap_or(false);
+
+        if !exists {
+            problems.push(format!("The user {user} doesn't exist"));
+        }
+    }
+
+    for env_file in get_unit_values(body, "EnvironmentFile") {
+        let env_file = env_file.replace("%%", "%");
+
+        if !env_file.starts_with('-') && !Path::new(&env_file).is_file() {
+            problems.push(format!("The env file {env_file} doesn't exist"));
+        }
+    }
+
+    problems
+}
+
+/// Interpreter of a script's `#!` line if it doesn't exist
+///
+/// # Arguments
+///
+/// * `program` - Path of the program
+///
+fn get_missing_shebang_interpreter(program: &Path) -> Option<String> {
+    let file = std::fs::File::open(program).ok()?;
+    let mut first_line = vec![];
+    // Only the first line is needed
+    BufReader::new(file.take(256))
+        .read_until(b'\n', &mut first_line)
+        .ok()?;
+
+    let first_line = String::from_utf8(first_line).ok()?;
+    let interpreter = first_line.strip_prefix("#!")?.split_whitespace().next()?;
+
+    if Path::new(interpreter).exists() {
+        None
+    } else {
+        Some(interpreter.to_string())
+    }
+}
+
+/// Meaning of the `Result` property of a failed service
+///
+/// # Arguments
+///
+/// * `result` - eg. `exit-code`
+///
+fn explain_result(result: &str) -> Option<&'static str> {
+    match result {
+        "exit-code" => Some("The main process exited with an error"),
+        "signal" => Some("The main process was killed by a signal"),
+        "core-dump" => Some("The main process crashed and dumped core"),
+        "oom-kill" => Some("The service was killed for running out of memory"),
+        "timeout" => Some("The service didn't start or stop in time"),
+        "watchdog" => Some("The service stopped responding to the watchdog"),
+        "start-limit-hit" => Some("The service was restarted too often in a short time"),
+        "resources" => Some("systemd couldn't set up the service, eg. a missing working directory, user, env file or secret"),
+        "exec-condition" => Some("An ExecCondition= check failed"),
+        _ => None,
+    }
+}
+
+/// Common causes of an exit code. Codes from 200 are set by systemd when it fails to run the
+/// program.
+///
+/// # Arguments
+///
+/// * `code`
+///
+fn explain_exit_code(code: i32) -> Option<&'static str> {
+    match code {
+        126 => Some("A command isn't executable"),
+        127 => Some("A command wasn't found. Use absolute paths or set PATH with `ser env set`"),
+        200 => Some("systemd couldn't change to the working directory"),
+        203 => Some("systemd couldn't run the program. It is missing, isn't executable or its #! interpreter is missing"),
+        209 => Some("systemd couldn't set up standard output or error"),
+        217 => Some("The user of the service doesn't exist"),
+        218 => Some("systemd couldn't apply the capability settings"),
+        226 => Some("systemd couldn't set up the sandbox, often because a path in ReadWritePaths= doesn't exist"),
+        243 => Some("An encrypted secret couldn't be decrypted. Store it again with `ser secret set`"),
+        _ => None,
+    }
+}
+
+/// Exit code with the name systemd gives it, eg. `203/EXEC`
+///
+/// # Arguments
+///
+/// * `code`
+///
+fn describe_exit_code(code: i32) -> String {
+    let name = match code {
+        200 => "CHDIR",
+        203 => "EXEC",
+        209 => "STDOUT",
+        217 => "USER",
+        218 => "CAPABILITIES",
+        226 => "NAMESPACE",
+        243 => "CREDENTIALS",
+        _ => return code.to_string(),
+    };
+
+    format!("{code}/{name}")
+}
+
+/// Signal number with its name, eg. `9/SIGKILL`
+///
+/// # Arguments
+///
+/// * `signal`
+///
+fn describe_signal(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGILL => "SIGILL",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGTERM => "SIGTERM",
+        _ => return signal.to_string(),
+    };
+
+    format!("{signal}/{name}")
+}
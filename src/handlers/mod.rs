@@ -1,13 +1,26 @@
+pub mod handle_apply_manifest;
+pub mod handle_audit;
 pub mod handle_create_service;
 pub mod handle_delete_service;
 pub mod handle_disable_service;
 pub mod handle_edit_service_file;
 pub mod handle_enable_service;
+pub mod handle_env;
+pub mod handle_events;
+pub mod handle_import;
+pub mod handle_limit;
+pub mod handle_monit;
 pub mod handle_print_paths;
 pub mod handle_print_service_file;
 pub mod handle_reload_service;
 pub mod handle_rename_service;
+pub mod handle_restart_service;
+pub mod handle_run_now;
+pub mod handle_secret;
 pub mod handle_show_logs;
 pub mod handle_show_status;
 pub mod handle_start_service;
 pub mod handle_stop_service;
+pub mod handle_timers;
+pub mod handle_watch;
+pub mod handle_why;
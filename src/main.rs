@@ -5,19 +5,39 @@ use clap::{Parser, Subcommand};
 mod handlers;
 mod utils;
 
-use handlers::handle_create_service::handle_create_service;
+use handlers::handle_apply_manifest::handle_apply_manifest;
+use handlers::handle_audit::handle_audit;
+use handlers::handle_create_service::{handle_create_service, CreateServiceOptions};
 use handlers::handle_delete_service::handle_delete_service;
 use handlers::handle_disable_service::handle_disable_service;
 use handlers::handle_edit_service_file::handle_edit_service_file;
 use handlers::handle_enable_service::handle_enable_service;
+use handlers::handle_env::{handle_env_list, handle_env_set, handle_env_unset};
+use handlers::handle_events::handle_events;
+use handlers::handle_import::handle_import_crontab;
+use handlers::handle_limit::handle_limit;
+use handlers::handle_monit::handle_monit;
 use handlers::handle_print_paths::handle_print_paths;
 use handlers::handle_print_service_file::handle_print_service_file;
 use handlers::handle_reload_service::handle_reload_service;
 use handlers::handle_rename_service::handle_rename_service;
+use handlers::handle_restart_service::handle_restart_service;
+use handlers::handle_run_now::handle_run_now;
+use handlers::handle_secret::{handle_secret_list, handle_secret_rm, handle_secret_set};
 use handlers::handle_show_logs::handle_show_logs;
 use handlers::handle_show_status::handle_show_status;
 use handlers::handle_start_service::handle_start_service;
 use handlers::handle_stop_service::handle_stop_service;
+use handlers::handle_timers::handle_timers;
+use handlers::handle_watch::handle_watch;
+use handlers::handle_why::handle_why;
+use utils::config::{load_config, set_config};
+use utils::hardening::Hardening;
+use utils::limits::ResourceLimits;
+use utils::output::{set_output_format, OutputFormat};
+use utils::restart::{RestartOptions, RestartPolicy};
+use utils::scope::{is_root, set_user_scope};
+use utils::unit_file::{parse_env_var, parse_env_vars};
 
 /// servicer process manager
 #[derive(Parser, Debug)]
@@ -25,17 +45,42 @@ use handlers::handle_stop_service::handle_stop_service;
 struct Args {
     #[command(subcommand)]
     command: Commands,
+
+    /// Manage services of the current user with `systemd --user`. Doesn't need sudo. Can be made the
+    /// default with `user = true` in `~/.config/servicer/config.toml`
+    #[arg(long, global = true)]
+    user: bool,
+
+    /// Manage system services. Overrides `user = true` in the config file
+    #[arg(long, global = true, conflicts_with = "user")]
+    system: bool,
+
+    /// Output format. `json` and `yaml` print documents with stable field names for scripts
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
 }
 
+// Parsed once per run, so the size of `Create` doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    /// Create a systemd service for a file at the given path
+    /// Create a systemd service for a file at the given path, or for a command line with `--cmd`
     #[command(arg_required_else_help = true)]
     Create {
         /// The file path
-        path: PathBuf,
+        #[arg(required_unless_present = "cmd")]
+        path: Option<PathBuf>,
+
+        /// Run a command line instead of a file, eg. `ser create --cmd "npm run start" --name web`. The first word
+        /// is resolved to an absolute path
+        #[arg(long, conflicts_with_all = ["path", "interpreter"], requires = "name")]
+        cmd: Option<String>,
+
+        /// Working directory for `--cmd`. Defaults to the current directory
+        #[arg(long, requires = "cmd")]
+        cwd: Option<PathBuf>,
 
-        /// Optional custom name for the service
+        /// Optional custom name for the service. Required with `--cmd`
         #[arg(short, long)]
         name: Option<String>,
 
@@ -48,26 +93,68 @@ pub enum Commands {
         #[arg(short, long)]
         enable: bool,
 
-        /// Auto-restart on failure. Default false. You should edit the .service file for more advanced features.
-        /// The service must be enabled for auto-restart to work.
+        /// Always restart the service when it exits. Same as `--restart always`
         #[arg(short = 'r', long)]
         auto_restart: bool,
 
-        /// Optional custom interpreter. Input can be the executable's name, eg `python3` or the full path
-        /// `usr/bin/python3`. If no input is provided servicer will use the file extension to detect the interpreter.
+        #[command(flatten)]
+        restart: RestartOptions,
+
+        /// Optional custom interpreter. Input can be the executable's name, eg `python3`, the full path
+        /// `usr/bin/python3` or a command like `java -jar`. If no input is provided servicer detects the interpreter
+        /// from the file extension, then from the `#!` shebang line. Executable files run by themselves.
         #[arg(short, long)]
         interpreter: Option<String>,
 
-        /// Optional environment variables. To run `FOO=BAR node index.js` call `ser create index.js --env_vars "FOO=BAR"`
+        /// Optional environment variables. To run `FOO=BAR node index.js` call `ser create index.js --env-vars "FOO=BAR"`.
+        /// Values may be quoted like in a shell, eg. `--env-vars 'GREETING="hello world" FOO=BAR'`
         #[arg(short = 'v', long)]
         env_vars: Option<String>,
 
-        /// Optional args passed to the file. Eg. to run `node index.js --foo bar` call `ser create index.js -- --foo bar`
+        /// Set an environment variable, eg. `--env "GREETING=hello world"`. Can be repeated
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
+
+        /// Load environment variables from a file with `EnvironmentFile=` instead of writing them into the
+        /// world-readable unit file. Defaults to `.env` in the working directory if present
+        #[arg(long)]
+        env_file: Option<PathBuf>,
+
+        /// Store an encrypted secret with `systemd-creds`, eg. `--secret API_KEY`. The value is prompted for. The
+        /// service reads it from `$CREDENTIALS_DIRECTORY/API_KEY`. Can be repeated
+        #[arg(long = "secret", value_name = "KEY")]
+        secrets: Vec<String>,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
+
+        /// Run the service on a schedule instead of keeping it running. Takes a systemd calendar expression like
+        /// `*-*-* 03:00:00`, a cron schedule like `0 3 * * *` or a shortcut like `hourly` or `daily`. Creates a
+        /// one-shot service and a `.ser.timer` starting it
+        #[arg(long, conflicts_with_all = ["enable", "auto_restart"])]
+        schedule: Option<String>,
+
+        /// Restart the service when a file or folder changes, eg. during staging deploys. Can be repeated.
+        /// Folders aren't watched recursively. Creates a `.ser.path` unit
+        #[arg(short, long, conflicts_with = "schedule")]
+        watch: Vec<PathBuf>,
+
+        /// Sandbox the service. The working directory stays writable. `custom` adds the directives under
+        /// `[hardening]` in the config
+        #[arg(long, value_enum)]
+        hardening: Option<Hardening>,
+
+        /// Tag the service to act on a group of services, eg. `ser restart @backend`. Can be repeated
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+
+        /// Optional args passed to the file or appended to `--cmd`. Eg. to run `node index.js --foo bar` call
+        /// `ser create index.js -- --foo bar`
         #[arg(last = true)]
         internal_args: Vec<String>,
     },
 
-    /// Open a text editor to create or edit the .service file for a service
+    /// Open a text editor to create or edit the .service file for a service, or one of its drop-ins
     #[command(arg_required_else_help = true)]
     Edit {
         /// The service name, eg. hello-world
@@ -76,51 +163,130 @@ pub enum Commands {
         /// Custom editor to use. Default nano
         #[arg(short, long, default_value = "nano")]
         editor: String,
+
+        /// Edit a drop-in of the service instead, eg. secrets.conf, env.conf or limits.conf. Created if missing
+        #[arg(short, long)]
+        drop_in: Option<String>,
     },
 
-    /// Start a service
+    /// Start services
     #[command(arg_required_else_help = true)]
     Start {
-        /// The service name, eg. hello-world
-        name: String,
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
     },
-    /// Stop a service
+    /// Stop services
     #[command(arg_required_else_help = true)]
     Stop {
-        /// The service name, eg. hello-world
-        name: String,
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+
+    /// Restart services, starting those that aren't running
+    #[command(arg_required_else_help = true)]
+    Restart {
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+
+    /// Restart services only if they are running
+    #[command(arg_required_else_help = true)]
+    TryRestart {
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
     },
 
-    /// Enable a service to start on boot. Doesn't immediately start the service. To do so use the `start` command.
+    /// Enable services to start on boot. Doesn't immediately start the services. To do so use the `start` command.
     #[command(arg_required_else_help = true)]
     Enable {
-        /// The service name, eg. hello-world
-        name: String,
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
     },
 
-    /// Disable a service from starting on boot
+    /// Disable services from starting on boot
     #[command(arg_required_else_help = true)]
     Disable {
-        /// The service name, eg. hello-world
-        name: String,
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
     },
 
-    /// Delete a service, stopping and disabling it if necessary and removing the .service file (alias: delete, rm, remove)
+    /// Delete services, stopping and disabling them if necessary and removing the .service files (alias: delete, rm, remove)
     #[command(arg_required_else_help = true, alias = "rm", alias = "remove")]
     Delete {
-        /// The service name, eg. hello-world
-        name: String,
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
+
+        /// Delete several services without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// View the status of your services (alias: ls)
     #[command(alias = "ls")]
-    Status {},
+    Status {
+        /// Also show sub-state, uptime, restart count, last result and exit status
+        #[arg(short, long)]
+        wide: bool,
 
-    /// View logs for a service
+        /// Only show services having this tag, eg. `backend` or `@backend`
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+
+    /// Score the sandboxing of your services like `systemd-analyze security`, from 0 (safe) to 10 (exposed).
+    /// Pass service names to list their missing directives
+    Audit {
+        /// Only audit these services and list their missing directives. Accepts `all`, `@tag` and quoted
+        /// globs like 'api-*'
+        names: Vec<String>,
+
+        /// Exit with an error if a service scores above this, eg. `5.0`. Defaults to `audit_threshold` in
+        /// the config
+        #[arg(long)]
+        threshold: Option<f32>,
+    },
+
+    /// List scheduled services with their schedule, last and next run, and the result of the last run
+    Timers {},
+
+    /// Run a scheduled service once now, without waiting for its schedule
     #[command(arg_required_else_help = true)]
-    Logs {
-        /// The service name
+    RunNow {
+        /// The service name, eg. backup
         name: String,
+    },
+
+    /// Open a live dashboard of your services. Select a service to follow its logs and start, stop,
+    /// restart, enable or disable it with key bindings
+    Monit {},
+
+    /// Stream state changes and jobs of your services. Prints a line, or a JSON or YAML document,
+    /// per event
+    Events {
+        /// Only watch these services. Accepts `all`, `@tag` and quoted globs like 'api-*'. Watches
+        /// every service, including services created later, if omitted
+        names: Vec<String>,
+
+        /// Run a command with `sh -c` on every event. The event is passed in the environment
+        /// variables SER_TIMESTAMP, SER_SERVICE, SER_EVENT, SER_ACTIVE_STATE, SER_SUB_STATE,
+        /// SER_JOB_ID and SER_JOB_RESULT
+        #[arg(long)]
+        exec: Option<String>,
+    },
+
+    /// View logs for services. Logs of multiple services are interleaved by time
+    #[command(arg_required_else_help = true)]
+    Logs {
+        /// Service names, eg. hello-world. Use `all` for every service, `@tag` for tagged services or a quoted glob like 'api-*'
+        #[arg(required = true)]
+        names: Vec<String>,
 
         /// Output the last N lines, instead of the default 15
         #[arg(short = 'n', long, default_value_t = 15)]
@@ -131,6 +297,18 @@ pub enum Commands {
         follow: bool,
     },
 
+    /// Explain why a service failed: how it exited, whether it is crash looping or was OOM-killed, likely
+    /// causes and the logs of the failing run
+    #[command(arg_required_else_help = true)]
+    Why {
+        /// The service name, eg. hello-world
+        name: String,
+
+        /// Output the last N log lines of the failing run
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: u32,
+    },
+
     /// Reloads a service having an `ExecScript`
     #[command(arg_required_else_help = true)]
     Reload {
@@ -161,53 +339,281 @@ pub enum Commands {
         /// The new name
         new_name: String,
     },
+
+    /// Change resource limits of a service. Limits apply right away and are persisted in a drop-in. Prints the
+    /// current limits if none are passed
+    #[command(arg_required_else_help = true)]
+    Limit {
+        /// The service name, eg hello-world
+        name: String,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
+    },
+
+    /// Restart a service when files or folders change, using a `.ser.path` unit. Passed paths replace the
+    /// watched paths. Prints the watched paths if none are passed
+    #[command(arg_required_else_help = true)]
+    Watch {
+        /// The service name, eg hello-world
+        name: String,
+
+        /// Files or folders to watch. Folders aren't watched recursively
+        #[arg(conflicts_with = "stop")]
+        paths: Vec<PathBuf>,
+
+        /// Stop watching the service
+        #[arg(long)]
+        stop: bool,
+    },
+
+    /// Manage variables in the env file of a service. The file is only readable by its owner
+    #[command(arg_required_else_help = true)]
+    Env {
+        #[command(subcommand)]
+        command: EnvCommands,
+    },
+
+    /// Manage encrypted secrets of a service. Secrets are encrypted with `systemd-creds` and never stored in plain
+    /// text. The service reads them from files in `$CREDENTIALS_DIRECTORY`
+    #[command(arg_required_else_help = true)]
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommands,
+    },
+
+    /// Create, update or delete services to match a `servicer.toml` manifest. Services not present in the
+    /// manifest are listed as unmanaged, and deleted with --prune. The plan is printed before making changes.
+    Apply {
+        /// Path to the manifest
+        #[arg(short, long, default_value = "servicer.toml")]
+        file: PathBuf,
+
+        /// Print the plan without making changes
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Delete services not present in the manifest
+        #[arg(long)]
+        prune: bool,
+    },
+
+    /// Convert jobs from other schedulers to servicer services
+    Import {
+        #[command(subcommand)]
+        command: ImportCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EnvCommands {
+    /// List variables of a service (alias: ls)
+    #[command(arg_required_else_help = true, alias = "ls")]
+    List {
+        /// The service name, eg hello-world
+        name: String,
+    },
+
+    /// Set variables of a service, eg. `ser env set api PORT=3000 "GREETING=hello world"`
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// The service name, eg hello-world
+        name: String,
+
+        /// Variables to set
+        #[arg(required = true, value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        vars: Vec<(String, String)>,
+
+        /// Restart the service if it is running, without asking
+        #[arg(short, long)]
+        restart: bool,
+    },
+
+    /// Remove variables of a service (alias: rm)
+    #[command(arg_required_else_help = true, alias = "rm")]
+    Unset {
+        /// The service name, eg hello-world
+        name: String,
+
+        /// Keys to remove
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        /// Restart the service if it is running, without asking
+        #[arg(short, long)]
+        restart: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretCommands {
+    /// Encrypt and store a secret. The value is prompted for, or read from stdin, eg.
+    /// `cat key.pem | ser secret set api TLS_KEY`
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// The service name, eg hello-world
+        name: String,
+
+        /// The secret name, eg. API_KEY
+        key: String,
+
+        /// Restart the service if it is running, without asking
+        #[arg(short, long)]
+        restart: bool,
+    },
+
+    /// List secrets of a service. Values are never printed (alias: ls)
+    #[command(arg_required_else_help = true, alias = "ls")]
+    List {
+        /// The service name, eg hello-world
+        name: String,
+    },
+
+    /// Remove secrets of a service (alias: remove)
+    #[command(arg_required_else_help = true, alias = "remove")]
+    Rm {
+        /// The service name, eg hello-world
+        name: String,
+
+        /// Secret names
+        #[arg(required = true)]
+        keys: Vec<String>,
+
+        /// Restart the service if it is running, without asking
+        #[arg(short, long)]
+        restart: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ImportCommands {
+    /// Convert crontab jobs to scheduled services with timers. `@reboot` jobs become services enabled on
+    /// boot. Lines that can't be converted are reported
+    Crontab {
+        /// Import the crontab of this user and run the services as them, like `crontab -u`. Defaults to
+        /// the user running the services. Named `--crontab-user` because `--user` selects user services
+        #[arg(short = 'u', long = "crontab-user", value_name = "USER")]
+        crontab_user: Option<String>,
+
+        /// Read a crontab file instead of the user's crontab
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Print the services without creating them
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    let config = load_config().await?;
+    // The config default only applies without sudo. `sudo ser ...` keeps managing system services.
+    let user_scope = args.user || (config.user && !args.system && !is_root());
+
+    if user_scope && is_root() {
+        return Err(
+            "User services are managed without sudo. Retry without sudo or pass --system".into(),
+        );
+    }
+    set_user_scope(user_scope);
+    set_output_format(args.output);
+    set_config(config);
+
     match args.command {
         Commands::Create {
             path,
+            cmd,
+            cwd,
             name,
             start,
             enable,
             auto_restart,
+            mut restart,
             interpreter,
             env_vars,
+            env,
+            env_file,
+            secrets,
+            limits,
+            hardening,
+            schedule,
+            watch,
+            tags,
             internal_args,
         } => {
-            handle_create_service(
+            let mut env_vars = match env_vars {
+                Some(vars) => parse_env_vars(&vars)?,
+                None => vec![],
+            };
+            env_vars.extend(env);
+
+            if auto_restart {
+                restart.policy = Some(RestartPolicy::Always);
+            }
+
+            handle_create_service(CreateServiceOptions {
                 path,
+                command: cmd,
+                working_directory: cwd,
                 name,
+                user: None,
                 start,
                 enable,
-                auto_restart,
+                restart,
                 interpreter,
                 env_vars,
+                env_file,
+                secrets,
+                limits,
+                hardening,
+                schedule,
+                watch,
+                tags,
                 internal_args,
-            )
+            })
             .await?
         }
 
-        Commands::Start { name } => handle_start_service(&name, true).await?,
+        Commands::Start { names } => handle_start_service(&names, true).await?,
+
+        Commands::Stop { names } => handle_stop_service(&names, true).await?,
+
+        Commands::Restart { names } => handle_restart_service(&names, false, true).await?,
+
+        Commands::TryRestart { names } => handle_restart_service(&names, true, true).await?,
+
+        Commands::Enable { names } => handle_enable_service(&names, true).await?,
 
-        Commands::Stop { name } => handle_stop_service(&name, true).await?,
+        Commands::Disable { names } => handle_disable_service(&names, true).await?,
 
-        Commands::Enable { name } => handle_enable_service(&name, true).await?,
+        Commands::Status { wide, tag } => handle_show_status(wide, tag.as_deref()).await?,
 
-        Commands::Disable { name } => handle_disable_service(&name, true).await?,
+        Commands::Audit { names, threshold } => handle_audit(&names, threshold).await?,
 
-        Commands::Status {} => handle_show_status().await?,
+        Commands::Timers {} => handle_timers().await?,
+
+        Commands::RunNow { name } => handle_run_now(&name).await?,
+
+        Commands::Monit {} => handle_monit().await?,
+
+        Commands::Events { names, exec } => handle_events(&names, exec).await?,
 
         Commands::Logs {
-            name,
+            names,
             lines,
             follow,
-        } => handle_show_logs(&name, lines, follow).await?,
+        } => handle_show_logs(&names, lines, follow).await?,
 
-        Commands::Edit { name, editor } => handle_edit_service_file(&name, &editor).await?,
+        Commands::Why { name, lines } => handle_why(&name, lines).await?,
+
+        Commands::Edit {
+            name,
+            editor,
+            drop_in,
+        } => handle_edit_service_file(&name, &editor, drop_in.as_deref()).await?,
 
         Commands::Reload { name } => handle_reload_service(&name, true).await?,
 
@@ -215,9 +621,53 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         Commands::Which { name } => handle_print_paths(&name).await?,
 
-        Commands::Delete { name } => handle_delete_service(&name, true).await?,
+        Commands::Delete { names, yes } => handle_delete_service(&names, true, yes).await?,
 
         Commands::Rename { name, new_name } => handle_rename_service(&name, &new_name).await?,
+
+        Commands::Limit { name, limits } => handle_limit(&name, &limits).await?,
+
+        Commands::Watch { name, paths, stop } => handle_watch(&name, &paths, stop).await?,
+
+        Commands::Env { command } => match command {
+            EnvCommands::List { name } => handle_env_list(&name).await?,
+            EnvCommands::Set {
+                name,
+                vars,
+                restart,
+            } => handle_env_set(&name, &vars, restart).await?,
+            EnvCommands::Unset {
+                name,
+                keys,
+                restart,
+            } => handle_env_unset(&name, &keys, restart).await?,
+        },
+
+        Commands::Secret { command } => match command {
+            SecretCommands::Set { name, key, restart } => {
+                handle_secret_set(&name, &key, restart).await?
+            }
+            SecretCommands::List { name } => handle_secret_list(&name).await?,
+            SecretCommands::Rm {
+                name,
+                keys,
+                restart,
+            } => handle_secret_rm(&name, &keys, restart).await?,
+        },
+
+        Commands::Apply {
+            file,
+            dry_run,
+            prune,
+        } => handle_apply_manifest(&file, dry_run, prune).await?,
+
+        Commands::Import { command } => match command {
+            ImportCommands::Crontab {
+                crontab_user,
+                file,
+                dry_run,
+            } => handle_import_crontab(crontab_user.as_deref(), file.as_deref(), dry_run).await?,
+        },
     }
 
     Ok(())
@@ -0,0 +1,512 @@
+use tokio::fs;
+
+use super::{
+    scope::is_user_scope,
+    service_names::{get_drop_in_paths, get_service_file_path},
+    unit_file::get_unit_values,
+};
+
+/// A sandboxing check, modelled on `systemd-analyze security`
+struct Check {
+    /// The directive checked, eg. `ProtectSystem`
+    directive: &'static str,
+
+    /// Share of the exposure score. Higher is more important.
+    weight: u32,
+
+    /// Line to add to pass the check
+    suggestion: &'static str,
+
+    /// Exposure from 0 (safe) to 1 (exposed) for the value of the directive, if set
+    exposure: fn(Option<&str>) -> f32,
+
+    /// Skipped for user services, where it doesn't apply
+    system_only: bool,
+}
+
+/// A check failed by a service
+pub struct Finding {
+    /// The directive checked, eg. `ProtectSystem`
+    pub directive: String,
+
+    /// Weight lost by the service, i.e. the check's weight scaled by its exposure
+    pub weight: u32,
+
+    /// Line to add to pass the check
+    pub suggestion: String,
+}
+
+/// Exposure of a service
+pub struct Audit {
+    /// Score from 0 (safe) to 10 (exposed), like `systemd-analyze security`
+    pub exposure: f32,
+
+    /// Failed checks, most important first
+    pub findings: Vec<Finding>,
+}
+
+/// Checks in order of importance
+const CHECKS: &[Check] = &[
+    Check {
+        directive: "User",
+        weight: 2000,
+        suggestion: "User=<an unprivileged user>",
+        exposure: |value| match value {
+            Some("root" | "0") | None => 1.0,
+            Some(_) => 0.0,
+        },
+        system_only: true,
+    },
+    Check {
+        directive: "CapabilityBoundingSet",
+        weight: 1500,
+        suggestion: "CapabilityBoundingSet=",
+        exposure: get_capability_exposure,
+        system_only: true,
+    },
+    Check {
+        directive: "NoNewPrivileges",
+        weight: 1000,
+        suggestion: "NoNewPrivileges=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectSystem",
+        weight: 1000,
+        suggestion: "ProtectSystem=strict",
+        exposure: |value| match value {
+            Some("strict") => 0.0,
+            Some("full") => 0.3,
+            Some(value) if is_true(value) => 0.5,
+            _ => 1.0,
+        },
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectHome",
+        weight: 1000,
+        suggestion: "ProtectHome=yes",
+        exposure: |value| match value {
+            Some("read-only") => 0.5,
+            Some("tmpfs") => 0.0,
+            Some(value) if is_true(value) => 0.0,
+            _ => 1.0,
+        },
+        system_only: false,
+    },
+    Check {
+        directive: "PrivateTmp",
+        weight: 1000,
+        suggestion: "PrivateTmp=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "PrivateDevices",
+        weight: 1000,
+        suggestion: "PrivateDevices=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectKernelTunables",
+        weight: 1000,
+        suggestion: "ProtectKernelTunables=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectKernelModules",
+        weight: 1000,
+        suggestion: "ProtectKernelModules=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectKernelLogs",
+        weight: 1000,
+        suggestion: "ProtectKernelLogs=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectControlGroups",
+        weight: 1000,
+        suggestion: "ProtectControlGroups=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectClock",
+        weight: 1000,
+        suggestion: "ProtectClock=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "RestrictSUIDSGID",
+        weight: 1000,
+        suggestion: "RestrictSUIDSGID=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "SystemCallArchitectures",
+        weight: 1000,
+        suggestion: "SystemCallArchitectures=native",
+        exposure: |value| match value {
+            Some("native") => 0.0,
+            _ => 1.0,
+        },
+        system_only: false,
+    },
+    Check {
+        directive: "SystemCallFilter",
+        weight: 1000,
+        suggestion: "SystemCallFilter=@system-service",
+        exposure: get_list_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "RestrictAddressFamilies",
+        weight: 1000,
+        suggestion: "RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6",
+        exposure: get_list_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "RestrictNamespaces",
+        weight: 500,
+        suggestion: "RestrictNamespaces=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "RestrictRealtime",
+        weight: 500,
+        suggestion: "RestrictRealtime=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "PrivateNetwork",
+        weight: 500,
+        suggestion: "PrivateNetwork=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "LockPersonality",
+        weight: 100,
+        suggestion: "LockPersonality=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "MemoryDenyWriteExecute",
+        weight: 100,
+        suggestion: "MemoryDenyWriteExecute=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+    Check {
+        directive: "ProtectHostname",
+        weight: 50,
+        suggestion: "ProtectHostname=yes",
+        exposure: get_boolean_exposure,
+        system_only: false,
+    },
+];
+
+/// Scores the exposure of a service from its unit file and drop-ins. Works offline.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn audit_service(full_service_name: &str) -> std::io::Result<Audit> {
+    let mut bodies = vec![fs::read_to_string(get_service_file_path(full_service_name)).await?];
+
+    for path in get_drop_in_paths(full_service_name).await? {
+        bodies.push(fs::read_to_string(path).await?);
+    }
+
+    Ok(audit_unit_files(&bodies, is_user_scope()))
+}
+
+/// Scores the exposure of a service from the contents of its unit file and drop-ins
+///
+/// # Arguments
+///
+/// * `bodies` - The unit file followed by its drop-ins. Later files override earlier ones
+/// * `is_user` - Whether the service is a user service, skipping the checks for system services
+///
+fn audit_unit_files(bodies: &[String], is_user: bool) -> Audit {
+    let mut total_weight = 0.0;
+    let mut lost_weight = 0.0;
+    let mut findings = vec![];
+
+    for check in CHECKS
+        .iter()
+        .filter(|check| !(is_user && check.system_only))
+    {
+        // Later files override earlier ones
+        let value = bodies
+            .iter()
+            .rev()
+            .find_map(|body| get_unit_values(body, check.directive).pop());
+
+        let exposure = (check.exposure)(value.as_deref());
+        let weight = check.weight as f32;
+
+        total_weight += weight;
+        lost_weight += weight * exposure;
+
+        if exposure > 0.0 {
+            findings.push(Finding {
+                directive: check.directive.to_string(),
+                weight: (weight * exposure).round() as u32,
+                suggestion: check.suggestion.to_string(),
+            });
+        }
+    }
+
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.weight));
+
+    Audit {
+        exposure: (lost_weight / total_weight * 100.0).round() / 10.0,
+        findings,
+    }
+}
+
+/// Rating of an exposure score, using the levels of `systemd-analyze security`
+///
+/// # Arguments
+///
+/// * `exposure` - Score from 0 to 10
+///
+pub fn get_exposure_rating(exposure: f32) -> &'static str {
+    match exposure {
+        exposure if exposure <= 1.0 => "SAFE",
+        exposure if exposure <= 4.0 => "OK",
+        exposure if exposure <= 7.0 => "MEDIUM",
+        exposure if exposure <= 9.0 => "EXPOSED",
+        _ => "UNSAFE",
+    }
+}
+
+/// Whether a unit file boolean is true
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn is_true(value: &str) -> bool {
+    matches!(value, "yes" | "true" | "on" | "1")
+}
+
+/// Exposure of a boolean directive. Passes if true.
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn get_boolean_exposure(value: Option<&str>) -> f32 {
+    match value {
+        Some(value) if is_true(value) => 0.0,
+        _ => 1.0,
+    }
+}
+
+/// Exposure of an allow list like `SystemCallFilter`. Passes if an allow list is set. Deny lists,
+/// starting with `~`, only pass partially.
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn get_list_exposure(value: Option<&str>) -> f32 {
+    match value {
+        Some(value) if value.starts_with('~') => 0.5,
+        Some(value) if !value.is_empty() => 0.0,
+        _ => 1.0,
+    }
+}
+
+/// Exposure of `CapabilityBoundingSet`. An allow list passes if it leaves out `CAP_SYS_ADMIN`, and a
+/// deny list, starting with `~`, passes if it includes it.
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn get_capability_exposure(value: Option<&str>) -> f32 {
+    let Some(value) = value else {
+        return 1.0;
+    };
+
+    let (is_deny_list, capabilities) = match value.strip_prefix('~') {
+        Some(capabilities) => (true, capabilities),
+        None => (false, value),
+    };
+    let has_sys_admin = capabilities
+        .split_whitespace()
+        .any(|capability| capability == "CAP_SYS_ADMIN");
+
+    if is_deny_list == has_sys_admin {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit file passing every check
+    fn hardened_body() -> String {
+        let mut body = String::from("[Service]\nExecStart=/usr/bin/app\n");
+
+        for check in CHECKS {
+            match check.directive {
+                "User" => body.push_str("User=app\n"),
+                _ => body.push_str(&format!("{}\n", check.suggestion)),
+            }
+        }
+
+        body
+    }
+
+    /// Directives of the findings, most important first
+    fn directives(audit: &Audit) -> Vec<&str> {
+        audit
+            .findings
+            .iter()
+            .map(|finding| finding.directive.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn scores_unhardened_services_as_unsafe() {
+        let audit = audit_unit_files(&["[Service]\nExecStart=/usr/bin/app\n".to_string()], false);
+
+        assert_eq!(audit.exposure, 10.0);
+        assert_eq!(get_exposure_rating(audit.exposure), "UNSAFE");
+        assert_eq!(audit.findings.len(), CHECKS.len());
+        assert_eq!(directives(&audit)[..2], ["User", "CapabilityBoundingSet"]);
+        assert_eq!(audit.findings[0].weight, 2000);
+    }
+
+    #[test]
+    fn scores_hardened_services_as_safe() {
+        let audit = audit_unit_files(&[hardened_body()], false);
+
+        assert_eq!(audit.exposure, 0.0);
+        assert_eq!(get_exposure_rating(audit.exposure), "SAFE");
+        assert!(audit.findings.is_empty());
+    }
+
+    #[test]
+    fn scores_partial_exposure() {
+        let body = hardened_body()
+            .replace("ProtectSystem=strict", "ProtectSystem=full")
+            .replace(
+                "SystemCallFilter=@system-service",
+                "SystemCallFilter=~@mount",
+            );
+        let audit = audit_unit_files(&[body], false);
+
+        assert_eq!(directives(&audit), ["SystemCallFilter", "ProtectSystem"]);
+        assert_eq!(audit.findings[0].weight, 500);
+        assert_eq!(audit.findings[1].weight, 300);
+        assert!(audit.exposure > 0.0 && audit.exposure <= 1.0);
+    }
+
+    #[test]
+    fn lets_drop_ins_override_the_unit_file() {
+        let drop_in = "[Service]\nNoNewPrivileges=no\nUser=root\n".to_string();
+        let audit = audit_unit_files(&[hardened_body(), drop_in], false);
+
+        assert_eq!(directives(&audit), ["User", "NoNewPrivileges"]);
+
+        let drop_in = "[Service]\nNoNewPrivileges=yes\n".to_string();
+        let audit = audit_unit_files(
+            &[hardened_body().replace("NoNewPrivileges=yes", ""), drop_in],
+            false,
+        );
+
+        assert!(audit.findings.is_empty());
+    }
+
+    #[test]
+    fn skips_system_checks_for_user_services() {
+        let audit = audit_unit_files(&["[Service]\nExecStart=/usr/bin/app\n".to_string()], true);
+
+        assert_eq!(audit.exposure, 10.0);
+        assert_eq!(
+            audit.findings.len(),
+            CHECKS.iter().filter(|check| !check.system_only).count()
+        );
+        assert!(!directives(&audit).contains(&"User"));
+        assert!(!directives(&audit).contains(&"CapabilityBoundingSet"));
+
+        let body = hardened_body().replace("User=app\n", "");
+        assert!(audit_unit_files(&[body], true).findings.is_empty());
+    }
+
+    #[test]
+    fn scores_capability_bounding_sets() {
+        // Value, exposure
+        let cases = [
+            (None, 1.0),
+            (Some(""), 0.0),
+            (Some("CAP_NET_BIND_SERVICE"), 0.0),
+            (Some("CAP_NET_BIND_SERVICE CAP_SYS_ADMIN"), 1.0),
+            (Some("CAP_SYS_ADMIN_EXTRA"), 0.0),
+            (Some("~CAP_SYS_ADMIN"), 0.0),
+            (Some("~CAP_NET_RAW CAP_SYS_ADMIN"), 0.0),
+            (Some("~CAP_NET_RAW"), 1.0),
+            (Some("~"), 1.0),
+        ];
+
+        for (value, exposure) in cases {
+            assert_eq!(get_capability_exposure(value), exposure, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn scores_lists_and_booleans() {
+        assert_eq!(get_list_exposure(None), 1.0);
+        assert_eq!(get_list_exposure(Some("")), 1.0);
+        assert_eq!(get_list_exposure(Some("@system-service")), 0.0);
+        assert_eq!(get_list_exposure(Some("AF_UNIX AF_INET")), 0.0);
+        assert_eq!(get_list_exposure(Some("~@mount @swap")), 0.5);
+
+        for value in ["yes", "true", "on", "1"] {
+            assert_eq!(get_boolean_exposure(Some(value)), 0.0, "{value}");
+        }
+
+        for value in [None, Some("no"), Some("false"), Some("0"), Some("")] {
+            assert_eq!(get_boolean_exposure(value), 1.0, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn rates_exposure() {
+        let cases = [
+            (0.0, "SAFE"),
+            (1.0, "SAFE"),
+            (1.1, "OK"),
+            (4.0, "OK"),
+            (6.5, "MEDIUM"),
+            (8.2, "EXPOSED"),
+            (9.5, "UNSAFE"),
+        ];
+
+        for (exposure, rating) in cases {
+            assert_eq!(get_exposure_rating(exposure), rating, "{exposure}");
+        }
+    }
+}
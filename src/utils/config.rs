@@ -0,0 +1,92 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Config loaded at startup
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Servicer config. Read from `~/.config/servicer/config.toml`, falling back to
+/// `/etc/servicer/config.toml`.
+///
+/// ```toml
+/// # Manage per-user services with `systemd --user` unless `--system` is passed
+/// user = true
+///
+/// # `ser audit` fails if a service scores above this
+/// audit_threshold = 5.0
+///
+/// # Interpreters by file extension. Overrides the built-in ones
+/// [interpreters]
+/// ts = "bun"
+/// jar = "java -jar"
+///
+/// # Directives added by `--hardening custom`
+/// [hardening]
+/// NoNewPrivileges = "yes"
+/// ProtectSystem = "strict"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Manage per-user services by default
+    pub user: bool,
+
+    /// Command used to run files with an extension, eg. `ts = "deno run --allow-net"`
+    pub interpreters: BTreeMap<String, String>,
+
+    /// Highest exposure accepted by `ser audit` before failing
+    pub audit_threshold: Option<f32>,
+
+    /// `[Service]` directives added by `--hardening custom`, eg. `ProtectSystem = "strict"`
+    pub hardening: BTreeMap<String, String>,
+}
+
+/// Paths searched for a config file, in order of priority
+pub fn get_config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+
+    let user_config_dir = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| Path::new(&home).join(".config")));
+
+    if let Ok(user_config_dir) = user_config_dir {
+        paths.push(user_config_dir.join("servicer/config.toml"));
+    }
+
+    paths.push(PathBuf::from("/etc/servicer/config.toml"));
+
+    paths
+}
+
+/// Reads the first config file found. Returns the default config if there is no config file.
+pub async fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    for path in get_config_paths() {
+        if path.is_file() {
+            let contents = tokio::fs::read_to_string(&path).await?;
+
+            return toml::from_str(&contents)
+                .map_err(|error| format!("Invalid config {}: {error}", path.display()).into());
+        }
+    }
+
+    Ok(Config::default())
+}
+
+/// Sets the config used by the rest of the program
+///
+/// # Arguments
+///
+/// * `config`
+///
+pub fn set_config(config: Config) {
+    CONFIG.set(config).expect("Config is already set");
+}
+
+/// The config loaded at startup
+pub fn get_config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,242 @@
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    path::PathBuf,
+    process::Stdio,
+};
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+
+use super::{
+    scope::is_user_scope,
+    service_names::{get_drop_in_folder, get_drop_in_paths, get_service_file_path},
+    unit_file::get_unit_values,
+};
+
+/// Drop-in holding the encrypted secrets of a service
+const SECRETS_DROP_IN: &str = "secrets.conf";
+
+/// Directives passing encrypted credentials to a service
+const CREDENTIAL_KEYS: &[&str] = &["SetCredentialEncrypted", "LoadCredentialEncrypted"];
+
+/// An encrypted credential of a service
+pub struct Secret {
+    /// The credential name. The service reads it from `$CREDENTIALS_DIRECTORY/<name>`
+    pub name: String,
+
+    /// The directive passing the credential, eg. `SetCredentialEncrypted`
+    pub directive: String,
+
+    /// The unit file or drop-in declaring the credential
+    pub path: PathBuf,
+}
+
+/// Path of the drop-in holding the secrets of a service
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub fn get_secrets_drop_in_path(full_service_name: &str) -> PathBuf {
+    get_drop_in_folder(full_service_name).join(SECRETS_DROP_IN)
+}
+
+/// Checks that a secret name can be used as a credential file name
+///
+/// # Arguments
+///
+/// * `name` - The secret name, eg. `API_KEY`
+///
+pub fn validate_secret_name(name: &str) -> Result<(), String> {
+    let is_valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_' | '.'));
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid secret name `{name}`. Names may only have letters, digits, '-', '_' and '.'"
+        ))
+    }
+}
+
+/// Encrypts a value with `systemd-creds`. Returns the base64 encoded credential. The value is passed
+/// through stdin so that it never shows up in the process list.
+///
+/// # Arguments
+///
+/// * `name` - The credential name, embedded in the credential
+/// * `value`
+///
+pub async fn encrypt_secret(
+    name: &str,
+    value: &[u8],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut command = Command::new("systemd-creds");
+
+    if is_user_scope() {
+        command.arg("--user");
+    }
+
+    let mut child = command
+        .arg("encrypt")
+        .arg(format!("--name={name}"))
+        .arg("-")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Failed to run systemd-creds: {error}"))?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(value).await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+
+    if !output.status.success() {
+        let mut message = format!(
+            "systemd-creds failed to encrypt {name}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+
+        if is_user_scope() {
+            message.push_str("\nEncrypted secrets for user services need systemd 256 or newer");
+        }
+
+        return Err(message.into());
+    }
+
+    // The credential is wrapped over multiple lines
+    Ok(String::from_utf8(output.stdout)?
+        .split_whitespace()
+        .collect())
+}
+
+/// Encrypted credentials of a service, declared in its unit file or drop-ins
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn get_secrets(full_service_name: &str) -> std::io::Result<Vec<Secret>> {
+    let mut paths = vec![get_service_file_path(full_service_name)];
+    paths.extend(get_drop_in_paths(full_service_name).await?);
+
+    let mut secrets = vec![];
+
+    for path in paths {
+        let body = fs::read_to_string(&path).await?;
+
+        for directive in CREDENTIAL_KEYS {
+            for value in get_unit_values(&body, directive) {
+                let name = value.split(':').next().unwrap_or_default().trim();
+
+                secrets.push(Secret {
+                    name: name.to_string(),
+                    directive: directive.to_string(),
+                    path: path.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(secrets)
+}
+
+/// Encrypted secrets in the secrets drop-in of a service, as name and credential pairs
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn read_secrets_drop_in(full_service_name: &str) -> Vec<(String, String)> {
+    let body = fs::read_to_string(get_secrets_drop_in_path(full_service_name))
+        .await
+        .unwrap_or_default();
+
+    get_unit_values(&body, "SetCredentialEncrypted")
+        .iter()
+        .filter_map(|value| value.split_once(':'))
+        .map(|(name, credential)| (name.trim().to_string(), credential.trim().to_string()))
+        .collect()
+}
+
+/// Writes the secrets drop-in of a service. The drop-in is removed if there are no secrets.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `secrets` - Name and encrypted credential pairs
+///
+pub async fn write_secrets_drop_in(
+    full_service_name: &str,
+    secrets: &[(String, String)],
+) -> std::io::Result<()> {
+    let path = get_secrets_drop_in_path(full_service_name);
+
+    if secrets.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).await?;
+        }
+
+        return Ok(());
+    }
+
+    let mut body = String::from("# Generated with Servicer\n[Service]\n");
+    for (name, credential) in secrets {
+        body.push_str(&format!("SetCredentialEncrypted={name}:{credential}\n"));
+    }
+
+    fs::create_dir_all(get_drop_in_folder(full_service_name)).await?;
+    fs::write(path, body).await
+}
+
+/// Reads the value of a secret. Prompts without echoing the input if stdin is a terminal, otherwise
+/// reads stdin till the end, eg. `cat key.pem | ser secret set api TLS_KEY`.
+///
+/// # Arguments
+///
+/// * `name` - The secret name, shown in the prompt
+///
+pub fn read_secret_value(name: &str) -> io::Result<Vec<u8>> {
+    let mut stdin = io::stdin();
+
+    if !stdin.is_terminal() {
+        let mut value = vec![];
+        stdin.read_to_end(&mut value)?;
+
+        // Drop the newline added by `echo`
+        if value.ends_with(b"\n") {
+            value.pop();
+        }
+
+        return Ok(value);
+    }
+
+    eprint!("Value for {name}: ");
+    io::stderr().flush()?;
+
+    let fd = libc::STDIN_FILENO;
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    let is_echo_disabled = unsafe { libc::tcgetattr(fd, &mut termios) } == 0 && {
+        let mut hidden = termios;
+        hidden.c_lflag &= !libc::ECHO;
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &hidden) == 0 }
+    };
+
+    let mut value = String::new();
+    let result = stdin.read_line(&mut value);
+
+    if is_echo_disabled {
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    }
+    eprintln!();
+
+    result?;
+
+    Ok(value.trim_end_matches(['\r', '\n']).as_bytes().to_vec())
+}
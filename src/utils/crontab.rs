@@ -0,0 +1,391 @@
+use std::path::Path;
+
+/// Shell running cron jobs unless `SHELL=` is set
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// Words skipped when naming a job after its command, eg. `cd /app && ./backup.sh` is named `backup`
+const SKIPPED_WORDS: &[&str] = &[
+    "cd", "&&", ";", "sudo", "nice", "ionice", "flock", "timeout",
+];
+
+/// A job read from a crontab
+pub struct CrontabJob {
+    /// Line of the job in the crontab, starting from 1
+    pub line_number: usize,
+
+    /// The schedule, eg. `0 3 * * *` or `@daily`
+    pub schedule: String,
+
+    /// The command line, run with the shell
+    pub command: String,
+
+    /// Shell running the command, eg. `/bin/sh`
+    pub shell: String,
+
+    /// Variables assigned above the job
+    pub env_vars: Vec<(String, String)>,
+}
+
+impl CrontabJob {
+    /// Whether the job runs on boot instead of on a schedule
+    pub fn is_reboot(&self) -> bool {
+        self.schedule == "@reboot"
+    }
+
+    /// Service name for the job, based on the program it runs. `backup` for `/opt/backup.sh --full`
+    pub fn get_default_name(&self) -> String {
+        let words = shell_words::split(&self.command).unwrap_or_else(|_| {
+            self.command
+                .split_whitespace()
+                .map(|word| word.to_string())
+                .collect()
+        });
+
+        let mut words = words.iter();
+        let mut program = None;
+
+        while let Some(word) = words.next() {
+            if word == "cd" {
+                // Skip the folder too
+                words.next();
+            } else if !word.contains('=') && !SKIPPED_WORDS.contains(&word.as_str()) {
+                program = Some(word);
+                break;
+            }
+        }
+
+        let name: String = program
+            .and_then(|program| Path::new(program).file_stem())
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default()
+            .chars()
+            .filter(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_'))
+            .collect();
+
+        if name.is_empty() {
+            "cron-job".to_string()
+        } else {
+            name.to_lowercase()
+        }
+    }
+}
+
+/// A crontab line that can't be imported
+pub struct SkippedLine {
+    /// Line in the crontab, starting from 1
+    pub line_number: usize,
+
+    /// Contents of the line
+    pub line: String,
+
+    /// Why the line can't be imported
+    pub reason: String,
+}
+
+/// Jobs and unsupported lines of a crontab
+#[derive(Default)]
+pub struct Crontab {
+    pub jobs: Vec<CrontabJob>,
+    pub skipped: Vec<SkippedLine>,
+}
+
+/// Parses a crontab in the format of `crontab -l`. Variable assignments apply to the jobs below
+/// them, like in cron. Comments and blank lines are ignored.
+///
+/// # Arguments
+///
+/// * `body` - Contents of the crontab
+///
+pub fn parse_crontab(body: &str) -> Crontab {
+    let mut crontab = Crontab::default();
+    let mut env_vars: Vec<(String, String)> = vec![];
+
+    for (index, line) in body.lines().enumerate() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let skip = |reason: &str| SkippedLine {
+            line_number,
+            line: trimmed.to_string(),
+            reason: reason.to_string(),
+        };
+
+        if let Some((key, value)) = parse_env_assignment(trimmed) {
+            match key.as_str() {
+                "MAILTO" | "MAILFROM" if !value.is_empty() => crontab.skipped.push(skip(
+                    "Output isn't mailed. It is logged to the journal, see `ser logs`",
+                )),
+                "CRON_TZ" => crontab.skipped.push(skip(
+                    "Time zones aren't supported. Schedules run in the system time zone",
+                )),
+                _ => {
+                    env_vars.retain(|(existing, _)| *existing != key);
+                    env_vars.push((key, value));
+                }
+            }
+            continue;
+        }
+
+        let field_count = if trimmed.starts_with('@') { 1 } else { 5 };
+        let Some((fields, command)) = split_fields(trimmed, field_count) else {
+            crontab
+                .skipped
+                .push(skip("Expected a schedule followed by a command"));
+            continue;
+        };
+
+        // Unescaped `%` ends the command and feeds the rest to stdin
+        if command.replace("\\%", "").contains('%') {
+            crontab.skipped.push(skip(
+                "`%` passes stdin to the command, which isn't supported. Use a pipe or escape it as `\\%`",
+            ));
+            continue;
+        }
+
+        let shell = env_vars
+            .iter()
+            .find(|(key, _)| key == "SHELL")
+            .map_or(DEFAULT_SHELL.to_string(), |(_, shell)| shell.clone());
+
+        crontab.jobs.push(CrontabJob {
+            line_number,
+            schedule: fields.join(" "),
+            command: command.replace("\\%", "%"),
+            shell,
+            env_vars: env_vars.clone(),
+        });
+    }
+
+    crontab
+}
+
+/// Parses a variable assignment like `PATH=/usr/bin:/bin` or `GREETING = "hello world"`. Quotes
+/// around the value are removed.
+///
+/// # Arguments
+///
+/// * `line`
+///
+fn parse_env_assignment(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once('=')?;
+    let key = key.trim();
+
+    let is_name = key
+        .chars()
+        .next()
+        .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+        && key
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_');
+
+    if !is_name {
+        return None;
+    }
+
+    let value = value.trim();
+    let value = ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value);
+
+    Some((key.to_string(), value.to_string()))
+}
+
+/// Splits the schedule fields from the command of a job line. The command keeps its spacing.
+///
+/// # Arguments
+///
+/// * `line`
+/// * `count` - Number of schedule fields
+///
+fn split_fields(line: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = vec![];
+    let mut rest = line;
+
+    for _ in 0..count {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    if rest.is_empty() {
+        None
+    } else {
+        Some((fields, rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::schedule::convert_schedule;
+
+    #[test]
+    fn converts_jobs_to_on_calendar() {
+        // Crontab line, `OnCalendar=` expression of its timer
+        let cases = [
+            ("0 3 * * * /opt/backup.sh", "*-*-* 3:0:00"),
+            ("@daily /opt/backup.sh", "daily"),
+            ("@midnight /opt/backup.sh", "daily"),
+            ("@hourly /opt/backup.sh", "hourly"),
+            (
+                "30 9 * * 1-5 /opt/report.sh",
+                "Mon,Tue,Wed,Thu,Fri *-*-* 9:30:00",
+            ),
+            ("0 9 * * mon-wed /opt/report.sh", "Mon,Tue,Wed *-*-* 9:0:00"),
+            ("0 0 * * 0,7 /opt/report.sh", "Sun *-*-* 0:0:00"),
+            ("0 9-17 * * * /opt/poll.sh", "*-*-* 9..17:0:00"),
+            ("*/15 * * * * /opt/poll.sh", "*-*-* *:0/15:00"),
+            ("5/20 */2 * * * /opt/poll.sh", "*-*-* 0/2:5/20:00"),
+            ("1-10/3 * * * * /opt/poll.sh", "*-*-* *:1,4,7,10:00"),
+            ("0 0 1 jan,jul * /opt/rotate.sh", "*-1,7-1 0:0:00"),
+        ];
+
+        for (line, on_calendar) in cases {
+            let crontab = parse_crontab(line);
+            assert_eq!(crontab.jobs.len(), 1, "{line}");
+
+            let job = &crontab.jobs[0];
+            assert!(!job.is_reboot(), "{line}");
+            assert_eq!(
+                convert_schedule(&job.schedule).as_deref(),
+                Ok(on_calendar),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn imports_reboot_jobs_without_a_schedule() {
+        let crontab = parse_crontab("@reboot /opt/start.sh --daemon");
+        let job = &crontab.jobs[0];
+
+        assert!(job.is_reboot());
+        assert_eq!(job.command, "/opt/start.sh --daemon");
+        assert!(convert_schedule(&job.schedule).is_err());
+    }
+
+    #[test]
+    fn applies_the_env_header_to_jobs_below_it() {
+        let crontab = parse_crontab(
+            "# m h dom mon dow command\n\
+             SHELL=/bin/bash\n\
+             PATH = \"/usr/local/bin:/usr/bin\"\n\
+             MAILTO=\"\"\n\
+             \n\
+             0 3 * * * cd /app && ./backup.sh\n\
+             GREETING='hello world'\n\
+             0 4 * * * echo $GREETING\n",
+        );
+
+        assert!(crontab.skipped.is_empty());
+        assert_eq!(crontab.jobs.len(), 2);
+
+        let backup = &crontab.jobs[0];
+        assert_eq!(backup.line_number, 6);
+        assert_eq!(backup.shell, "/bin/bash");
+        assert_eq!(backup.get_default_name(), "backup");
+        assert_eq!(
+            backup.env_vars,
+            [
+                ("SHELL".to_string(), "/bin/bash".to_string()),
+                ("PATH".to_string(), "/usr/local/bin:/usr/bin".to_string()),
+                ("MAILTO".to_string(), "".to_string()),
+            ]
+        );
+
+        let echo = &crontab.jobs[1];
+        assert_eq!(echo.line_number, 8);
+        assert_eq!(
+            echo.env_vars.last(),
+            Some(&("GREETING".to_string(), "hello world".to_string()))
+        );
+    }
+
+    #[test]
+    fn uses_the_default_shell() {
+        let crontab = parse_crontab("0 3 * * * /opt/backup.sh");
+
+        assert_eq!(crontab.jobs[0].shell, DEFAULT_SHELL);
+        assert!(crontab.jobs[0].env_vars.is_empty());
+    }
+
+    #[test]
+    fn keeps_escaped_percent_signs() {
+        let crontab = parse_crontab("0 3 * * * date +\\%F");
+
+        assert_eq!(crontab.jobs[0].command, "date +%F");
+    }
+
+    #[test]
+    fn skips_unsupported_lines() {
+        let lines = [
+            "MAILTO=admin@example.com",
+            "CRON_TZ=Europe/Paris",
+            "0 3 * * * mail -s backup admin%done",
+            "0 3 * * *",
+            "@daily",
+        ];
+
+        for line in lines {
+            let crontab = parse_crontab(line);
+
+            assert!(crontab.jobs.is_empty(), "{line}");
+            assert_eq!(crontab.skipped.len(), 1, "{line}");
+            assert_eq!(crontab.skipped[0].line, line);
+            assert_eq!(crontab.skipped[0].line_number, 1);
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_schedules() {
+        let lines = [
+            // cron matches either day, systemd needs both
+            "0 0 1 * 1 /opt/report.sh",
+            "60 * * * * /opt/report.sh",
+            "0 0 * * 8 /opt/report.sh",
+            "*/0 * * * * /opt/report.sh",
+            "0 0 * foo * /opt/report.sh",
+        ];
+
+        for line in lines {
+            let crontab = parse_crontab(line);
+
+            assert_eq!(crontab.jobs.len(), 1, "{line}");
+            assert!(
+                convert_schedule(&crontab.jobs[0].schedule).is_err(),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn names_jobs_after_their_program() {
+        let cases = [
+            ("0 3 * * * /opt/backup.sh --full", "backup"),
+            (
+                "0 3 * * * cd /app && NODE_ENV=production node index.js",
+                "node",
+            ),
+            ("0 3 * * * sudo /usr/bin/Certbot renew", "certbot"),
+            ("0 3 * * * ./run_job.py", "run_job"),
+            ("0 3 * * * cd /app", "cron-job"),
+        ];
+
+        for (line, name) in cases {
+            assert_eq!(
+                parse_crontab(line).jobs[0].get_default_name(),
+                name,
+                "{line}"
+            );
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+use super::{
+    scope::get_env_folder,
+    service_names::{get_drop_in_folder, get_service_file_path},
+    unit_file::{escape_specifiers, get_unit_values},
+};
+
+/// Drop-in loading the env file of a service created before `ser env` existed
+const ENV_DROP_IN: &str = "env.conf";
+
+/// Path of the env file of a service, edited with `ser env`. Loaded with `EnvironmentFile=` after
+/// the service's other variables, so that its values take priority.
+///
+/// # Arguments
+///
+/// * `name` - The short service name, eg. hello-world
+///
+pub fn get_env_file_path(name: &str) -> PathBuf {
+    get_env_folder().join(format!("{name}.env"))
+}
+
+/// `EnvironmentFile=` value for a file. Paths starting with `-` are optional, i.e. systemd doesn't
+/// fail if they are missing.
+///
+/// # Arguments
+///
+/// * `path` - Absolute path of the env file
+/// * `optional` - Don't fail if the file is missing
+///
+pub fn get_environment_file_value(path: &Path, optional: bool) -> String {
+    let path = escape_specifiers(&path.display().to_string());
+
+    if optional {
+        format!("-{path}")
+    } else {
+        path
+    }
+}
+
+/// Merges variables the way systemd merges repeated `Environment=` keys. A key keeps the position
+/// of its first assignment and the value of its last one.
+///
+/// # Arguments
+///
+/// * `vars` - Key and value pairs, later ones overriding earlier ones
+///
+pub fn merge_env_vars(vars: impl IntoIterator<Item = (String, String)>) -> Vec<(String, String)> {
+    let mut merged: Vec<(String, String)> = vec![];
+
+    for (key, value) in vars {
+        match merged
+            .iter_mut()
+            .find(|(current_key, _)| *current_key == key)
+        {
+            Some(current) => current.1 = value,
+            None => merged.push((key, value)),
+        }
+    }
+
+    merged
+}
+
+/// Reads the variables of an env file. Returns an empty list if the file doesn't exist.
+///
+/// # Arguments
+///
+/// * `path`
+///
+pub async fn read_env_file(path: &Path) -> std::io::Result<Vec<(String, String)>> {
+    match fs::read_to_string(path).await {
+        Ok(contents) => Ok(parse_env_file(&contents)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(error) => Err(error),
+    }
+}
+
+/// Writes variables to an env file readable only by its owner, since it may hold secrets
+///
+/// # Arguments
+///
+/// * `path`
+/// * `vars` - Key and value pairs
+///
+pub async fn write_env_file(path: &Path, vars: &[(String, String)]) -> std::io::Result<()> {
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder).await?;
+
+        // Only the env folder is private. Its parent also holds the config file.
+        fs::set_permissions(folder, std::fs::Permissions::from_mode(0o700)).await?;
+    }
+
+    let contents: String = vars
+        .iter()
+        .map(|(key, value)| format!("{key}={}\n", quote_env_value(value)))
+        .collect();
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .await?;
+
+    // The mode only applies to new files
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))
+        .await?;
+    file.write_all(contents.as_bytes()).await?;
+
+    Ok(())
+}
+
+/// Makes sure that a service loads its env file. Services created by servicer already do. A drop-in
+/// is written for other services. Returns true if a drop-in was written, in which case the service
+/// manager must be reloaded.
+///
+/// # Arguments
+///
+/// * `name` - The short service name
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn ensure_env_file_loaded(name: &str, full_service_name: &str) -> std::io::Result<bool> {
+    let env_file_value = get_environment_file_value(&get_env_file_path(name), true);
+    let drop_in_folder = get_drop_in_folder(full_service_name);
+    let drop_in_path = drop_in_folder.join(ENV_DROP_IN);
+
+    let body = fs::read_to_string(get_service_file_path(full_service_name)).await?;
+    let drop_in = fs::read_to_string(&drop_in_path).await.unwrap_or_default();
+
+    let is_loaded = get_unit_values(&body, "EnvironmentFile")
+        .iter()
+        .chain(get_unit_values(&drop_in, "EnvironmentFile").iter())
+        .any(|value| *value == env_file_value);
+
+    if is_loaded {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(&drop_in_folder).await?;
+    fs::write(
+        &drop_in_path,
+        format!("# Generated with Servicer\n[Service]\nEnvironmentFile={env_file_value}\n"),
+    )
+    .await?;
+
+    Ok(true)
+}
+
+/// Quotes a value for an env file if it has whitespace or characters special to systemd
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn quote_env_value(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || "-_./:,@+=%".contains(char));
+
+    if is_plain {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+
+    for char in value.chars() {
+        if matches!(char, '"' | '\\' | '`' | '$') {
+            quoted.push('\\');
+        }
+        quoted.push(char);
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Parses an env file the way systemd reads `EnvironmentFile=`. Lines starting with `#` or `;`
+/// are comments. Values may be single or double quoted, and quoted values may span lines.
+///
+/// # Arguments
+///
+/// * `contents`
+///
+fn parse_env_file(contents: &str) -> Vec<(String, String)> {
+    let mut vars = vec![];
+    let mut chars = contents.chars().peekable();
+
+    loop {
+        while chars.next_if(|char| char.is_whitespace()).is_some() {}
+
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+
+        if first == '#' || first == ';' {
+            while chars.next_if(|char| *char != '\n').is_some() {}
+            continue;
+        }
+
+        let mut key = String::new();
+        while let Some(char) = chars.next_if(|char| *char != '=' && *char != '\n') {
+            key.push(char);
+        }
+
+        // Lines without `=` are ignored
+        if chars.next() != Some('=') {
+            continue;
+        }
+
+        while chars
+            .next_if(|char| *char == ' ' || *char == '\t')
+            .is_some()
+        {}
+
+        let mut value = String::new();
+        // Length of the value without trailing unquoted whitespace
+        let mut length = 0;
+
+        while let Some(char) = chars.next() {
+            match char {
+                '\n' => break,
+                '\'' => {
+                    for char in chars.by_ref() {
+                        if char == '\'' {
+                            break;
+                        }
+                        value.push(char);
+                    }
+                    length = value.len();
+                }
+                '"' => {
+                    while let Some(char) = chars.next() {
+                        match char {
+                            '"' => break,
+                            '\\' => match chars.next() {
+                                Some('\n') => {}
+                                Some(char @ ('"' | '\\' | '`' | '$')) => value.push(char),
+                                Some(char) => {
+                                    value.push('\\');
+                                    value.push(char);
+                                }
+                                None => {}
+                            },
+                            char => value.push(char),
+                        }
+                    }
+                    length = value.len();
+                }
+                '\\' => {
+                    match chars.next() {
+                        Some('\n') | None => {}
+                        Some(char) => value.push(char),
+                    }
+                    length = value.len();
+                }
+                char => {
+                    value.push(char);
+
+                    if !char.is_whitespace() {
+                        length = value.len();
+                    }
+                }
+            }
+        }
+
+        value.truncate(length);
+
+        let key = key.trim();
+        let key = key.strip_prefix("export ").unwrap_or(key).trim();
+
+        if !key.is_empty() {
+            vars.push((key.to_string(), value));
+        }
+    }
+
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Owned pairs for comparing variables
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_plain_values() {
+        assert_eq!(
+            parse_env_file("PORT=8080\nHOST = localhost  \nEMPTY=\nURL=http://a.b/c?d=e\n"),
+            vars(&[
+                ("PORT", "8080"),
+                ("HOST", "localhost"),
+                ("EMPTY", ""),
+                ("URL", "http://a.b/c?d=e"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        assert_eq!(
+            parse_env_file(
+                "SINGLE='hello world'\n\
+                 DOUBLE=\"hello world\"\n\
+                 ESCAPED=\"say \\\"hi\\\" to \\$USER \\\\ \\n\"\n\
+                 LITERAL='no $expansion \\here'\n\
+                 MIXED=a'b c'\"d e\"\n"
+            ),
+            vars(&[
+                ("SINGLE", "hello world"),
+                ("DOUBLE", "hello world"),
+                ("ESCAPED", "say \"hi\" to $USER \\ \\n"),
+                ("LITERAL", "no $expansion \\here"),
+                ("MIXED", "ab cd e"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_values_spanning_lines() {
+        assert_eq!(
+            parse_env_file(
+                "KEY=\"-----BEGIN-----\nabc\n-----END-----\"\nNEXT=1\nJOINED=one\\\ntwo\n"
+            ),
+            vars(&[
+                ("KEY", "-----BEGIN-----\nabc\n-----END-----"),
+                ("NEXT", "1"),
+                ("JOINED", "onetwo"),
+            ])
+        );
+    }
+
+    #[test]
+    fn strips_export_prefixes() {
+        assert_eq!(
+            parse_env_file("export PORT=8080\nexport   NAME='app'\nexported=yes\n"),
+            vars(&[("PORT", "8080"), ("NAME", "app"), ("exported", "yes")])
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        assert_eq!(
+            parse_env_file(
+                "# comment\n\
+                 ; also a comment\n\
+                 \n   \n\
+                 \tINDENTED=1\n\
+                 NOT_A_COMMENT=a#b\n\
+                 no equals sign\n\
+                 =no key\n"
+            ),
+            vars(&[("INDENTED", "1"), ("NOT_A_COMMENT", "a#b")])
+        );
+        assert!(parse_env_file("").is_empty());
+        assert!(parse_env_file("\n\n# only comments\n").is_empty());
+    }
+
+    #[test]
+    fn quotes_special_values() {
+        assert_eq!(quote_env_value("8080"), "8080");
+        assert_eq!(quote_env_value("http://a.b/c"), "http://a.b/c");
+        assert_eq!(quote_env_value(""), "\"\"");
+        assert_eq!(quote_env_value("hello world"), "\"hello world\"");
+        assert_eq!(quote_env_value("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_env_value("$HOME"), "\"\\$HOME\"");
+    }
+
+    #[test]
+    fn quoted_values_parse_back() {
+        let values = [
+            "plain",
+            "",
+            "hello world",
+            "it's",
+            "say \"hi\"",
+            "$HOME and `pwd`",
+            "back\\slash",
+            "#not a comment",
+            "line one\nline two",
+            " padded ",
+        ];
+
+        for value in values {
+            let line = format!("KEY={}\n", quote_env_value(value));
+            assert_eq!(parse_env_file(&line), vars(&[("KEY", value)]), "{line}");
+        }
+    }
+
+    #[test]
+    fn merges_repeated_keys() {
+        // The user's PATH overrides the one found for the interpreter
+        assert_eq!(
+            merge_env_vars(vars(&[
+                (
+                    "PATH",
+                    "/home/alice/.nvm/versions/node/v20.10.0/bin:/usr/bin"
+                ),
+                ("NODE_ENV", "production"),
+                ("PATH", "/opt/bin:/usr/bin"),
+                ("NODE_ENV", "staging"),
+                ("PORT", "8080"),
+            ])),
+            vars(&[
+                ("PATH", "/opt/bin:/usr/bin"),
+                ("NODE_ENV", "staging"),
+                ("PORT", "8080"),
+            ])
+        );
+        assert!(merge_env_vars(vec![]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn writes_and_reads_env_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env/app.env");
+
+        assert!(read_env_file(&path).await.unwrap().is_empty());
+
+        let written = vars(&[("PORT", "8080"), ("GREETING", "hello world")]);
+        write_env_file(&path, &written).await.unwrap();
+
+        assert_eq!(read_env_file(&path).await.unwrap(), written);
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
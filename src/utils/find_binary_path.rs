@@ -1,37 +1,472 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
 use tokio::process::Command;
 
-/**
- * Runs `which` as SUDO_USER to find the path of the given binary.
- *
- * `ser create` must be called in sudo mode. The variable $PATH in sudo mode doesn't
- * hold most of the paths available to the regular user. Therefore we must call `which`
- * as SUDO_USER.
- *
- * # Arguments
- *
- * * `binary_name`- Find path for this interpreter
- * * `user` - Lookup as this user
- */
-pub async fn find_binary_path(
-    binary_name: &str,
-    user: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
-    // Runs sudo -u hp bash -i -c "which deno"
-    let output = Command::new("sudo")
-        .arg("-u")
-        .arg(user)
-        .arg("bash")
-        .arg("-i")
+use super::scope::{get_current_user, get_user_account, UserAccount};
+
+/// Folders searched after the user's PATH
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr/local/sbin",
+    "/usr/local/bin",
+    "/usr/sbin",
+    "/usr/bin",
+    "/sbin",
+    "/bin",
+];
+
+/// How long the login shell may take to print its environment. Guards against rc files waiting
+/// for input.
+const LOGIN_SHELL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error returned when a binary can't be resolved
+#[derive(Debug)]
+pub enum FindBinaryError {
+    /// The user doesn't exist in the passwd database
+    UnknownUser(String),
+
+    /// The binary isn't present in any searched folder
+    NotFound {
+        binary_name: String,
+        user: String,
+        searched: Vec<PathBuf>,
+    },
+}
+
+impl fmt::Display for FindBinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindBinaryError::UnknownUser(user) => write!(f, "User {user} not found"),
+            FindBinaryError::NotFound {
+                binary_name,
+                user,
+                searched,
+            } => {
+                write!(f, "Failed to find {binary_name} for user {user}. Searched:")?;
+
+                for folder in searched {
+                    write!(f, "\n  {}", folder.display())?;
+                }
+
+                write!(f, "\nPass the absolute path with --interpreter")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FindBinaryError {}
+
+/// Finds the absolute path of a binary as seen by a user.
+///
+/// `ser create` must be called in sudo mode. The variable $PATH in sudo mode doesn't hold most of
+/// the paths available to the regular user. Therefore the user's PATH is read from their login
+/// shell. Version managers are resolved to concrete binaries, since nvm is only loaded by
+/// interactive shells and pyenv, asdf and mise shims need the user's shell environment.
+///
+/// # Arguments
+///
+/// * `binary_name`- Find path for this interpreter. Paths are checked and returned as absolute
+///   paths, relative ones starting from the current folder.
+/// * `user` - Lookup as this user
+///
+pub async fn find_binary_path(binary_name: &str, user: &str) -> Result<PathBuf, FindBinaryError> {
+    if binary_name.contains('/') {
+        // Symlinks are kept, since a virtualenv's python only finds its packages when run through
+        // the link
+        let path = std::path::absolute(binary_name).unwrap_or_else(|_| PathBuf::from(binary_name));
+
+        return if is_executable(&path) {
+            Ok(path)
+        } else {
+            Err(FindBinaryError::NotFound {
+                binary_name: binary_name.to_string(),
+                user: user.to_string(),
+                searched: vec![],
+            })
+        };
+    }
+
+    let account =
+        get_user_account(user).ok_or_else(|| FindBinaryError::UnknownUser(user.to_string()))?;
+
+    let mut searched: Vec<PathBuf> = vec![];
+
+    // nvm prepends the default version to PATH in interactive shells
+    searched.extend(get_nvm_paths(&account.home));
+
+    for folder in get_login_paths(user, &account).await {
+        if folder.ends_with("shims") {
+            // Replace the shims with the versions they point to. The shims stay as a fallback.
+            searched.extend(get_shim_targets(&account.home, &folder));
+        }
+
+        searched.push(folder);
+    }
+
+    searched.extend(SYSTEM_PATHS.iter().map(PathBuf::from));
+
+    let mut seen = HashSet::new();
+    searched.retain(|folder| seen.insert(folder.clone()));
+
+    for folder in &searched {
+        let path = folder.join(binary_name);
+
+        if is_executable(&path) {
+            return Ok(path);
+        }
+    }
+
+    Err(FindBinaryError::NotFound {
+        binary_name: binary_name.to_string(),
+        user: user.to_string(),
+        searched,
+    })
+}
+
+/// `PATH` for a service running a binary outside the system folders, eg. from nvm. Holds the
+/// binary's folder followed by the system folders, so that tools like `npm` find `node`. `None` for
+/// system binaries.
+///
+/// # Arguments
+///
+/// * `binary_path` - Absolute path of the binary
+///
+pub fn get_path_env(binary_path: &Path) -> Option<String> {
+    let folder = binary_path.parent()?;
+
+    if SYSTEM_PATHS
+        .iter()
+        .any(|system_path| folder == Path::new(system_path))
+    {
+        return None;
+    }
+
+    Some(format!("{}:{}", folder.display(), SYSTEM_PATHS.join(":")))
+}
+
+/// Whether a path is a file with an executable bit set
+///
+/// # Arguments
+///
+/// * `path`
+///
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Reads PATH from the user's login shell. Returns an empty list if the shell fails.
+///
+/// The shell prints its environment with `env -0` so that output from noisy rc files can be told
+/// apart.
+///
+/// # Arguments
+///
+/// * `user`
+/// * `account` - The user's passwd entry
+///
+async fn get_login_paths(user: &str, account: &UserAccount) -> Vec<PathBuf> {
+    let mut command = if get_current_user().as_deref() == Some(user) {
+        Command::new(&account.shell)
+    } else {
+        let mut command = Command::new("sudo");
+        command.arg("-H").arg("-u").arg(user).arg(&account.shell);
+        command
+    };
+
+    command
+        .arg("-l")
         .arg("-c")
-        .arg(format!("which {binary_name}"))
-        .output()
-        .await?;
+        .arg("env -0")
+        .current_dir(&account.home)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    let Ok(Ok(output)) = tokio::time::timeout(LOGIN_SHELL_TIMEOUT, command.output()).await else {
+        return vec![];
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    stdout
+        .split('\0')
+        // rc file output ends up before the first variable
+        .filter_map(|variable| variable.rsplit('\n').next())
+        .filter_map(|variable| variable.strip_prefix("PATH="))
+        .next_back()
+        .map(|path| {
+            path.split(':')
+                .filter(|folder| folder.starts_with('/'))
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Binary folder of the default nvm version. nvm has no shims and is only loaded by interactive
+/// shells, so its binaries are missing from the login PATH.
+///
+/// # Arguments
+///
+/// * `home` - Home directory of the user
+///
+fn get_nvm_paths(home: &Path) -> Vec<PathBuf> {
+    [home.join(".nvm"), home.join(".config/nvm")]
+        .iter()
+        .filter_map(|nvm_dir| {
+            let alias = std::fs::read_to_string(nvm_dir.join("alias/default")).unwrap_or_default();
+            let alias = alias.trim();
+
+            // Aliases like `node`, `stable` or `lts/*` pick the newest install
+            let prefix = if alias.starts_with(|char: char| char.is_ascii_digit() || char == 'v') {
+                alias
+            } else {
+                ""
+            };
+
+            find_version(&nvm_dir.join("versions/node"), prefix)
+        })
+        .map(|version_dir| version_dir.join("bin"))
+        .collect()
+}
+
+/// Folders holding the binaries that a shims folder points to
+///
+/// # Arguments
+///
+/// * `home` - Home directory of the user
+/// * `shims` - The shims folder found in PATH
+///
+fn get_shim_targets(home: &Path, shims: &Path) -> Vec<PathBuf> {
+    let pyenv = home.join(".pyenv");
+    let asdf = home.join(".asdf");
+    let mise = home.join(".local/share/mise");
+
+    if shims == pyenv.join("shims") {
+        let version = std::fs::read_to_string(pyenv.join("version")).unwrap_or_default();
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        version
+            .split_whitespace()
+            .filter(|version| *version != "system")
+            .filter_map(|version| find_version(&pyenv.join("versions"), version))
+            .map(|version_dir| version_dir.join("bin"))
+            .collect()
+    } else if shims == asdf.join("shims") || shims == mise.join("shims") {
+        let installs = shims.parent().unwrap().join("installs");
 
-    if !output.status.success() {
-        panic!("Failed to find {binary_name} in PATH")
+        get_tool_versions(home)
+            .iter()
+            .filter_map(|(tool, version)| find_version(&installs.join(tool), version))
+            .map(|version_dir| version_dir.join("bin"))
+            .collect()
+    } else {
+        vec![]
     }
+}
+
+/// Global tool versions used by asdf and mise, read from `~/.tool-versions` and the `[tools]`
+/// table of `~/.config/mise/config.toml`
+///
+/// # Arguments
+///
+/// * `home` - Home directory of the user
+///
+fn get_tool_versions(home: &Path) -> Vec<(String, String)> {
+    let mut tool_versions = vec![];
+
+    let contents = std::fs::read_to_string(home.join(".tool-versions")).unwrap_or_default();
+
+    for line in contents.lines() {
+        let mut parts = line.split_whitespace();
+
+        if let (Some(tool), Some(version)) = (parts.next(), parts.next()) {
+            if !tool.starts_with('#') {
+                tool_versions.push((tool.to_string(), version.to_string()));
+            }
+        }
+    }
+
+    let contents =
+        std::fs::read_to_string(home.join(".config/mise/config.toml")).unwrap_or_default();
+
+    if let Ok(config) = contents.parse::<toml::Table>() {
+        if let Some(tools) = config.get("tools").and_then(|tools| tools.as_table()) {
+            for (tool, version) in tools {
+                if let Some(version) = version.as_str() {
+                    tool_versions.push((tool.to_string(), version.to_string()));
+                }
+            }
+        }
+    }
+
+    tool_versions
+}
+
+/// Newest installed version matching a prefix, eg. `20` matches `v20.1.0`. An empty prefix or
+/// `latest` matches every version.
+///
+/// # Arguments
+///
+/// * `versions_dir` - Folder having one folder per installed version
+/// * `prefix` - Version prefix, with or without a leading `v`
+///
+pub fn find_version(versions_dir: &Path, prefix: &str) -> Option<PathBuf> {
+    let prefix = prefix.trim_start_matches('v');
+    let prefix = if prefix == "latest" { "" } else { prefix };
+
+    std::fs::read_dir(versions_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|version| {
+            let version = version.trim_start_matches('v');
+
+            prefix.is_empty() || version == prefix || version.starts_with(&format!("{prefix}."))
+        })
+        .max_by_key(|version| parse_version(version))
+        .map(|version| versions_dir.join(version))
+}
+
+/// Numeric parts of a version, eg. `v20.1.0` gives `[20, 1, 0]`. Used to sort versions.
+///
+/// # Arguments
+///
+/// * `version`
+///
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(|char: char| !char.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    /// Folder holding one empty folder per version
+    fn versions_dir(versions: &[&str]) -> TempDir {
+        let dir = tempdir().unwrap();
+
+        for version in versions {
+            std::fs::create_dir(dir.path().join(version)).unwrap();
+        }
+
+        dir
+    }
+
+    /// Name of the version folder found for a prefix
+    fn find(dir: &TempDir, prefix: &str) -> Option<String> {
+        find_version(dir.path(), prefix)
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn finds_versions_by_prefix() {
+        let dir = versions_dir(&["v18.19.0", "v20.1.0", "v200.0.0", "v20.10.0", "v20.9.0"]);
+
+        assert_eq!(find(&dir, "20").as_deref(), Some("v20.10.0"));
+        assert_eq!(find(&dir, "v20").as_deref(), Some("v20.10.0"));
+        assert_eq!(find(&dir, "20.9").as_deref(), Some("v20.9.0"));
+        assert_eq!(find(&dir, "20.1.0").as_deref(), Some("v20.1.0"));
+        assert_eq!(find(&dir, "18").as_deref(), Some("v18.19.0"));
+        assert_eq!(find(&dir, "200").as_deref(), Some("v200.0.0"));
+        assert_eq!(find(&dir, "2"), None);
+        assert_eq!(find(&dir, "20.1.1"), None);
+    }
+
+    #[test]
+    fn finds_the_newest_version_for_latest() {
+        let dir = versions_dir(&["3.9.18", "3.12.1", "3.11.7"]);
+
+        assert_eq!(find(&dir, "latest").as_deref(), Some("3.12.1"));
+        assert_eq!(find(&dir, "").as_deref(), Some("3.12.1"));
+        assert_eq!(find(&dir, "3.11").as_deref(), Some("3.11.7"));
+    }
+
+    #[test]
+    fn finds_nothing_without_versions() {
+        assert_eq!(find(&versions_dir(&[]), "20"), None);
+        assert_eq!(find_version(Path::new("/nonexistent/versions"), "20"), None);
+    }
+
+    #[tokio::test]
+    async fn makes_relative_paths_absolute() {
+        let dir = tempfile::Builder::new()
+            .tempdir_in(std::env::current_dir().unwrap())
+            .unwrap();
+        let python = dir.path().join("venv/bin/python");
+        std::fs::create_dir_all(python.parent().unwrap()).unwrap();
+        std::fs::write(&python, "").unwrap();
+        std::fs::set_permissions(&python, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let relative = format!(
+            "./{}/venv/bin/python",
+            dir.path().file_name().unwrap().to_string_lossy()
+        );
+        let path = find_binary_path(&relative, "nobody").await.unwrap();
+
+        assert!(path.is_absolute());
+        assert_eq!(path, python);
 
-    Ok(stdout)
+        let missing = format!("{relative}-missing");
+        assert!(find_binary_path(&missing, "nobody").await.is_err());
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(parse_version("v20.1.0"), [20, 1, 0]);
+        assert_eq!(parse_version("3.12.1"), [3, 12, 1]);
+        assert_eq!(parse_version("1.75.0-beta.2"), [1, 75, 0, 2]);
+        assert!(parse_version("v20.10.0") > parse_version("v20.9.0"));
+        assert!(parse_version("v20.1.0") > parse_version("v20"));
+    }
+
+    #[test]
+    fn reads_tool_versions() {
+        let home = tempdir().unwrap();
+
+        std::fs::write(
+            home.path().join(".tool-versions"),
+            "# global versions\nnodejs 20.10.0\n\npython 3.12.1 3.11.7\nruby\n",
+        )
+        .unwrap();
+
+        std::fs::create_dir_all(home.path().join(".config/mise")).unwrap();
+        std::fs::write(
+            home.path().join(".config/mise/config.toml"),
+            "[tools]\nnode = \"20\"\ngo = \"latest\"\nrust = { version = \"1.75\" }\n",
+        )
+        .unwrap();
+
+        let tool_versions = get_tool_versions(home.path());
+        let tool_versions: Vec<(&str, &str)> = tool_versions
+            .iter()
+            .map(|(tool, version)| (tool.as_str(), version.as_str()))
+            .collect();
+
+        assert_eq!(
+            tool_versions,
+            [
+                ("nodejs", "20.10.0"),
+                ("python", "3.12.1"),
+                ("go", "latest"),
+                ("node", "20"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_no_tool_versions_without_files() {
+        assert!(get_tool_versions(tempdir().unwrap().path()).is_empty());
+    }
 }
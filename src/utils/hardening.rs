@@ -0,0 +1,291 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+use super::{config::get_config, scope::is_user_scope, unit_file::quote_path};
+
+/// Folders holding home directories. Programs and working directories in them must stay readable.
+const HOME_FOLDERS: &[&str] = &["/home", "/root", "/run/user"];
+
+/// Folders replaced by `PrivateTmp=`
+const TMP_FOLDERS: &[&str] = &["/tmp", "/var/tmp"];
+
+/// Sandboxing presets for generated services
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Hardening {
+    /// Read-only system folders and home directories, private /tmp and no privilege escalation
+    Basic,
+
+    /// Basic, plus a read-only file system outside the working directory, no capabilities, no
+    /// devices and only IP and Unix sockets
+    Strict,
+
+    /// Directives from `[hardening]` in the config
+    Custom,
+}
+
+/// Directives for the `[Service]` section of a hardened service. The working directory stays
+/// writable, home directories stay readable if the service runs from one, and /tmp is shared if
+/// the service runs from it.
+///
+/// # Arguments
+///
+/// * `hardening` - The preset
+/// * `working_directory` - Folder the service runs in
+/// * `program` - Absolute path of the program run by the service
+///
+pub fn get_hardening_directives(
+    hardening: Hardening,
+    working_directory: &str,
+    program: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let mut directives: Vec<(&str, String)> = vec![];
+
+    let is_in = |folders: &[&str]| {
+        [working_directory, program].iter().any(|path| {
+            folders
+                .iter()
+                .any(|folder| Path::new(path).starts_with(folder))
+        })
+    };
+    let uses_home = is_in(HOME_FOLDERS);
+
+    // A private /tmp would hide the working directory
+    let private_tmp = if is_in(TMP_FOLDERS) { "no" } else { "yes" };
+
+    match hardening {
+        Hardening::Basic => {
+            directives.extend([
+                ("NoNewPrivileges", "yes".to_string()),
+                ("PrivateTmp", private_tmp.to_string()),
+                ("ProtectSystem", "full".to_string()),
+                ("ProtectHome", "read-only".to_string()),
+                ("ProtectKernelTunables", "yes".to_string()),
+                ("ProtectKernelModules", "yes".to_string()),
+                ("ProtectControlGroups", "yes".to_string()),
+                ("RestrictSUIDSGID", "yes".to_string()),
+                ("LockPersonality", "yes".to_string()),
+            ]);
+        }
+        Hardening::Strict => {
+            // Hidden home directories would hide the program too
+            let protect_home = if uses_home { "read-only" } else { "yes" };
+
+            directives.extend([
+                ("NoNewPrivileges", "yes".to_string()),
+                ("PrivateTmp", private_tmp.to_string()),
+                ("PrivateDevices", "yes".to_string()),
+                ("ProtectSystem", "strict".to_string()),
+                ("ProtectHome", protect_home.to_string()),
+                ("ProtectKernelTunables", "yes".to_string()),
+                ("ProtectKernelModules", "yes".to_string()),
+                ("ProtectKernelLogs", "yes".to_string()),
+                ("ProtectControlGroups", "yes".to_string()),
+                ("ProtectClock", "yes".to_string()),
+                ("ProtectHostname", "yes".to_string()),
+                ("RestrictSUIDSGID", "yes".to_string()),
+                ("RestrictNamespaces", "yes".to_string()),
+                ("RestrictRealtime", "yes".to_string()),
+                ("LockPersonality", "yes".to_string()),
+                ("SystemCallArchitectures", "native".to_string()),
+                (
+                    "RestrictAddressFamilies",
+                    "AF_UNIX AF_INET AF_INET6".to_string(),
+                ),
+            ]);
+
+            // The user service manager can't drop capabilities, and user services have none anyway
+            if !is_user_scope() {
+                directives.extend([
+                    ("CapabilityBoundingSet", "".to_string()),
+                    ("AmbientCapabilities", "".to_string()),
+                ]);
+            }
+        }
+        Hardening::Custom => directives.extend(get_custom_directives(&get_config().hardening)?),
+    }
+
+    directives.push(("ReadWritePaths", quote_path(working_directory)));
+
+    Ok(directives
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect())
+}
+
+/// Checks the directives under `[hardening]` in the config, used by the custom preset
+///
+/// # Arguments
+///
+/// * `custom` - Directives by key, eg. `ProtectSystem = "strict"`
+///
+fn get_custom_directives(custom: &BTreeMap<String, String>) -> Result<Vec<(&str, String)>, String> {
+    if custom.is_empty() {
+        return Err("--hardening custom needs directives under [hardening] in the config, eg. ProtectSystem = 'strict'".to_string());
+    }
+
+    let mut directives = vec![];
+
+    for (key, value) in custom {
+        let is_valid_key = !key.is_empty() && key.chars().all(|char| char.is_ascii_alphanumeric());
+
+        if !is_valid_key || value.contains('\n') {
+            return Err(format!(
+                "Invalid [hardening] directive `{key}` in the config"
+            ));
+        }
+
+        directives.push((key.as_str(), value.clone()));
+    }
+
+    Ok(directives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Value of a directive, if set
+    fn get<'a>(directives: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        directives
+            .iter()
+            .find(|(current_key, _)| current_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Keys of the directives, in order
+    fn keys(directives: &[(String, String)]) -> Vec<&str> {
+        directives.iter().map(|(key, _)| key.as_str()).collect()
+    }
+
+    #[test]
+    fn writes_basic_directives() {
+        let directives =
+            get_hardening_directives(Hardening::Basic, "/srv/app", "/usr/bin/node").unwrap();
+
+        assert_eq!(
+            keys(&directives),
+            [
+                "NoNewPrivileges",
+                "PrivateTmp",
+                "ProtectSystem",
+                "ProtectHome",
+                "ProtectKernelTunables",
+                "ProtectKernelModules",
+                "ProtectControlGroups",
+                "RestrictSUIDSGID",
+                "LockPersonality",
+                "ReadWritePaths",
+            ]
+        );
+        assert_eq!(get(&directives, "PrivateTmp"), Some("yes"));
+        assert_eq!(get(&directives, "ProtectSystem"), Some("full"));
+        assert_eq!(get(&directives, "ProtectHome"), Some("read-only"));
+        assert_eq!(get(&directives, "ReadWritePaths"), Some("/srv/app"));
+    }
+
+    #[test]
+    fn writes_strict_directives() {
+        let directives =
+            get_hardening_directives(Hardening::Strict, "/srv/app", "/usr/bin/node").unwrap();
+
+        assert_eq!(get(&directives, "ProtectSystem"), Some("strict"));
+        assert_eq!(get(&directives, "ProtectHome"), Some("yes"));
+        assert_eq!(get(&directives, "PrivateTmp"), Some("yes"));
+        assert_eq!(get(&directives, "PrivateDevices"), Some("yes"));
+        assert_eq!(get(&directives, "SystemCallArchitectures"), Some("native"));
+        assert_eq!(
+            get(&directives, "RestrictAddressFamilies"),
+            Some("AF_UNIX AF_INET AF_INET6")
+        );
+        assert_eq!(get(&directives, "CapabilityBoundingSet"), Some(""));
+        assert_eq!(get(&directives, "AmbientCapabilities"), Some(""));
+        assert_eq!(get(&directives, "ReadWritePaths"), Some("/srv/app"));
+        assert_eq!(keys(&directives).last(), Some(&"ReadWritePaths"));
+    }
+
+    #[test]
+    fn keeps_home_working_directories_writable() {
+        let directives = get_hardening_directives(
+            Hardening::Strict,
+            "/home/alice/app",
+            "/home/alice/.nvm/versions/node/v20.10.0/bin/node",
+        )
+        .unwrap();
+
+        assert_eq!(get(&directives, "ProtectSystem"), Some("strict"));
+        assert_eq!(get(&directives, "ProtectHome"), Some("read-only"));
+        assert_eq!(get(&directives, "ReadWritePaths"), Some("/home/alice/app"));
+
+        // A program in a home directory must stay readable too
+        let directives =
+            get_hardening_directives(Hardening::Strict, "/srv/app", "/root/.local/bin/app")
+                .unwrap();
+        assert_eq!(get(&directives, "ProtectHome"), Some("read-only"));
+    }
+
+    #[test]
+    fn keeps_tmp_working_directories_writable() {
+        for hardening in [Hardening::Basic, Hardening::Strict] {
+            let directives =
+                get_hardening_directives(hardening, "/tmp/build", "/usr/bin/python3").unwrap();
+
+            assert_eq!(get(&directives, "PrivateTmp"), Some("no"), "{hardening:?}");
+            assert_eq!(
+                get(&directives, "ReadWritePaths"),
+                Some("/tmp/build"),
+                "{hardening:?}"
+            );
+        }
+
+        let directives =
+            get_hardening_directives(Hardening::Strict, "/var/tmp/job", "/usr/bin/python3")
+                .unwrap();
+        assert_eq!(get(&directives, "PrivateTmp"), Some("no"));
+    }
+
+    #[test]
+    fn quotes_working_directories_with_spaces() {
+        let directives =
+            get_hardening_directives(Hardening::Basic, "/srv/my app", "/usr/bin/node").unwrap();
+
+        assert_eq!(
+            get(&directives, "ReadWritePaths"),
+            Some(quote_path("/srv/my app").as_str())
+        );
+        assert_ne!(get(&directives, "ReadWritePaths"), Some("/srv/my app"));
+    }
+
+    #[test]
+    fn checks_custom_directives() {
+        let custom = BTreeMap::from([
+            ("ProtectSystem".to_string(), "strict".to_string()),
+            ("NoNewPrivileges".to_string(), "yes".to_string()),
+        ]);
+
+        assert_eq!(
+            get_custom_directives(&custom),
+            Ok(vec![
+                ("NoNewPrivileges", "yes".to_string()),
+                ("ProtectSystem", "strict".to_string()),
+            ])
+        );
+
+        assert!(get_custom_directives(&BTreeMap::new()).is_err());
+
+        for (key, value) in [
+            ("Protect System", "strict"),
+            ("", "yes"),
+            ("ExecStartPre", "/bin/true\nExecStart=/bin/sh"),
+        ] {
+            let custom = BTreeMap::from([(key.to_string(), value.to_string())]);
+            assert!(get_custom_directives(&custom).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn needs_custom_directives_in_the_config() {
+        assert!(get_hardening_directives(Hardening::Custom, "/srv/app", "/usr/bin/node").is_err());
+    }
+}
@@ -0,0 +1,624 @@
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncReadExt;
+
+use super::{
+    config::get_config,
+    find_binary_path::{find_version, is_executable},
+};
+
+/// Built-in interpreters by file extension. Entries under `[interpreters]` in the config file take
+/// priority.
+const DEFAULT_INTERPRETERS: &[(&str, &str)] = &[
+    ("js", "node"),
+    ("mjs", "node"),
+    ("cjs", "node"),
+    ("ts", "tsx"),
+    ("mts", "tsx"),
+    ("py", "python3"),
+    ("rb", "ruby"),
+    ("sh", "bash"),
+    ("php", "php"),
+    ("pl", "perl"),
+    ("jar", "java -jar"),
+];
+
+/// Number of bytes read from the start of a file to find a shebang line
+const SHEBANG_MAX_LENGTH: usize = 256;
+
+/// Folders holding a project-local Python virtualenv
+const VIRTUALENV_FOLDERS: &[&str] = &[".venv", "venv"];
+
+/// Files marking the root of a project. Virtualenvs and version files aren't searched above it.
+const PROJECT_MARKERS: &[&str] = &[".git", "pyproject.toml", "package.json"];
+
+/// Command used to run a file, eg. `java -jar`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interpreter {
+    /// The executable's name or full path, eg. `java`
+    pub program: String,
+
+    /// Args placed before the file, eg. `-jar`
+    pub args: Vec<String>,
+
+    /// Why a project-local interpreter was picked, eg. `.venv`. Recorded in the unit file.
+    pub origin: Option<String>,
+}
+
+impl Interpreter {
+    /// Parses a whitespace separated command, eg. `deno run --allow-net`
+    ///
+    /// # Arguments
+    ///
+    /// * `command`
+    ///
+    pub fn parse(command: &str) -> Result<Self, String> {
+        let mut parts = command.split_whitespace().map(|part| part.to_string());
+
+        match parts.next() {
+            Some(program) => Ok(Interpreter {
+                program,
+                args: parts.collect(),
+                origin: None,
+            }),
+            None => Err("Interpreter can't be empty".to_string()),
+        }
+    }
+}
+
+/// Finds the interpreter for a file. Looks up the file extension in the config file and the
+/// built-in interpreters, then falls back to the file's `#!` shebang line. Returns `None` if the
+/// file is executable by itself.
+///
+/// # Arguments
+///
+/// * `path` - The file to run
+///
+pub async fn detect_interpreter(
+    path: &Path,
+) -> Result<Option<Interpreter>, Box<dyn std::error::Error>> {
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        if let Some(command) = get_registered_interpreter(extension) {
+            return Ok(Some(Interpreter::parse(&command)?));
+        }
+    }
+
+    if let Some(interpreter) = read_shebang(path).await? {
+        return Ok(Some(interpreter));
+    }
+
+    let mode = tokio::fs::metadata(path).await?.permissions().mode();
+    if mode & 0o111 != 0 {
+        return Ok(None);
+    }
+
+    Err(format!(
+        "No interpreter found for {}. Pass one with --interpreter, add it under [interpreters] in the config file or make the file executable.",
+        path.display()
+    )
+    .into())
+}
+
+/// Command registered for a file extension in the config file or the built-in interpreters
+///
+/// # Arguments
+///
+/// * `extension` - The file extension without a dot, eg. `ts`
+///
+fn get_registered_interpreter(extension: &str) -> Option<String> {
+    get_config()
+        .interpreters
+        .get(extension)
+        .cloned()
+        .or_else(|| {
+            DEFAULT_INTERPRETERS
+                .iter()
+                .find(|(default_extension, _)| *default_extension == extension)
+                .map(|(_, command)| command.to_string())
+        })
+}
+
+/// Reads the interpreter from the file's shebang line. `#!/usr/bin/env node` gives `node` so
+/// that the user's version is found in PATH.
+///
+/// # Arguments
+///
+/// * `path` - The file to run
+///
+async fn read_shebang(path: &Path) -> std::io::Result<Option<Interpreter>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0; SHEBANG_MAX_LENGTH];
+    let length = file.read(&mut buffer).await?;
+    buffer.truncate(length);
+
+    let Some(line) = buffer.strip_prefix(b"#!") else {
+        return Ok(None);
+    };
+
+    let line = String::from_utf8_lossy(line);
+    let line = line.lines().next().unwrap_or_default();
+    let mut parts = line.split_whitespace();
+
+    let Some(mut program) = parts.next() else {
+        return Ok(None);
+    };
+
+    // `#!/usr/bin/env -S deno run` gives `deno run`
+    if Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        == Some("env")
+    {
+        match parts.find(|part| !part.starts_with('-')) {
+            Some(name) => program = name,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(Interpreter {
+        program: program.to_string(),
+        args: parts.map(|part| part.to_string()).collect(),
+        origin: None,
+    }))
+}
+
+/// Finds a project-local version of a Python or Node interpreter. Looks in the file's folder and
+/// its parents, up to the project root, for
+///
+/// - Python: a `.venv` or `venv` virtualenv (also created by uv), a Poetry virtualenv, then a
+///   `.python-version` file
+/// - Node: a `.nvmrc` or `.node-version` file
+///
+/// Versions are looked up in nvm, pyenv, asdf and mise installs. Aliases like `node` or `lts/iron`
+/// pick the newest installed Node, which may not be on the LTS line the alias names. Returns `None`
+/// for other interpreters or if nothing is found.
+///
+/// # Arguments
+///
+/// * `interpreter` - The detected interpreter, eg. `python3`
+/// * `working_directory` - Folder holding the file to run
+/// * `home` - Home directory of the user running the service
+///
+pub fn find_project_interpreter(
+    interpreter: &Interpreter,
+    working_directory: &Path,
+    home: &Path,
+) -> Option<Interpreter> {
+    let program = interpreter.program.as_str();
+
+    for folder in working_directory.ancestors() {
+        let found = match program {
+            "python" | "python3" => find_virtualenv(folder, program, home)
+                .or_else(|| find_python_version(folder, program, home)),
+            "node" => find_node_version(folder, home),
+            _ => return None,
+        };
+
+        if let Some((path, origin)) = found {
+            return Some(Interpreter {
+                program: path.display().to_string(),
+                args: interpreter.args.clone(),
+                origin: Some(origin),
+            });
+        }
+
+        if folder == home
+            || PROJECT_MARKERS
+                .iter()
+                .any(|marker| folder.join(marker).exists())
+        {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Python binary of a virtualenv in a project folder
+///
+/// # Arguments
+///
+/// * `folder` - The project folder
+/// * `program` - `python` or `python3`
+/// * `home` - Home directory of the user, holding Poetry's virtualenvs
+///
+fn find_virtualenv(folder: &Path, program: &str, home: &Path) -> Option<(PathBuf, String)> {
+    for virtualenv in VIRTUALENV_FOLDERS {
+        if let Some(path) = find_python_binary(&folder.join(virtualenv), program) {
+            return Some((path, virtualenv.to_string()));
+        }
+    }
+
+    // Poetry keeps virtualenvs outside the project unless `virtualenvs.in-project` is set
+    let pyproject = std::fs::read_to_string(folder.join("pyproject.toml")).ok()?;
+    let pyproject = pyproject.parse::<toml::Table>().ok()?;
+    let poetry = pyproject.get("tool").and_then(|tool| tool.get("poetry"));
+
+    if poetry.is_none() && !folder.join("poetry.lock").exists() {
+        return None;
+    }
+
+    let name = poetry
+        .and_then(|poetry| poetry.get("name"))
+        .or_else(|| pyproject.get("project")?.get("name"))?
+        .as_str()?;
+
+    // Poetry names virtualenvs `{sanitized name}-{hash}-py{version}`
+    let sanitized_name: String = name
+        .to_lowercase()
+        .chars()
+        .map(|char| match char {
+            ' ' | '$' | '`' | '!' | '*' | '@' | '"' | '\\' | '\r' | '\n' | '\t' => '_',
+            char => char,
+        })
+        .take(42)
+        .collect();
+    let prefix = format!("{sanitized_name}-");
+
+    let virtualenvs = home.join(".cache/pypoetry/virtualenvs");
+    let mut candidates: Vec<PathBuf> = std::fs::read_dir(&virtualenvs)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|entry| entry.path())
+        .collect();
+    candidates.sort();
+
+    candidates.iter().find_map(|virtualenv| {
+        find_python_binary(virtualenv, program).map(|path| (path, "poetry virtualenv".to_string()))
+    })
+}
+
+/// Python version pinned with `.python-version`, installed with pyenv, asdf or mise
+///
+/// # Arguments
+///
+/// * `folder` - The project folder
+/// * `program` - `python` or `python3`
+/// * `home` - Home directory of the user
+///
+fn find_python_version(folder: &Path, program: &str, home: &Path) -> Option<(PathBuf, String)> {
+    let version = read_version_file(&folder.join(".python-version"))?;
+
+    let versions_folders = [
+        home.join(".pyenv/versions"),
+        home.join(".asdf/installs/python"),
+        home.join(".local/share/mise/installs/python"),
+    ];
+
+    versions_folders.iter().find_map(|versions_folder| {
+        let installation = find_version(versions_folder, &version)?;
+
+        find_python_binary(&installation, program)
+            .map(|path| (path, format!(".python-version: {version}")))
+    })
+}
+
+/// Node version pinned with `.nvmrc` or `.node-version`, installed with nvm, asdf or mise
+///
+/// # Arguments
+///
+/// * `folder` - The project folder
+/// * `home` - Home directory of the user
+///
+fn find_node_version(folder: &Path, home: &Path) -> Option<(PathBuf, String)> {
+    let (file_name, version) = [".nvmrc", ".node-version"]
+        .iter()
+        .find_map(|file_name| Some((file_name, read_version_file(&folder.join(file_name))?)))?;
+
+    // Aliases like `node`, `stable` or `lts/iron` pick the newest install, whatever its LTS line
+    let prefix = if version.starts_with(|char: char| char.is_ascii_digit() || char == 'v') {
+        version.as_str()
+    } else {
+        ""
+    };
+
+    let versions_folders = [
+        home.join(".nvm/versions/node"),
+        home.join(".config/nvm/versions/node"),
+        home.join(".asdf/installs/nodejs"),
+        home.join(".local/share/mise/installs/node"),
+    ];
+
+    versions_folders.iter().find_map(|versions_folder| {
+        let path = find_version(versions_folder, prefix)?.join("bin/node");
+
+        if is_executable(&path) {
+            Some((path, format!("{file_name}: {version}")))
+        } else {
+            None
+        }
+    })
+}
+
+/// Python binary inside a virtualenv or installation folder
+///
+/// # Arguments
+///
+/// * `folder` - The virtualenv or installation
+/// * `program` - `python` or `python3`
+///
+fn find_python_binary(folder: &Path, program: &str) -> Option<PathBuf> {
+    [program, "python3", "python"]
+        .iter()
+        .map(|name| folder.join("bin").join(name))
+        .find(|path| is_executable(path))
+}
+
+/// First line of a version file like `.nvmrc`, eg. `20.1.0`
+///
+/// # Arguments
+///
+/// * `path`
+///
+fn read_version_file(path: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let version = contents.lines().next()?.trim();
+
+    if version.is_empty() {
+        None
+    } else {
+        Some(version.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Writes a file with a mode, creating its folders
+    fn write_file(path: &Path, contents: &str, mode: u32) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    /// Interpreter detected for a file
+    async fn detect(file_name: &str, contents: &str, mode: u32) -> Option<Interpreter> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(file_name);
+        write_file(&path, contents, mode);
+
+        detect_interpreter(&path).await.unwrap()
+    }
+
+    /// An interpreter that isn't project-local
+    fn interpreter(program: &str, args: &[&str]) -> Interpreter {
+        Interpreter {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            origin: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_interpreters_by_extension() {
+        assert_eq!(
+            detect("index.js", "console.log(1)", 0o644).await,
+            Some(interpreter("node", &[]))
+        );
+        assert_eq!(
+            detect("app.jar", "", 0o644).await,
+            Some(interpreter("java", &["-jar"]))
+        );
+
+        // The extension wins over the shebang
+        assert_eq!(
+            detect("main.py", "#!/usr/bin/env ruby\n", 0o755).await,
+            Some(interpreter("python3", &[]))
+        );
+    }
+
+    #[tokio::test]
+    async fn detects_env_shebangs_without_an_extension() {
+        assert_eq!(
+            detect("server", "#!/usr/bin/env python3\nprint(1)\n", 0o755).await,
+            Some(interpreter("python3", &[]))
+        );
+        assert_eq!(
+            detect("server", "#!/usr/bin/env -S deno run --allow-net\n", 0o644).await,
+            Some(interpreter("deno", &["run", "--allow-net"]))
+        );
+    }
+
+    #[tokio::test]
+    async fn detects_shebangs_for_unknown_extensions() {
+        assert_eq!(
+            detect("job.task", "#!/usr/bin/perl -w\nprint 1;\n", 0o644).await,
+            Some(interpreter("/usr/bin/perl", &["-w"]))
+        );
+        assert_eq!(
+            detect("job.task", "#! /bin/bash\necho 1\n", 0o644).await,
+            Some(interpreter("/bin/bash", &[]))
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_executables_directly() {
+        assert_eq!(detect("server", "\x7fELF", 0o755).await, None);
+        assert_eq!(detect("server.bin", "", 0o700).await, None);
+    }
+
+    #[tokio::test]
+    async fn fails_without_an_interpreter() {
+        let dir = tempdir().unwrap();
+
+        for (file_name, contents) in [("notes", "hello"), ("data.xyz", ""), ("bare", "#!")] {
+            let path = dir.path().join(file_name);
+            write_file(&path, contents, 0o644);
+
+            assert!(detect_interpreter(&path).await.is_err(), "{file_name}");
+        }
+
+        assert!(detect_interpreter(&dir.path().join("missing"))
+            .await
+            .is_err());
+    }
+
+    /// A home folder holding a project at `app`, marked by `.git`
+    fn project() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempdir().unwrap();
+        let home = dir.path().join("home");
+        let project = home.join("app");
+        std::fs::create_dir_all(project.join(".git")).unwrap();
+
+        (dir, home, project)
+    }
+
+    /// Program and origin of the project interpreter found for `program`
+    fn find(program: &str, working_directory: &Path, home: &Path) -> Option<(String, String)> {
+        find_project_interpreter(&interpreter(program, &[]), working_directory, home)
+            .map(|found| (found.program, found.origin.unwrap()))
+    }
+
+    /// Expected program and origin
+    fn found(path: &Path, origin: &str) -> Option<(String, String)> {
+        Some((path.display().to_string(), origin.to_string()))
+    }
+
+    #[test]
+    fn finds_virtualenvs() {
+        let (_dir, home, project) = project();
+
+        let venv = project.join("venv/bin/python3");
+        write_file(&venv, "", 0o755);
+        assert_eq!(find("python3", &project, &home), found(&venv, "venv"));
+
+        // `.venv` is preferred, also from subfolders of the project
+        let dot_venv = project.join(".venv/bin/python3");
+        write_file(&dot_venv, "", 0o755);
+        assert_eq!(find("python3", &project, &home), found(&dot_venv, ".venv"));
+        assert_eq!(
+            find("python3", &project.join("src/jobs"), &home),
+            found(&dot_venv, ".venv")
+        );
+
+        // `python` falls back to `python3`
+        assert_eq!(find("python", &project, &home), found(&dot_venv, ".venv"));
+    }
+
+    #[test]
+    fn skips_non_executable_virtualenvs() {
+        let (_dir, home, project) = project();
+        write_file(&project.join(".venv/bin/python3"), "", 0o644);
+
+        assert_eq!(find("python3", &project, &home), None);
+    }
+
+    #[test]
+    fn stops_at_the_project_root() {
+        let (_dir, home, project) = project();
+        write_file(&home.join(".venv/bin/python3"), "", 0o755);
+
+        assert_eq!(find("python3", &project, &home), None);
+    }
+
+    #[test]
+    fn finds_poetry_virtualenvs() {
+        let (_dir, home, project) = project();
+        std::fs::write(
+            project.join("pyproject.toml"),
+            "[tool.poetry]\nname = \"My App\"\n",
+        )
+        .unwrap();
+
+        let virtualenvs = home.join(".cache/pypoetry/virtualenvs");
+        write_file(&virtualenvs.join("other-x1-py3.12/bin/python3"), "", 0o755);
+        let python = virtualenvs.join("my_app-Ab12Cd34-py3.12/bin/python3");
+        write_file(&python, "", 0o755);
+
+        assert_eq!(
+            find("python3", &project, &home),
+            found(&python, "poetry virtualenv")
+        );
+    }
+
+    #[test]
+    fn finds_python_versions() {
+        let (_dir, home, project) = project();
+        std::fs::write(project.join(".python-version"), "3.11\n").unwrap();
+
+        let versions = home.join(".pyenv/versions");
+        write_file(&versions.join("3.12.1/bin/python3"), "", 0o755);
+        write_file(&versions.join("3.11.2/bin/python3"), "", 0o755);
+        let python = versions.join("3.11.7/bin/python3");
+        write_file(&python, "", 0o755);
+
+        assert_eq!(
+            find("python3", &project, &home),
+            found(&python, ".python-version: 3.11")
+        );
+
+        // A virtualenv wins over the pinned version
+        let venv = project.join(".venv/bin/python3");
+        write_file(&venv, "", 0o755);
+        assert_eq!(find("python3", &project, &home), found(&venv, ".venv"));
+    }
+
+    #[test]
+    fn finds_nvmrc_versions() {
+        let (_dir, home, project) = project();
+        std::fs::write(project.join(".nvmrc"), "20\n").unwrap();
+
+        let versions = home.join(".nvm/versions/node");
+        write_file(&versions.join("v18.19.0/bin/node"), "", 0o755);
+        write_file(&versions.join("v200.0.0/bin/node"), "", 0o755);
+        let node = versions.join("v20.10.0/bin/node");
+        write_file(&node, "", 0o755);
+        write_file(&versions.join("v20.9.0/bin/node"), "", 0o755);
+
+        assert_eq!(find("node", &project, &home), found(&node, ".nvmrc: 20"));
+    }
+
+    #[test]
+    fn finds_node_version_files() {
+        let (_dir, home, project) = project();
+        std::fs::write(project.join(".node-version"), "v18\n").unwrap();
+
+        let node = home.join(".asdf/installs/nodejs/18.19.0/bin/node");
+        write_file(&node, "", 0o755);
+
+        assert_eq!(
+            find("node", &project.join("src"), &home),
+            found(&node, ".node-version: v18")
+        );
+    }
+
+    #[test]
+    fn picks_the_newest_node_for_aliases() {
+        let (_dir, home, project) = project();
+        std::fs::write(project.join(".nvmrc"), "lts/iron\n").unwrap();
+
+        let versions = home.join(".nvm/versions/node");
+        write_file(&versions.join("v20.10.0/bin/node"), "", 0o755);
+        let node = versions.join("v22.1.0/bin/node");
+        write_file(&node, "", 0o755);
+
+        assert_eq!(
+            find("node", &project, &home),
+            found(&node, ".nvmrc: lts/iron")
+        );
+    }
+
+    #[test]
+    fn finds_nothing_for_other_interpreters() {
+        let (_dir, home, project) = project();
+        write_file(&project.join(".venv/bin/python3"), "", 0o755);
+        std::fs::write(project.join(".nvmrc"), "20\n").unwrap();
+
+        assert_eq!(find("ruby", &project, &home), None);
+        assert_eq!(find("node", &project, &home), None);
+    }
+
+    #[test]
+    fn parses_interpreter_commands() {
+        assert_eq!(
+            Interpreter::parse("deno run --allow-net"),
+            Ok(interpreter("deno", &["run", "--allow-net"]))
+        );
+        assert!(Interpreter::parse("  ").is_err());
+    }
+}
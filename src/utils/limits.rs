@@ -0,0 +1,558 @@
+use serde::Deserialize;
+use tokio::fs;
+use zbus::zvariant::Value;
+
+use super::{
+    service_names::{get_drop_in_folder, get_drop_in_paths, get_service_file_path},
+    unit_file::get_unit_values,
+};
+
+/// Drop-in holding the limits set with `ser limit`
+const LIMITS_DROP_IN: &str = "limits.conf";
+
+/// Unit file keys of the limits managed by servicer
+const LIMIT_KEYS: &[&str] = &[
+    "MemoryMax",
+    "CPUQuota",
+    "TasksMax",
+    "Nice",
+    "IOWeight",
+    "CPUAffinity",
+];
+
+/// Value removing a memory, CPU or task limit
+const INFINITY: &str = "infinity";
+
+/// Resource limits of a service. Set at create time, in a manifest under `limits`, or changed on a
+/// running service with `ser limit`.
+#[derive(clap::Args, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// Hard memory limit, eg. `512M` or `2G`. The service is OOM-killed above it. `infinity` removes the limit
+    #[arg(long, value_parser = validate_bytes)]
+    pub memory_max: Option<String>,
+
+    /// CPU time relative to one CPU, eg. `50%`, or `200%` for two CPUs. `infinity` removes the limit
+    #[arg(long, value_parser = validate_cpu_quota)]
+    pub cpu_quota: Option<String>,
+
+    /// Maximum number of processes and threads. `infinity` removes the limit
+    #[arg(long, value_parser = validate_tasks_max)]
+    pub tasks_max: Option<String>,
+
+    /// Scheduling priority from -20 (highest) to 19 (lowest). Applies on the next start
+    #[arg(long, allow_negative_numbers = true, value_parser = clap::value_parser!(i32).range(-20..=19))]
+    pub nice: Option<i32>,
+
+    /// IO weight from 1 to 10000. Services get 100 by default
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=10000))]
+    pub io_weight: Option<u64>,
+
+    /// CPUs the service may run on, eg. `0-3` or `0,2`. Applies on the next start
+    #[arg(long, value_parser = validate_cpu_affinity)]
+    pub cpu_affinity: Option<String>,
+}
+
+impl ResourceLimits {
+    /// Whether no limit is set
+    pub fn is_empty(&self) -> bool {
+        self.get_directives().is_empty()
+    }
+
+    /// Checks the values. Values passed on the command line are already checked by clap, values
+    /// from a manifest are not.
+    pub fn validate(&self) -> Result<(), String> {
+        self.get_properties()?;
+
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(format!("Invalid nice {nice}. Use -20 to 19"));
+            }
+        }
+
+        if let Some(cpu_affinity) = &self.cpu_affinity {
+            parse_cpu_set(cpu_affinity)?;
+        }
+
+        Ok(())
+    }
+
+    /// Key and value pairs for the `[Service]` section, eg. `("MemoryMax", "512M")`
+    pub fn get_directives(&self) -> Vec<(String, String)> {
+        let mut values = vec![];
+
+        if let Some(memory_max) = &self.memory_max {
+            values.push(("MemoryMax", normalize_bytes(memory_max)));
+        }
+
+        if let Some(cpu_quota) = &self.cpu_quota {
+            // An empty value removes the quota
+            let cpu_quota = if cpu_quota == INFINITY {
+                "".to_string()
+            } else {
+                cpu_quota.clone()
+            };
+            values.push(("CPUQuota", cpu_quota));
+        }
+
+        if let Some(tasks_max) = &self.tasks_max {
+            values.push(("TasksMax", tasks_max.clone()));
+        }
+
+        if let Some(nice) = self.nice {
+            values.push(("Nice", nice.to_string()));
+        }
+
+        if let Some(io_weight) = self.io_weight {
+            values.push(("IOWeight", io_weight.to_string()));
+        }
+
+        if let Some(cpu_affinity) = &self.cpu_affinity {
+            values.push(("CPUAffinity", cpu_affinity.replace(',', " ")));
+        }
+
+        values
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+
+    /// Properties for `SetUnitProperties` on the Manager. Only cgroup limits can change on a running
+    /// service, so nice and CPU affinity are left to the drop-in.
+    pub fn get_properties(&self) -> Result<Vec<(String, Value<'static>)>, String> {
+        let mut properties: Vec<(&str, Value<'static>)> = vec![];
+
+        if let Some(memory_max) = &self.memory_max {
+            properties.push(("MemoryMax", parse_bytes(memory_max)?.into()));
+        }
+
+        if let Some(cpu_quota) = &self.cpu_quota {
+            properties.push(("CPUQuotaPerSecUSec", parse_cpu_quota(cpu_quota)?.into()));
+        }
+
+        if let Some(tasks_max) = &self.tasks_max {
+            properties.push(("TasksMax", parse_tasks_max(tasks_max)?.into()));
+        }
+
+        if let Some(io_weight) = self.io_weight {
+            if !(1..=10000).contains(&io_weight) {
+                return Err(format!("Invalid IO weight {io_weight}. Use 1 to 10000"));
+            }
+            properties.push(("IOWeight", io_weight.into()));
+        }
+
+        Ok(properties
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect())
+    }
+}
+
+/// Limits of a service from its unit file and drop-ins. Later files override earlier ones.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn get_service_limits(full_service_name: &str) -> std::io::Result<Vec<(String, String)>> {
+    let mut paths = vec![get_service_file_path(full_service_name)];
+    paths.extend(get_drop_in_paths(full_service_name).await?);
+
+    let mut limits: Vec<(String, String)> = vec![];
+
+    for path in paths {
+        let body = fs::read_to_string(&path).await?;
+
+        for key in LIMIT_KEYS {
+            if let Some(value) = get_unit_values(&body, key).pop() {
+                set_value(&mut limits, key, value);
+            }
+        }
+    }
+
+    // Empty values reset a limit
+    limits.retain(|(_, value)| !value.is_empty());
+
+    Ok(limits)
+}
+
+/// Adds limits to the limits drop-in of a service, replacing earlier values of the same limits
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `limits`
+///
+pub async fn write_limits_drop_in(
+    full_service_name: &str,
+    limits: &ResourceLimits,
+) -> std::io::Result<()> {
+    let drop_in_folder = get_drop_in_folder(full_service_name);
+    let path = drop_in_folder.join(LIMITS_DROP_IN);
+
+    let body = fs::read_to_string(&path).await.unwrap_or_default();
+    let mut values: Vec<(String, String)> = vec![];
+
+    for key in LIMIT_KEYS {
+        if let Some(value) = get_unit_values(&body, key).pop() {
+            set_value(&mut values, key, value);
+        }
+    }
+
+    for (key, value) in limits.get_directives() {
+        set_value(&mut values, &key, value);
+    }
+
+    let mut body = String::from("# Generated with Servicer\n[Service]\n");
+    for (key, value) in values {
+        // CPU lists are merged with earlier ones unless reset first
+        if key == "CPUAffinity" {
+            body.push_str("CPUAffinity=\n");
+        }
+        body.push_str(&format!("{key}={value}\n"));
+    }
+
+    fs::create_dir_all(&drop_in_folder).await?;
+    fs::write(path, body).await
+}
+
+/// Sets the value of a key in a list of pairs, keeping the order of existing keys
+///
+/// # Arguments
+///
+/// * `values`
+/// * `key`
+/// * `value`
+///
+fn set_value(values: &mut Vec<(String, String)>, key: &str, value: String) {
+    match values
+        .iter_mut()
+        .find(|(current_key, _)| current_key == key)
+    {
+        Some(current) => current.1 = value,
+        None => values.push((key.to_string(), value)),
+    }
+}
+
+/// Checks a size like `512M` for clap
+fn validate_bytes(value: &str) -> Result<String, String> {
+    parse_bytes(value).map(|_| value.to_string())
+}
+
+/// Checks a CPU quota like `50%` for clap
+fn validate_cpu_quota(value: &str) -> Result<String, String> {
+    parse_cpu_quota(value).map(|_| value.to_string())
+}
+
+/// Checks a task limit for clap
+fn validate_tasks_max(value: &str) -> Result<String, String> {
+    parse_tasks_max(value).map(|_| value.to_string())
+}
+
+/// Checks a CPU list like `0-3,5` for clap
+fn validate_cpu_affinity(value: &str) -> Result<String, String> {
+    parse_cpu_set(value).map(|_| value.to_string())
+}
+
+/// Parses a size in bytes with an optional 1024-based suffix, eg. `512M` or `1.5G`. `infinity`
+/// gives `u64::MAX`.
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn parse_bytes(value: &str) -> Result<u64, String> {
+    if value == INFINITY {
+        return Ok(u64::MAX);
+    }
+
+    let error = || format!("Invalid size `{value}`. Use bytes or a suffix like 512M or 2G");
+
+    let split_index = value
+        .find(|char: char| !char.is_ascii_digit() && char != '.')
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split_index);
+
+    let exponent = match suffix.to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(error()),
+    };
+
+    let number: f64 = number.parse().map_err(|_| error())?;
+
+    Ok((number * 1024_f64.powi(exponent)) as u64)
+}
+
+/// Size as written to the unit file. systemd only accepts uppercase suffixes, so `512m` becomes
+/// `512M`.
+///
+/// # Arguments
+///
+/// * `value` - A size accepted by `parse_bytes`
+///
+fn normalize_bytes(value: &str) -> String {
+    if value == INFINITY {
+        value.to_string()
+    } else {
+        value.to_ascii_uppercase()
+    }
+}
+
+/// Parses a CPU quota like `50%` into microseconds of CPU time per second. `infinity` gives
+/// `u64::MAX`.
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn parse_cpu_quota(value: &str) -> Result<u64, String> {
+    if value == INFINITY {
+        return Ok(u64::MAX);
+    }
+
+    value
+        .strip_suffix('%')
+        .and_then(|percent| percent.parse::<f64>().ok())
+        .filter(|percent| *percent > 0.0)
+        .map(|percent| (percent * 10_000.0) as u64)
+        .ok_or(format!(
+            "Invalid CPU quota `{value}`. Use a percentage like 50%"
+        ))
+}
+
+/// Parses a task limit. `infinity` gives `u64::MAX`.
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn parse_tasks_max(value: &str) -> Result<u64, String> {
+    if value == INFINITY {
+        return Ok(u64::MAX);
+    }
+
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|tasks| *tasks > 0)
+        .ok_or(format!(
+            "Invalid task limit `{value}`. Use a number or infinity"
+        ))
+}
+
+/// Parses a list of CPUs like `0-3,5` or `0-3 5`
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn parse_cpu_set(value: &str) -> Result<Vec<u32>, String> {
+    let error = || format!("Invalid CPU list `{value}`. Use CPU numbers and ranges like 0-3,5");
+    let mut cpus = vec![];
+
+    for part in value.split([',', ' ']).filter(|part| !part.is_empty()) {
+        let (start, end) = part.split_once('-').unwrap_or((part, part));
+        let start: u32 = start.parse().map_err(|_| error())?;
+        let end: u32 = end.parse().map_err(|_| error())?;
+
+        if start > end || end >= 8192 {
+            return Err(error());
+        }
+
+        cpus.extend(start..=end);
+    }
+
+    if cpus.is_empty() {
+        return Err(error());
+    }
+
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIB: u64 = 1024;
+
+    #[test]
+    fn parses_bytes() {
+        let cases = [
+            ("0", 0),
+            ("512", 512),
+            ("512B", 512),
+            ("4K", 4 * KIB),
+            ("512M", 512 * KIB * KIB),
+            ("512m", 512 * KIB * KIB),
+            ("2G", 2 * KIB * KIB * KIB),
+            ("1.5G", 3 * KIB * KIB * KIB / 2),
+            ("1T", KIB * KIB * KIB * KIB),
+            ("infinity", u64::MAX),
+        ];
+
+        for (value, bytes) in cases {
+            assert_eq!(parse_bytes(value), Ok(bytes), "{value}");
+        }
+
+        for invalid in ["", "M", "512X", "512MB", "-1G", "1.2.3G", "Infinity", "ten"] {
+            assert!(parse_bytes(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn normalizes_bytes() {
+        let cases = [
+            ("512m", "512M"),
+            ("2g", "2G"),
+            ("1.5t", "1.5T"),
+            ("4K", "4K"),
+            ("1024", "1024"),
+            ("infinity", "infinity"),
+        ];
+
+        for (value, normalized) in cases {
+            assert_eq!(normalize_bytes(value), normalized, "{value}");
+        }
+    }
+
+    #[test]
+    fn parses_cpu_quotas() {
+        let cases = [
+            ("50%", 500_000),
+            ("100%", 1_000_000),
+            ("12.5%", 125_000),
+            ("200%", 2_000_000),
+            ("350%", 3_500_000),
+            ("infinity", u64::MAX),
+        ];
+
+        for (value, usec_per_sec) in cases {
+            assert_eq!(parse_cpu_quota(value), Ok(usec_per_sec), "{value}");
+        }
+
+        for invalid in ["", "50", "0%", "-10%", "%", "half%"] {
+            assert!(parse_cpu_quota(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_tasks_max() {
+        assert_eq!(parse_tasks_max("512"), Ok(512));
+        assert_eq!(parse_tasks_max("infinity"), Ok(u64::MAX));
+
+        for invalid in ["", "0", "-1", "50%"] {
+            assert!(parse_tasks_max(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_cpu_sets() {
+        let cases: [(&str, &[u32]); 6] = [
+            ("0", &[0]),
+            ("0-3", &[0, 1, 2, 3]),
+            ("0,2", &[0, 2]),
+            ("0-3,5", &[0, 1, 2, 3, 5]),
+            ("0-1 4-5", &[0, 1, 4, 5]),
+            ("7-7", &[7]),
+        ];
+
+        for (value, cpus) in cases {
+            assert_eq!(parse_cpu_set(value).as_deref(), Ok(cpus), "{value}");
+        }
+
+        for invalid in ["", ",", "3-1", "0-", "-3", "a", "0-8192", "1.5"] {
+            assert!(parse_cpu_set(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn writes_directives() {
+        let limits = ResourceLimits {
+            memory_max: Some("512m".to_string()),
+            cpu_quota: Some("150%".to_string()),
+            tasks_max: Some("infinity".to_string()),
+            nice: Some(-5),
+            io_weight: Some(500),
+            cpu_affinity: Some("0-3,5".to_string()),
+        };
+
+        let directives = limits.get_directives();
+        let directives: Vec<(&str, &str)> = directives
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+
+        assert_eq!(
+            directives,
+            [
+                ("MemoryMax", "512M"),
+                ("CPUQuota", "150%"),
+                ("TasksMax", "infinity"),
+                ("Nice", "-5"),
+                ("IOWeight", "500"),
+                ("CPUAffinity", "0-3 5"),
+            ]
+        );
+        assert!(limits.validate().is_ok());
+
+        let unlimited_cpu = ResourceLimits {
+            cpu_quota: Some("infinity".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            unlimited_cpu.get_directives(),
+            [("CPUQuota".to_string(), "".to_string())]
+        );
+        assert!(ResourceLimits::default().is_empty());
+    }
+
+    #[test]
+    fn only_sends_cgroup_limits_to_running_services() {
+        let limits = ResourceLimits {
+            memory_max: Some("1G".to_string()),
+            cpu_quota: Some("250%".to_string()),
+            nice: Some(10),
+            cpu_affinity: Some("0-1".to_string()),
+            ..Default::default()
+        };
+
+        let keys: Vec<String> = limits
+            .get_properties()
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        assert_eq!(keys, ["MemoryMax", "CPUQuotaPerSecUSec"]);
+    }
+
+    #[test]
+    fn validates_manifest_limits() {
+        let invalid = [
+            ResourceLimits {
+                memory_max: Some("lots".to_string()),
+                ..Default::default()
+            },
+            ResourceLimits {
+                nice: Some(20),
+                ..Default::default()
+            },
+            ResourceLimits {
+                io_weight: Some(0),
+                ..Default::default()
+            },
+            ResourceLimits {
+                cpu_affinity: Some("3-1".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        for limits in invalid {
+            assert!(limits.validate().is_err(), "{limits:?}");
+        }
+    }
+}
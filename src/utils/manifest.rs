@@ -0,0 +1,193 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::handlers::handle_create_service::CreateServiceOptions;
+
+use super::{
+    hardening::Hardening,
+    limits::ResourceLimits,
+    restart::{RestartOptions, RestartPolicy},
+};
+
+/// A `servicer.toml` manifest declaring the services that should exist on the machine
+///
+/// ```toml
+/// [[service]]
+/// name = "api"
+/// path = "./api/index.js"
+/// args = ["--port", "3000"]
+/// env = { NODE_ENV = "production" }
+/// tags = ["backend"]
+/// limits = { memory_max = "512M", cpu_quota = "50%" }
+/// hardening = "strict"
+/// restart = { policy = "on-failure", restart_sec = "5s" }
+/// enable = true
+/// start = true
+///
+/// [[service]]
+/// name = "web"
+/// cmd = "npm run start"
+/// cwd = "./web"
+///
+/// [[service]]
+/// name = "backup"
+/// path = "./backup.sh"
+/// schedule = "0 3 * * *"
+/// ```
+#[derive(Deserialize, Debug)]
+pub struct Manifest {
+    /// Services declared in the manifest
+    #[serde(default, rename = "service")]
+    pub services: Vec<ManifestService>,
+
+    /// Absolute path of the folder holding the manifest. Relative paths start from it.
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// A single service in a `servicer.toml` manifest
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestService {
+    /// The service name, eg. hello-world
+    pub name: String,
+
+    /// Path of the file to run. Relative paths are resolved from the manifest's folder
+    pub path: Option<PathBuf>,
+
+    /// Command line to run instead of a file, eg. `npm run start`
+    pub cmd: Option<String>,
+
+    /// Working directory for `cmd`. Relative paths are resolved from the manifest's folder
+    pub cwd: Option<PathBuf>,
+
+    /// Optional custom interpreter, eg. `python3`
+    pub interpreter: Option<String>,
+
+    /// Args passed to the file or appended to `cmd`
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Environment variables
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// File to load environment variables from. Relative paths are resolved from the manifest's folder
+    pub env_file: Option<PathBuf>,
+
+    /// Resource limits, eg. `{ memory_max = "512M" }`
+    #[serde(default)]
+    pub limits: ResourceLimits,
+
+    /// Sandboxing preset: `basic`, `strict` or `custom`
+    pub hardening: Option<Hardening>,
+
+    /// Run on a schedule with a timer, eg. `daily`, `*-*-* 03:00:00` or `0 3 * * *`
+    pub schedule: Option<String>,
+
+    /// Files or folders restarting the service when they change. Relative paths are resolved from
+    /// the manifest's folder
+    #[serde(default)]
+    pub watch: Vec<PathBuf>,
+
+    /// Tags to act on a group of services
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Restart policy, delays and start limits, eg. `{ policy = "on-failure", restart_sec = "5s" }`
+    #[serde(default)]
+    pub restart: RestartOptions,
+
+    /// Always restart. Same as `restart = { policy = "always" }`
+    #[serde(default)]
+    pub auto_restart: bool,
+
+    /// Enable the service to start on boot
+    #[serde(default)]
+    pub enable: bool,
+
+    /// Ensure that the service is running
+    #[serde(default)]
+    pub start: bool,
+}
+
+impl ManifestService {
+    /// Options to create this service with `create_service_file`
+    ///
+    /// # Arguments
+    ///
+    /// * `manifest_dir` - Folder holding the manifest. Relative paths are resolved from here.
+    ///
+    pub fn to_create_options(&self, manifest_dir: &Path) -> CreateServiceOptions {
+        let mut restart = self.restart.clone();
+        if self.auto_restart && restart.policy.is_none() {
+            restart.policy = Some(RestartPolicy::Always);
+        }
+
+        CreateServiceOptions {
+            path: self.path.as_ref().map(|path| manifest_dir.join(path)),
+            command: self.cmd.clone(),
+            working_directory: self
+                .cmd
+                .as_ref()
+                .map(|_| manifest_dir.join(self.cwd.as_deref().unwrap_or(Path::new("")))),
+            name: Some(self.name.clone()),
+            user: None,
+            start: self.start,
+            enable: self.enable,
+            restart,
+            interpreter: self.interpreter.clone(),
+            env_vars: self.env.clone().into_iter().collect(),
+            env_file: self
+                .env_file
+                .as_ref()
+                .map(|env_file| manifest_dir.join(env_file)),
+            secrets: vec![],
+            limits: self.limits.clone(),
+            hardening: self.hardening,
+            schedule: self.schedule.clone(),
+            watch: self
+                .watch
+                .iter()
+                .map(|path| manifest_dir.join(path))
+                .collect(),
+            tags: self.tags.clone(),
+            internal_args: self.args.clone(),
+        }
+    }
+}
+
+/// Reads and parses a manifest file
+///
+/// # Arguments
+///
+/// * `path` - Path to the manifest, eg. `servicer.toml`
+///
+pub async fn read_manifest(path: &Path) -> Result<Manifest, Box<dyn std::error::Error>> {
+    let read_error = |error| format!("Failed to read manifest {}: {error}", path.display());
+
+    let path = tokio::fs::canonicalize(path).await.map_err(read_error)?;
+    let contents = tokio::fs::read_to_string(&path).await.map_err(read_error)?;
+
+    let mut manifest: Manifest = toml::from_str(&contents)
+        .map_err(|error| format!("Invalid manifest {}: {error}", path.display()))?;
+    manifest.dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+    let mut names = std::collections::HashSet::new();
+    for service in &manifest.services {
+        if !names.insert(&service.name) {
+            return Err(format!("Service {} is declared more than once", service.name).into());
+        }
+
+        if service.path.is_some() == service.cmd.is_some() {
+            return Err(
+                format!("Service {} needs exactly one of path or cmd", service.name).into(),
+            );
+        }
+    }
+
+    Ok(manifest)
+}
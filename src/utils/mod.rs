@@ -1,5 +1,20 @@
+pub mod audit;
+pub mod config;
+pub mod credentials;
+pub mod crontab;
+pub mod env_file;
 pub mod find_binary_path;
+pub mod hardening;
+pub mod interpreter;
+pub mod limits;
+pub mod manifest;
+pub mod output;
 pub mod process_status;
+pub mod restart;
+pub mod schedule;
+pub mod scope;
 pub mod service_actions;
 pub mod service_names;
 pub mod systemd;
+pub mod unit_file;
+pub mod watch;
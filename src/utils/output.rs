@@ -0,0 +1,121 @@
+use cli_table::{Row, Table, Title, WithTitle};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// Output format chosen with `--output`. Set once at startup.
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// How results are printed
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable tables
+    #[default]
+    Table,
+
+    /// JSON documents for scripts
+    Json,
+
+    /// YAML documents for scripts
+    Yaml,
+}
+
+/// Result of an action on a service, eg. starting or deleting it
+#[derive(Table, Serialize, Clone)]
+pub struct ActionResult {
+    /// The short service name, excluding '.ser.service'
+    pub name: String,
+
+    /// The action, eg. `start`
+    pub action: String,
+
+    /// `done` on success, `no-op` if nothing had to be done, otherwise the failed job's result
+    /// such as `failed`, `timeout` or `dependency`. `error` if the action couldn't be requested.
+    pub result: String,
+}
+
+impl ActionResult {
+    pub fn new(name: &str, action: &str, result: &str) -> Self {
+        ActionResult {
+            name: name.to_string(),
+            action: action.to_string(),
+            result: result.to_string(),
+        }
+    }
+
+    /// Whether the action failed
+    pub fn is_failure(&self) -> bool {
+        self.result != "done" && self.result != "no-op"
+    }
+}
+
+/// Sets the output format
+///
+/// # Arguments
+///
+/// * `output_format`
+///
+pub fn set_output_format(output_format: OutputFormat) {
+    OUTPUT_FORMAT
+        .set(output_format)
+        .expect("Output format is already set");
+}
+
+/// Whether output is printed as JSON or YAML documents instead of tables
+pub fn is_structured_output() -> bool {
+    get_output_format() != OutputFormat::Table
+}
+
+/// The output format chosen with `--output`
+pub fn get_output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.get().unwrap_or(&OutputFormat::Table)
+}
+
+/// Prints rows as a table, or as a JSON or YAML list
+///
+/// # Arguments
+///
+/// * `rows`
+///
+pub fn print_rows<R>(rows: &[R]) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: Title + Serialize + 'static,
+    for<'a> &'a R: Row,
+{
+    match get_output_format() {
+        OutputFormat::Table => cli_table::print_stdout(rows.with_title())?,
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(rows)?),
+    }
+
+    Ok(())
+}
+
+/// Prints a single JSON or YAML document. Only used with `--output json` or `--output yaml`.
+///
+/// # Arguments
+///
+/// * `document`
+///
+pub fn print_document<D: Serialize>(document: &D) -> Result<(), Box<dyn std::error::Error>> {
+    match get_output_format() {
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(document)?),
+        _ => println!("{}", serde_json::to_string_pretty(document)?),
+    }
+
+    Ok(())
+}
+
+/// Prints a progress message for humans. Goes to stderr when printing JSON or YAML so that
+/// stdout only holds the document.
+///
+/// # Arguments
+///
+/// * `message`
+///
+pub fn print_message(message: &str) {
+    if is_structured_output() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
@@ -1,72 +1,134 @@
-use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
-};
-
-/// Gets the kernel page size of the system in KB
-pub async fn get_page_size() -> Result<usize, Box<dyn std::error::Error>> {
-    let path = "/proc/self/smaps";
-    let file = File::open(path).await?;
-    let reader = BufReader::new(file);
-
-    let mut kernel_page_size: Option<usize> = None;
-
-    let mut lines = reader.lines();
-    while let Some(line) = lines.next_line().await? {
-        if line.starts_with("KernelPageSize:") {
-            if let Some(size_str) = line.split_whitespace().nth(1) {
-                if let Ok(size) = size_str.parse::<usize>() {
-                    kernel_page_size = Some(size);
-                    break;
-                }
-            }
-        }
-    }
+use std::path::Path;
+use zbus::Connection;
+
+use super::systemd::get_service_accounting;
+
+/// Root of the unified (v2) cgroup hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Resource usage of a service's whole control group, i.e. the main process and all its children
+#[derive(Default, Clone, Copy)]
+pub struct ResourceUsage {
+    /// Memory in bytes
+    pub memory_bytes: u64,
+
+    /// Total CPU time consumed in nanoseconds
+    pub cpu_usage_nsec: u64,
+
+    /// Number of processes and threads
+    pub tasks: u64,
+
+    /// Bytes read from block devices
+    pub io_read_bytes: u64,
 
-    kernel_page_size.ok_or_else(|| format!("can't find KernelPageSize from {}", path).into())
+    /// Bytes written to block devices
+    pub io_write_bytes: u64,
 }
 
-/// Gets the memory used by a process in KB
+/// Gets the resource usage of a service's control group
 ///
-/// Formula from QPS: (rss pages - shared pages) * page size
+/// Values are read from the systemd `Service` interface. If accounting is disabled for the unit,
+/// the value is read from the cgroup files instead. Unavailable values are 0.
 ///
 /// # Arguments
 ///
-/// * `pid` - Process ID
-/// * `page_size` - The page size in KB
+/// * `connection`: zbus connection
+/// * `full_service_name`: Full name of the service, having '.ser.service' at the end
 ///
-pub async fn get_memory_usage(pid: u32, page_size_kb: u64) -> Result<u64, std::io::Error> {
-    let path = format!("/proc/{}/statm", pid);
-    let mut file = File::open(&path).await?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).await?;
-
-    let values: Vec<&str> = contents.trim().split_whitespace().collect();
-    if values.len() < 2 {
-        panic!("Invalid format of /proc/PID/statm file");
-    }
+pub async fn get_resource_usage(
+    connection: &Connection,
+    full_service_name: &str,
+) -> Result<ResourceUsage, zbus::Error> {
+    let accounting = get_service_accounting(connection, full_service_name).await?;
+    let cgroup_path = Path::new(CGROUP_ROOT).join(accounting.control_group.trim_start_matches('/'));
+
+    let memory_bytes = match accounting.memory_bytes {
+        Some(memory_bytes) => memory_bytes,
+        None => read_cgroup_value(&cgroup_path, "memory.current").await,
+    };
+
+    let cpu_usage_nsec = match accounting.cpu_usage_nsec {
+        Some(cpu_usage_nsec) => cpu_usage_nsec,
+        None => read_cgroup_cpu_usage_usec(&cgroup_path).await * 1000,
+    };
+
+    let tasks = match accounting.tasks {
+        Some(tasks) => tasks,
+        None => read_cgroup_value(&cgroup_path, "pids.current").await,
+    };
 
-    let rss_pages: u64 = values[1].parse().unwrap_or(0);
-    let shared_pages: u64 = values[2].parse().unwrap_or(0);
+    let (io_read_bytes, io_write_bytes) =
+        match (accounting.io_read_bytes, accounting.io_write_bytes) {
+            (Some(io_read_bytes), Some(io_write_bytes)) => (io_read_bytes, io_write_bytes),
+            _ => read_cgroup_io_bytes(&cgroup_path).await,
+        };
 
-    Ok((rss_pages - shared_pages) * page_size_kb)
+    Ok(ResourceUsage {
+        memory_bytes,
+        cpu_usage_nsec,
+        tasks,
+        io_read_bytes,
+        io_write_bytes,
+    })
 }
 
-/// Gets the CPU time of a process
+/// Reads a cgroup file holding a single number, eg. `memory.current`. Returns 0 if the file is
+/// missing.
 ///
 /// # Arguments
 ///
-/// * `pid`
+/// * `cgroup_path` - Folder of the control group
+/// * `file_name`
 ///
-pub async fn get_cpu_time(pid: u32) -> Result<u64, Box<dyn std::error::Error>> {
-    let stat_path = format!("/proc/{}/stat", pid);
-    let stat_content = tokio::fs::read_to_string(stat_path).await?;
-    let stat_fields: Vec<&str> = stat_content.split_whitespace().collect();
+async fn read_cgroup_value(cgroup_path: &Path, file_name: &str) -> u64 {
+    tokio::fs::read_to_string(cgroup_path.join(file_name))
+        .await
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+        .unwrap_or(0)
+}
 
-    // The 14th field in /proc/<pid>/stat represents utime (user mode CPU time) in clock ticks
-    // The 15th field represents stime (kernel mode CPU time) in clock ticks
-    let utime: u64 = stat_fields[13].parse()?;
-    let stime: u64 = stat_fields[14].parse()?;
+/// Reads the CPU time consumed by a control group in microseconds from `cpu.stat`
+///
+/// # Arguments
+///
+/// * `cgroup_path` - Folder of the control group
+///
+async fn read_cgroup_cpu_usage_usec(cgroup_path: &Path) -> u64 {
+    let contents = tokio::fs::read_to_string(cgroup_path.join("cpu.stat"))
+        .await
+        .unwrap_or_default();
+
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Reads bytes read and written by a control group from `io.stat`, summed over all devices
+///
+/// Each line has the format `8:0 rbytes=1459200 wbytes=314773504 rios=192 wios=353 ...`
+///
+/// # Arguments
+///
+/// * `cgroup_path` - Folder of the control group
+///
+async fn read_cgroup_io_bytes(cgroup_path: &Path) -> (u64, u64) {
+    let contents = tokio::fs::read_to_string(cgroup_path.join("io.stat"))
+        .await
+        .unwrap_or_default();
+
+    let mut read_bytes = 0;
+    let mut write_bytes = 0;
+
+    for field in contents.split_whitespace() {
+        if let Some(value) = field.strip_prefix("rbytes=") {
+            read_bytes += value.parse::<u64>().unwrap_or(0);
+        } else if let Some(value) = field.strip_prefix("wbytes=") {
+            write_bytes += value.parse::<u64>().unwrap_or(0);
+        }
+    }
 
-    Ok(utime + stime)
+    (read_bytes, write_bytes)
 }
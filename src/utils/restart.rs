@@ -0,0 +1,375 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// First systemd version supporting `RestartSteps=` and `RestartMaxDelaySec=`
+pub const BACKOFF_MIN_VERSION: u32 = 254;
+
+/// When systemd restarts a service, written as `Restart=`
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Never restart
+    No,
+
+    /// Restart if the service exits with an error, is killed by a signal or times out
+    OnFailure,
+
+    /// Restart if the service is killed by a signal or times out, but not on error exit codes
+    OnAbnormal,
+
+    /// Always restart, even after a clean exit
+    Always,
+}
+
+impl RestartPolicy {
+    /// Value for `Restart=`, eg. `on-failure`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestartPolicy::No => "no",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::OnAbnormal => "on-abnormal",
+            RestartPolicy::Always => "always",
+        }
+    }
+}
+
+/// Restart behaviour of a service. Set at create time or in a manifest under `restart`.
+#[derive(clap::Args, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestartOptions {
+    /// When to restart the service
+    #[arg(long = "restart", value_enum, conflicts_with = "auto_restart")]
+    pub policy: Option<RestartPolicy>,
+
+    /// Time to wait before restarting, eg. `5s` or `1min`. Defaults to 100ms
+    #[arg(long, value_parser = validate_duration)]
+    pub restart_sec: Option<String>,
+
+    /// Give up restarting after this many starts within `--start-limit-interval`. Defaults to 5
+    #[arg(long)]
+    pub start_limit_burst: Option<u32>,
+
+    /// Interval for `--start-limit-burst`, eg. `1min`. `0` never gives up. Defaults to 10s
+    #[arg(long, value_parser = validate_duration)]
+    pub start_limit_interval: Option<String>,
+
+    /// Number of steps to grow the restart delay from `--restart-sec` to `--restart-max-delay`.
+    /// Needs systemd 254 or newer
+    #[arg(long, requires = "restart_max_delay")]
+    pub restart_steps: Option<u32>,
+
+    /// Longest delay between restarts when backing off, eg. `5min`. Needs systemd 254 or newer
+    #[arg(long, value_parser = validate_duration)]
+    pub restart_max_delay: Option<String>,
+}
+
+impl RestartOptions {
+    /// Whether exponential backoff is configured. It needs a recent systemd.
+    pub fn has_backoff(&self) -> bool {
+        self.restart_steps.is_some() || self.restart_max_delay.is_some()
+    }
+
+    /// Checks the values. Values passed on the command line are already checked by clap, values
+    /// from a manifest are not.
+    pub fn validate(&self) -> Result<(), String> {
+        for duration in [
+            &self.restart_sec,
+            &self.start_limit_interval,
+            &self.restart_max_delay,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            parse_duration(duration)?;
+        }
+
+        if self.restart_steps.is_some() && self.restart_max_delay.is_none() {
+            return Err("restart_steps needs restart_max_delay".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Key and value pairs for the `[Unit]` section
+    pub fn get_unit_directives(&self) -> Vec<(String, String)> {
+        let mut directives = vec![];
+
+        if let Some(interval) = &self.start_limit_interval {
+            directives.push(("StartLimitIntervalSec", format_duration(interval)));
+        }
+
+        if let Some(burst) = self.start_limit_burst {
+            directives.push(("StartLimitBurst", burst.to_string()));
+        }
+
+        to_owned_pairs(directives)
+    }
+
+    /// Key and value pairs for the `[Service]` section
+    ///
+    /// # Arguments
+    ///
+    /// * `supports_backoff` - Whether the installed systemd supports `RestartSteps=` and
+    ///   `RestartMaxDelaySec=`
+    ///
+    pub fn get_service_directives(&self, supports_backoff: bool) -> Vec<(String, String)> {
+        let mut directives = vec![];
+
+        if let Some(policy) = self.policy {
+            directives.push(("Restart", policy.as_str().to_string()));
+        }
+
+        if let Some(restart_sec) = &self.restart_sec {
+            directives.push(("RestartSec", format_duration(restart_sec)));
+        }
+
+        if supports_backoff {
+            if let Some(steps) = self.restart_steps {
+                directives.push(("RestartSteps", steps.to_string()));
+            }
+
+            if let Some(max_delay) = &self.restart_max_delay {
+                directives.push(("RestartMaxDelaySec", format_duration(max_delay)));
+            }
+        }
+
+        to_owned_pairs(directives)
+    }
+}
+
+/// Major version of systemd from the Manager's `Version` property, eg. 252 for `252.17-1~deb12u1`
+///
+/// # Arguments
+///
+/// * `version`
+///
+pub fn parse_systemd_version(version: &str) -> Option<u32> {
+    let major: String = version
+        .trim_start_matches('v')
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+
+    major.parse().ok()
+}
+
+/// Whether a systemd version supports `RestartSteps=` and `RestartMaxDelaySec=`
+///
+/// # Arguments
+///
+/// * `version` - The Manager's `Version` property
+///
+pub fn supports_backoff(version: &str) -> bool {
+    parse_systemd_version(version).is_some_and(|major| major >= BACKOFF_MIN_VERSION)
+}
+
+/// Checks a duration like `5s` for clap
+fn validate_duration(value: &str) -> Result<String, String> {
+    parse_duration(value).map(|_| value.to_string())
+}
+
+/// Parses a duration like `5s`, `1min 30s` or a number of seconds
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    humantime::parse_duration(value)
+        .map_err(|_| format!("Invalid duration `{value}`. Use eg. 500ms, 5s or 1min"))
+}
+
+/// Formats a checked duration in a form systemd reads, eg. `1m 30s`
+///
+/// # Arguments
+///
+/// * `value`
+///
+fn format_duration(value: &str) -> String {
+    let duration = parse_duration(value).unwrap_or_default();
+
+    if duration.is_zero() {
+        "0".to_string()
+    } else {
+        humantime::format_duration(duration).to_string()
+    }
+}
+
+/// Converts static keys to owned pairs
+///
+/// # Arguments
+///
+/// * `pairs`
+///
+fn to_owned_pairs(pairs: Vec<(&str, String)>) -> Vec<(String, String)> {
+    pairs
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Owned pairs for comparing directives
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("5"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1min 30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+
+        for invalid in ["", "-5", "5 parsecs", "fast"] {
+            assert!(parse_duration(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration("5"), "5s");
+        assert_eq!(format_duration("0"), "0");
+        assert_eq!(format_duration("0s"), "0");
+        assert_eq!(format_duration("500ms"), "500ms");
+        assert_eq!(format_duration("90s"), "1m 30s");
+        assert_eq!(format_duration("1min 30s"), "1m 30s");
+    }
+
+    #[test]
+    fn writes_restart_sec() {
+        let options = RestartOptions {
+            policy: Some(RestartPolicy::OnFailure),
+            restart_sec: Some("90".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.get_service_directives(true),
+            pairs(&[("Restart", "on-failure"), ("RestartSec", "1m 30s")])
+        );
+        assert!(options.get_unit_directives().is_empty());
+    }
+
+    #[test]
+    fn writes_start_limits() {
+        let options = RestartOptions {
+            start_limit_burst: Some(3),
+            start_limit_interval: Some("1min".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.get_unit_directives(),
+            pairs(&[("StartLimitIntervalSec", "1m"), ("StartLimitBurst", "3")])
+        );
+        assert!(options.get_service_directives(true).is_empty());
+
+        let never_give_up = RestartOptions {
+            start_limit_interval: Some("0".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            never_give_up.get_unit_directives(),
+            pairs(&[("StartLimitIntervalSec", "0")])
+        );
+    }
+
+    #[test]
+    fn writes_backoff_only_when_supported() {
+        let options = RestartOptions {
+            policy: Some(RestartPolicy::Always),
+            restart_sec: Some("1s".to_string()),
+            restart_steps: Some(5),
+            restart_max_delay: Some("5min".to_string()),
+            ..Default::default()
+        };
+
+        assert!(options.has_backoff());
+        assert_eq!(
+            options.get_service_directives(true),
+            pairs(&[
+                ("Restart", "always"),
+                ("RestartSec", "1s"),
+                ("RestartSteps", "5"),
+                ("RestartMaxDelaySec", "5m"),
+            ])
+        );
+        assert_eq!(
+            options.get_service_directives(false),
+            pairs(&[("Restart", "always"), ("RestartSec", "1s")])
+        );
+    }
+
+    #[test]
+    fn validates_options() {
+        assert!(RestartOptions::default().validate().is_ok());
+        assert!(!RestartOptions::default().has_backoff());
+
+        let max_delay_only = RestartOptions {
+            restart_max_delay: Some("5min".to_string()),
+            ..Default::default()
+        };
+        assert!(max_delay_only.validate().is_ok());
+
+        let steps_only = RestartOptions {
+            restart_steps: Some(5),
+            ..Default::default()
+        };
+        assert!(steps_only.has_backoff());
+        assert!(steps_only.validate().is_err());
+
+        let invalid_durations = [
+            RestartOptions {
+                restart_sec: Some("soon".to_string()),
+                ..Default::default()
+            },
+            RestartOptions {
+                start_limit_interval: Some("1 fortnight".to_string()),
+                ..Default::default()
+            },
+            RestartOptions {
+                restart_steps: Some(5),
+                restart_max_delay: Some("later".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        for options in invalid_durations {
+            assert!(options.validate().is_err(), "{options:?}");
+        }
+    }
+
+    #[test]
+    fn parses_systemd_versions() {
+        assert_eq!(parse_systemd_version("252.17-1~deb12u1"), Some(252));
+        assert_eq!(parse_systemd_version("255"), Some(255));
+        assert_eq!(parse_systemd_version("v256.4"), Some(256));
+        assert_eq!(parse_systemd_version("249.11-0ubuntu3.12"), Some(249));
+        assert_eq!(parse_systemd_version(""), None);
+        assert_eq!(parse_systemd_version("unknown"), None);
+    }
+
+    #[test]
+    fn supports_backoff_from_systemd_254() {
+        assert!(!supports_backoff("253.5-1"));
+        assert!(supports_backoff("254"));
+        assert!(supports_backoff(&BACKOFF_MIN_VERSION.to_string()));
+        assert!(supports_backoff("256.4-2"));
+        assert!(!supports_backoff("unknown"));
+    }
+}
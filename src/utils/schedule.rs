@@ -0,0 +1,448 @@
+use indoc::formatdoc;
+use tokio::{fs, process::Command};
+
+use super::{
+    service_actions::{disable_service, enable_service, start_service, stop_service},
+    service_names::{get_full_timer_name, get_timer_file_path},
+    systemd::ManagerProxy,
+    unit_file::get_unit_values,
+};
+
+/// Month names accepted in cron schedules
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Weekday names accepted in cron schedules, starting from 0 = Sunday
+const CRON_WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Weekday names in systemd calendar expressions, starting from Sunday
+const WEEKDAYS: &[&str] = &["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Converts a schedule to an `OnCalendar=` expression and checks it with `systemd-analyze`.
+///
+/// Accepts systemd calendar expressions like `*-*-* 03:00:00`, shortcuts like `hourly` or
+/// `@daily`, and 5 field cron schedules like `0 3 * * 1-5`.
+///
+/// # Arguments
+///
+/// * `schedule`
+///
+pub async fn parse_schedule(schedule: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let schedule = schedule.trim();
+    let on_calendar = convert_schedule(schedule)?;
+
+    let output = Command::new("systemd-analyze")
+        .arg("calendar")
+        .arg(&on_calendar)
+        .output()
+        .await
+        .map_err(|error| format!("Failed to run systemd-analyze: {error}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Invalid schedule `{schedule}`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(on_calendar)
+}
+
+/// Generates the timer file starting a scheduled service. Missed runs, eg. while the machine was
+/// off, run as soon as possible.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `on_calendar` - The `OnCalendar=` expression
+///
+pub fn get_timer_file_body(full_service_name: &str, on_calendar: &str) -> String {
+    let on_calendar = on_calendar.replace('%', "%%");
+
+    formatdoc! {
+        r#"
+      # Generated with Servicer
+      [Unit]
+      Description=Runs {full_service_name} on a schedule
+
+      [Timer]
+      OnCalendar={on_calendar}
+      Persistent=true
+
+      [Install]
+      WantedBy=timers.target
+      "#
+    }
+}
+
+/// Schedule of a service read from its timer file. `None` if the service isn't scheduled.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn get_service_schedule(full_service_name: &str) -> Option<String> {
+    let body = fs::read_to_string(get_timer_file_path(full_service_name))
+        .await
+        .ok()?;
+
+    get_unit_values(&body, "OnCalendar")
+        .pop()
+        .map(|value| value.replace("%%", "%"))
+}
+
+/// Enables and starts the timer of a scheduled service
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn activate_timer(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_timer_name = get_full_timer_name(full_service_name);
+
+    enable_service(manager_proxy, &full_timer_name).await?;
+
+    match start_service(manager_proxy, &full_timer_name)
+        .await?
+        .as_str()
+    {
+        "done" => Ok(()),
+        result => Err(format!("Failed to start {full_timer_name}: {result}").into()),
+    }
+}
+
+/// Stops and disables the timer of a scheduled service, so that it doesn't start the service
+/// anymore
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn deactivate_timer(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_timer_name = get_full_timer_name(full_service_name);
+
+    stop_service(manager_proxy, &full_timer_name).await?;
+    disable_service(manager_proxy, &full_timer_name).await?;
+
+    Ok(())
+}
+
+/// Converts a schedule to an `OnCalendar=` expression without checking it. Cron schedules and
+/// `@` shortcuts are converted, calendar expressions are kept.
+///
+/// # Arguments
+///
+/// * `schedule`
+///
+pub(crate) fn convert_schedule(schedule: &str) -> Result<String, String> {
+    match schedule.strip_prefix('@') {
+        Some("reboot") => {
+            Err("@reboot isn't a schedule. Create a service and `ser enable` it instead".into())
+        }
+        Some("midnight") => Ok("daily".to_string()),
+        Some(shortcut) => Ok(shortcut.to_string()),
+        None if is_cron(schedule) => convert_cron(schedule),
+        None => Ok(schedule.to_string()),
+    }
+}
+
+/// Whether a schedule looks like a 5 field cron schedule, eg. `*/5 * * * *`. Calendar expressions
+/// have fewer fields, and their times have `:`.
+///
+/// # Arguments
+///
+/// * `schedule`
+///
+fn is_cron(schedule: &str) -> bool {
+    schedule.split_whitespace().count() == 5 && !schedule.contains(':')
+}
+
+/// Converts a cron schedule to a calendar expression, eg. `30 3 * * 1-5` to
+/// `Mon,Tue,Wed,Thu,Fri *-*-* 3:30:00`
+///
+/// # Arguments
+///
+/// * `cron` - Minute, hour, day of month, month and day of week
+///
+fn convert_cron(cron: &str) -> Result<String, String> {
+    let fields: Vec<&str> = cron.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return Err(format!("Invalid cron schedule `{cron}`"));
+    };
+
+    // cron runs when either matches, while systemd requires both to match
+    if day != "*" && weekday != "*" {
+        return Err(format!(
+            "Cron schedules restricting both the day of month and the day of week aren't supported: `{cron}`. Create one service per schedule"
+        ));
+    }
+
+    let minute = convert_cron_field(minute, 0, 59, &[])?;
+    let hour = convert_cron_field(hour, 0, 23, &[])?;
+    let day = convert_cron_field(day, 1, 31, &[])?;
+    let month = convert_cron_field(month, 1, 12, MONTHS)?;
+
+    let date_time = format!("*-{month}-{day} {hour}:{minute}:00");
+
+    if weekday == "*" {
+        return Ok(date_time);
+    }
+
+    // Both 0 and 7 are Sunday
+    let mut days: Vec<usize> = get_cron_values(weekday, 0, 7, CRON_WEEKDAYS)?
+        .into_iter()
+        .map(|day| day as usize % 7)
+        .collect();
+    days.sort();
+    days.dedup();
+
+    let weekdays: Vec<&str> = days.into_iter().map(|day| WEEKDAYS[day]).collect();
+
+    Ok(format!("{} {date_time}", weekdays.join(",")))
+}
+
+/// Converts a cron field to a calendar expression field. Lists and ranges are kept, steps become
+/// repetitions or lists.
+///
+/// # Arguments
+///
+/// * `field` - eg. `*/15`, `1-5` or `0,30`
+/// * `min` - Lowest value of the field
+/// * `max` - Highest value of the field
+/// * `names` - Names of the values, starting from `min`
+///
+fn convert_cron_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<String, String> {
+    let mut parts = vec![];
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (part, None),
+        };
+
+        let converted = match (range.split_once('-'), step) {
+            (None, None) if range == "*" => "*".to_string(),
+            (None, None) => parse_cron_value(range, min, max, names)?.to_string(),
+            (Some(_), None) => {
+                let (start, end) = parse_cron_range(range, min, max, names)?;
+                format!("{start}..{end}")
+            }
+            // `*/15` and `5/15` repeat till the end of the range
+            (None, Some(step)) => {
+                let start = if range == "*" {
+                    min
+                } else {
+                    parse_cron_value(range, min, max, names)?
+                };
+                format!("{start}/{}", parse_cron_step(step, field)?)
+            }
+            (Some(_), Some(_)) => get_cron_values(part, min, max, names)?
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<String>>()
+                .join(","),
+        };
+
+        parts.push(converted);
+    }
+
+    Ok(parts.join(","))
+}
+
+/// Every value matched by a cron field, eg. `1,3,5` for `1-5/2`
+///
+/// # Arguments
+///
+/// * `field`
+/// * `min` - Lowest value of the field
+/// * `max` - Highest value of the field
+/// * `names` - Names of the values, starting from `min`
+///
+fn get_cron_values(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<u32>, String> {
+    let mut values = vec![];
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_cron_step(step, field)?),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if range.contains('-') {
+            parse_cron_range(range, min, max, names)?
+        } else {
+            let value = parse_cron_value(range, min, max, names)?;
+            // `5/15` runs from 5 till the end
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    values.sort();
+    values.dedup();
+
+    Ok(values)
+}
+
+/// Parses a cron range like `1-5` or `mon-fri`
+///
+/// # Arguments
+///
+/// * `range`
+/// * `min` - Lowest value of the field
+/// * `max` - Highest value of the field
+/// * `names` - Names of the values, starting from `min`
+///
+fn parse_cron_range(range: &str, min: u32, max: u32, names: &[&str]) -> Result<(u32, u32), String> {
+    let (start, end) = range
+        .split_once('-')
+        .ok_or(format!("Invalid cron range `{range}`"))?;
+
+    let start = parse_cron_value(start, min, max, names)?;
+    let end = parse_cron_value(end, min, max, names)?;
+
+    if start > end {
+        return Err(format!("Invalid cron range `{range}`"));
+    }
+
+    Ok((start, end))
+}
+
+/// Parses a cron value, either a number or a name like `jan`
+///
+/// # Arguments
+///
+/// * `value`
+/// * `min` - Lowest value of the field
+/// * `max` - Highest value of the field
+/// * `names` - Names of the values, starting from `min`
+///
+fn parse_cron_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lowercase = value.to_ascii_lowercase();
+
+    let parsed = match names.iter().position(|name| *name == lowercase) {
+        Some(index) => Some(min + index as u32),
+        None => value.parse::<u32>().ok(),
+    };
+
+    parsed
+        .filter(|value| (min..=max).contains(value))
+        .ok_or(format!("Invalid cron value `{value}`. Use {min} to {max}"))
+}
+
+/// Parses the step of a cron field, eg. `15` in `*/15`
+///
+/// # Arguments
+///
+/// * `step`
+/// * `field` - The whole field, for the error message
+///
+fn parse_cron_step(step: &str, field: &str) -> Result<u32, String> {
+    step.parse::<u32>()
+        .ok()
+        .filter(|step| *step > 0)
+        .ok_or(format!("Invalid cron step in `{field}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_schedules() {
+        // Schedule, `OnCalendar=` expression
+        let cases = [
+            ("daily", "daily"),
+            ("@daily", "daily"),
+            ("@midnight", "daily"),
+            ("@weekly", "weekly"),
+            ("*-*-* 03:00:00", "*-*-* 03:00:00"),
+            ("Mon..Fri 09:00", "Mon..Fri 09:00"),
+            ("*/15 * * * *", "*-*-* *:0/15:00"),
+            ("0 3 * * *", "*-*-* 3:0:00"),
+            ("0,30 8-18 * * *", "*-*-* 8..18:0,30:00"),
+            ("0 0 1 */3 *", "*-1/3-1 0:0:00"),
+            ("30 9 * * 1-5", "Mon,Tue,Wed,Thu,Fri *-*-* 9:30:00"),
+            ("30 9 * * MON-FRI", "Mon,Tue,Wed,Thu,Fri *-*-* 9:30:00"),
+            ("0 10 * * 5-7", "Sun,Fri,Sat *-*-* 10:0:00"),
+            ("0 10 * * sat,sun", "Sun,Sat *-*-* 10:0:00"),
+            ("0 10 * * 1-5/2", "Mon,Wed,Fri *-*-* 10:0:00"),
+            ("0 10 * * */3", "Sun,Wed,Sat *-*-* 10:0:00"),
+        ];
+
+        for (schedule, on_calendar) in cases {
+            assert_eq!(
+                convert_schedule(schedule).as_deref(),
+                Ok(on_calendar),
+                "{schedule}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        let schedules = [
+            "@reboot",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "* * * * mon-sun",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "*/x * * * *",
+            "a b c d e",
+            "0 0 1 * 1",
+        ];
+
+        for schedule in schedules {
+            assert!(convert_schedule(schedule).is_err(), "{schedule}");
+        }
+    }
+
+    #[test]
+    fn detects_cron_schedules() {
+        assert!(is_cron("*/15 * * * *"));
+        assert!(is_cron("0 3 * * mon-fri"));
+        assert!(!is_cron("daily"));
+        assert!(!is_cron("* * * *"));
+        assert!(!is_cron("Mon *-*-* 03:00:00"));
+        assert!(!is_cron("Mon..Fri *-*-* 3 03:00"));
+    }
+
+    #[test]
+    fn converts_cron_fields() {
+        assert_eq!(convert_cron_field("*", 0, 59, &[]).as_deref(), Ok("*"));
+        assert_eq!(convert_cron_field("5", 0, 59, &[]).as_deref(), Ok("5"));
+        assert_eq!(convert_cron_field("1-5", 0, 59, &[]).as_deref(), Ok("1..5"));
+        assert_eq!(
+            convert_cron_field("*/15", 0, 59, &[]).as_deref(),
+            Ok("0/15")
+        );
+        assert_eq!(
+            convert_cron_field("5/15", 0, 59, &[]).as_deref(),
+            Ok("5/15")
+        );
+        assert_eq!(
+            convert_cron_field("0-10/5,30", 0, 59, &[]).as_deref(),
+            Ok("0,5,10,30")
+        );
+        assert_eq!(
+            convert_cron_field("mar-may", 1, 12, MONTHS).as_deref(),
+            Ok("3..5")
+        );
+    }
+}
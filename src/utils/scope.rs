@@ -0,0 +1,146 @@
+use std::{
+    env,
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Whether servicer manages the invoking user's services (`systemd --user`) instead of system
+/// services. Set once at startup.
+static USER_SCOPE: OnceLock<bool> = OnceLock::new();
+
+/// Sets the scope of services managed by servicer
+///
+/// # Arguments
+///
+/// * `user_scope` - Manage the invoking user's services instead of system services
+///
+pub fn set_user_scope(user_scope: bool) {
+    USER_SCOPE.set(user_scope).expect("Scope is already set");
+}
+
+/// Whether servicer manages the invoking user's services instead of system services
+pub fn is_user_scope() -> bool {
+    *USER_SCOPE.get().unwrap_or(&false)
+}
+
+/// Folder holding the unit files. `/etc/systemd/system/` for system services and
+/// `~/.config/systemd/user/` for user services.
+pub fn get_unit_folder() -> PathBuf {
+    if is_user_scope() {
+        get_user_config_folder().join("systemd/user")
+    } else {
+        PathBuf::from("/etc/systemd/system/")
+    }
+}
+
+/// Folder holding the env files managed with `ser env`. `/etc/servicer/env/` for system services and
+/// `~/.config/servicer/env/` for user services.
+pub fn get_env_folder() -> PathBuf {
+    if is_user_scope() {
+        get_user_config_folder().join("servicer/env")
+    } else {
+        PathBuf::from("/etc/servicer/env/")
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config`
+fn get_user_config_folder() -> PathBuf {
+    env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(&env::var("HOME").unwrap_or_default()).join(".config"))
+}
+
+/// Target used for `WantedBy=`, i.e. the target that starts enabled services
+pub fn get_default_target() -> &'static str {
+    if is_user_scope() {
+        "default.target"
+    } else {
+        "multi-user.target"
+    }
+}
+
+/// The user that services run as. This is the user who invoked sudo for system services, and the
+/// current user for user services.
+pub fn get_service_user() -> Result<String, Box<dyn std::error::Error>> {
+    if is_user_scope() {
+        get_current_user().ok_or_else(|| "Failed to find the current user".into())
+    } else {
+        // This gets `root` instead of `hp` if sudo is used
+        env::var("SUDO_USER")
+            .map_err(|_| "Must be in sudo mode. ENV variable $SUDO_USER not found".into())
+    }
+}
+
+/// Name of the user running this process
+pub fn get_current_user() -> Option<String> {
+    // SAFETY: getpwuid returns a pointer to static storage or null. The name is copied out
+    // before any other passwd lookup.
+    unsafe {
+        let passwd = libc::getpwuid(libc::getuid());
+
+        if passwd.is_null() {
+            env::var("USER").ok()
+        } else {
+            CStr::from_ptr((*passwd).pw_name)
+                .to_str()
+                .ok()
+                .map(|name| name.to_string())
+        }
+    }
+}
+
+/// Account details of a user, read from the passwd database
+pub struct UserAccount {
+    /// Home directory, eg. `/home/hp`
+    pub home: PathBuf,
+
+    /// Login shell, eg. `/bin/zsh`
+    pub shell: PathBuf,
+}
+
+/// Looks up the home directory and login shell of a user
+///
+/// # Arguments
+///
+/// * `user`
+///
+pub fn get_user_account(user: &str) -> Option<UserAccount> {
+    let user = CString::new(user).ok()?;
+
+    // SAFETY: getpwnam returns a pointer to static storage or null. The fields are copied out
+    // before any other passwd lookup.
+    unsafe {
+        let passwd = libc::getpwnam(user.as_ptr());
+
+        if passwd.is_null() {
+            return None;
+        }
+
+        let read = |field: *const libc::c_char| {
+            PathBuf::from(CStr::from_ptr(field).to_string_lossy().into_owned())
+        };
+
+        Some(UserAccount {
+            home: read((*passwd).pw_dir),
+            shell: read((*passwd).pw_shell),
+        })
+    }
+}
+
+/// Whether running as root
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Whether lingering is enabled for a user. User services keep running after logout and start on
+/// boot only if lingering is enabled.
+///
+/// # Arguments
+///
+/// * `user`
+///
+pub fn is_lingering(user: &str) -> bool {
+    Path::new("/var/lib/systemd/linger").join(user).exists()
+}
@@ -1,18 +1,65 @@
-use super::systemd::ManagerProxy;
+use futures::StreamExt;
+use zbus::zvariant::OwnedObjectPath;
 
-/// Starts a service
+use super::systemd::{JobRemovedStream, ManagerProxy};
+
+/// Starts a service and waits for the start job to complete. Returns the job result, one of
+/// `done`, `canceled`, `timeout`, `failed`, `dependency` or `skipped`.
 ///
 /// # Arguments
 ///
 /// * `manager_proxy`: Manager proxy object
 /// * `full_service_name`: Full name of the service, having '.ser.service' at the end
 ///
-pub async fn start_service(manager_proxy: &ManagerProxy<'_>, full_service_name: &String) -> String {
-    manager_proxy
-        .start_unit(full_service_name.clone(), "replace".into())
-        .await
-        .expect(&format!("Failed to start service {full_service_name}"))
-        .to_string()
+pub async fn start_service(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> zbus::Result<String> {
+    let mut job_removed = subscribe_to_jobs(manager_proxy).await?;
+    let job = manager_proxy
+        .start_unit(full_service_name.to_string(), "replace".into())
+        .await?;
+
+    wait_for_job(&mut job_removed, &job).await
+}
+
+/// Restarts a service, starting it if it is not running. Waits for the job to complete and
+/// returns its result.
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name`: Full name of the service, having '.ser.service' at the end
+///
+pub async fn restart_service(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> zbus::Result<String> {
+    let mut job_removed = subscribe_to_jobs(manager_proxy).await?;
+    let job = manager_proxy
+        .restart_unit(full_service_name.to_string(), "replace".into())
+        .await?;
+
+    wait_for_job(&mut job_removed, &job).await
+}
+
+/// Restarts a service only if it is running. Waits for the job to complete and returns its result.
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name`: Full name of the service, having '.ser.service' at the end
+///
+pub async fn try_restart_service(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> zbus::Result<String> {
+    let mut job_removed = subscribe_to_jobs(manager_proxy).await?;
+    let job = manager_proxy
+        .try_restart_unit(full_service_name.to_string(), "replace".into())
+        .await?;
+
+    wait_for_job(&mut job_removed, &job).await
 }
 
 /// Enables a service on boot
@@ -24,21 +71,30 @@ pub async fn start_service(manager_proxy: &ManagerProxy<'_>, full_service_name:
 ///
 pub async fn enable_service(
     manager_proxy: &ManagerProxy<'_>,
-    full_service_name: &String,
-) -> (bool, Vec<(String, String, String)>) {
+    full_service_name: &str,
+) -> zbus::Result<(bool, Vec<(String, String, String)>)> {
     manager_proxy
-        .enable_unit_files(vec![full_service_name.clone()], false, true)
+        .enable_unit_files(vec![full_service_name.to_string()], false, true)
         .await
-        .expect(&format!(
-            "Failed to enable service {full_service_name}. Retry in sudo mode."
-        ))
 }
 
-pub async fn stop_service(manager_proxy: &ManagerProxy<'_>, full_service_name: &String) {
-    manager_proxy
+/// Stops a service and waits for the stop job to complete. Returns the job result.
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name`: Full name of the service, having '.ser.service' at the end
+///
+pub async fn stop_service(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> zbus::Result<String> {
+    let mut job_removed = subscribe_to_jobs(manager_proxy).await?;
+    let job = manager_proxy
         .stop_unit(full_service_name.to_string(), "replace".into())
-        .await
-        .expect(&format!("Failed to stop service {full_service_name}"));
+        .await?;
+
+    wait_for_job(&mut job_removed, &job).await
 }
 
 /// Reloads the unit of a failed service
@@ -52,9 +108,7 @@ pub async fn reload_service(manager_proxy: &ManagerProxy<'_>, full_service_name:
     manager_proxy
         .reload_unit(full_service_name.clone(), "replace".into())
         .await
-        .expect(&format!(
-            "Failed to reload service {full_service_name}. Ensure it has an ExecReload statement"
-        ));
+        .unwrap_or_else(|_| panic!("Failed to reload service {full_service_name}. Ensure it has an ExecReload statement"));
 }
 
 /// Disables a service on boot
@@ -64,11 +118,55 @@ pub async fn reload_service(manager_proxy: &ManagerProxy<'_>, full_service_name:
 /// * `manager_proxy`: Manager proxy object
 /// * `full_service_name`: Full name of the service, having '.ser.service' at the end
 ///
-pub async fn disable_service(manager_proxy: &ManagerProxy<'_>, full_service_name: &String) {
+pub async fn disable_service(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> zbus::Result<Vec<(String, String, String)>> {
     manager_proxy
-        .disable_unit_files(vec![full_service_name.clone()], false)
+        .disable_unit_files(vec![full_service_name.to_string()], false)
         .await
-        .expect(&format!(
-            "Failed to disable service {full_service_name}. Retry in sudo mode."
-        ));
+}
+
+/// Listens for `JobRemoved` signals. Must be called before queueing a job, otherwise the signal
+/// for a job that completes immediately can be missed.
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+///
+async fn subscribe_to_jobs<'a>(
+    manager_proxy: &ManagerProxy<'a>,
+) -> zbus::Result<JobRemovedStream<'a>> {
+    let job_removed = manager_proxy.receive_job_removed().await?;
+
+    // systemd only emits job signals once a client subscribes. This fails with `AlreadySubscribed`
+    // if this connection has subscribed before, which is harmless.
+    let _ = manager_proxy.subscribe().await;
+
+    Ok(job_removed)
+}
+
+/// Waits for a job to be removed and returns its result
+///
+/// # Arguments
+///
+/// * `job_removed`: Stream of `JobRemoved` signals
+/// * `job`: Object path of the job
+///
+async fn wait_for_job(
+    job_removed: &mut JobRemovedStream<'_>,
+    job: &OwnedObjectPath,
+) -> zbus::Result<String> {
+    while let Some(signal) = job_removed.next().await {
+        let args = signal.args()?;
+
+        if args.job() == job {
+            return Ok(args.result().to_string());
+        }
+    }
+
+    Err(zbus::Error::Failure(format!(
+        "Stopped receiving signals before job {} completed",
+        job.as_str()
+    )))
 }
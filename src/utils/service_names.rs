@@ -1,4 +1,10 @@
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+use tokio::fs;
+
+use super::{scope::get_unit_folder, unit_file::get_service_tags};
+
+/// Target selecting every servicer service
+const ALL_SERVICES: &str = "all";
 
 /// Shortens the service name from `example.ser.service` to `example`.
 ///
@@ -10,10 +16,10 @@ use std::path::{Path, PathBuf};
 /// * `full_service_name`
 ///
 pub fn get_short_service_name(full_service_name: &str) -> String {
-    let file_extension = format!(".ser.service");
+    let file_extension = ".ser.service";
 
     full_service_name
-        .trim_end_matches(file_extension.as_str())
+        .trim_end_matches(file_extension)
         .to_string()
 }
 
@@ -36,17 +42,237 @@ pub fn get_full_service_name(short_name: &str) -> String {
 /// * `name` - The service name
 ///
 pub fn is_full_name(name: &str) -> bool {
-    let service_extension = format!(".ser.service");
+    let service_extension = ".ser.service";
 
-    name.ends_with(&service_extension)
+    name.ends_with(service_extension)
 }
 
-/// Get the path to a service file
+/// Get the path to a service file. Services live in `/etc/systemd/system/`, or in
+/// `~/.config/systemd/user/` when managing user services.
 ///
 /// # Arguments
 ///
 /// * `full_service_name`
 ///
 pub fn get_service_file_path(full_service_name: &str) -> PathBuf {
-    Path::new("/etc/systemd/system/").join(full_service_name)
+    get_unit_folder().join(full_service_name)
+}
+
+/// Errors if a service doesn't exist
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub fn check_service_exists(full_service_name: &str) -> Result<(), String> {
+    if get_service_file_path(full_service_name).exists() {
+        Ok(())
+    } else {
+        Err(format!("No such service {full_service_name}"))
+    }
+}
+
+/// Name of the timer running a scheduled service, eg. `foo.ser.timer` for `foo.ser.service`
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_full_timer_name(full_service_name: &str) -> String {
+    format!("{}.ser.timer", get_short_service_name(full_service_name))
+}
+
+/// Get the path to the timer file of a scheduled service, next to the service file
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_timer_file_path(full_service_name: &str) -> PathBuf {
+    get_unit_folder().join(get_full_timer_name(full_service_name))
+}
+
+/// Name of the path unit watching the files of a service, eg. `foo.ser.path` for `foo.ser.service`
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_full_path_unit_name(full_service_name: &str) -> String {
+    format!("{}.ser.path", get_short_service_name(full_service_name))
+}
+
+/// Get the path to the path unit file of a watched service, next to the service file
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_path_unit_file_path(full_service_name: &str) -> PathBuf {
+    get_unit_folder().join(get_full_path_unit_name(full_service_name))
+}
+
+/// Name of the one-shot service restarting a watched service, eg. `foo.ser-restart.service` for
+/// `foo.ser.service`. It doesn't end with `.ser.service`, so it isn't listed as a servicer service.
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_full_restarter_name(full_service_name: &str) -> String {
+    format!(
+        "{}.ser-restart.service",
+        get_short_service_name(full_service_name)
+    )
+}
+
+/// Get the path to the restarter file of a watched service, next to the service file
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_restarter_file_path(full_service_name: &str) -> PathBuf {
+    get_unit_folder().join(get_full_restarter_name(full_service_name))
+}
+
+/// Get the folder holding drop-in files of a service, eg. `/etc/systemd/system/foo.ser.service.d/`.
+/// Settings in drop-ins override the service file.
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub fn get_drop_in_folder(full_service_name: &str) -> PathBuf {
+    get_unit_folder().join(format!("{full_service_name}.d"))
+}
+
+/// Get the drop-in files of a service in the order systemd applies them. Empty if there are none.
+///
+/// # Arguments
+///
+/// * `full_service_name`
+///
+pub async fn get_drop_in_paths(full_service_name: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut drop_ins = vec![];
+
+    let Ok(mut entries) = fs::read_dir(get_drop_in_folder(full_service_name)).await else {
+        return Ok(drop_ins);
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name().to_string_lossy().ends_with(".conf") {
+            drop_ins.push(entry.path());
+        }
+    }
+
+    drop_ins.sort();
+
+    Ok(drop_ins)
+}
+
+/// Get systemd services having an extension `.ser.service`, sorted by name. We only monitor
+/// services created by this tool
+///
+/// # Arguments
+///
+/// * `tag` - Only get services having this tag
+///
+pub async fn get_servicer_services(tag: Option<&str>) -> Result<Vec<String>, std::io::Error> {
+    let folder_path = get_unit_folder();
+
+    let mut files = Vec::<String>::new();
+
+    // The user unit folder doesn't exist till the first user service is created
+    if !folder_path.exists() {
+        return Ok(files);
+    }
+
+    let mut dir = fs::read_dir(folder_path).await?;
+
+    while let Some(entry) = dir.next_entry().await? {
+        let path = entry.path();
+
+        if path.is_file() {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            if is_full_name(name) {
+                files.push(name.to_string());
+            }
+        }
+    }
+
+    if let Some(tag) = tag {
+        let mut tagged_files = vec![];
+
+        for file in files {
+            if get_service_tags(&file).await.iter().any(|t| t == tag) {
+                tagged_files.push(file);
+            }
+        }
+
+        files = tagged_files;
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// Resolves the services targeted by a command. `all` selects every servicer service, `@backend`
+/// selects services tagged `backend` and glob patterns like `api-*` select the services with
+/// matching short names. Other names are kept as is. Duplicates are removed.
+///
+/// # Arguments
+///
+/// * `targets` - Service names, `all`, `@tag` or glob patterns
+///
+pub async fn resolve_service_names(
+    targets: &[String],
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut names: Vec<String> = vec![];
+
+    for target in targets {
+        let matches = if target == ALL_SERVICES || target.starts_with('@') || is_glob(target) {
+            let tag = target.strip_prefix('@');
+
+            let mut services: Vec<String> = get_servicer_services(tag)
+                .await?
+                .iter()
+                .map(|full_service_name| get_short_service_name(full_service_name))
+                .collect();
+
+            if tag.is_none() && target != ALL_SERVICES {
+                let pattern = glob::Pattern::new(target)
+                    .map_err(|error| format!("Invalid pattern {target}: {error}"))?;
+
+                services.retain(|name| pattern.matches(name));
+            }
+
+            if services.is_empty() {
+                return Err(format!("No services match {target}").into());
+            }
+
+            services
+        } else {
+            vec![target.clone()]
+        };
+
+        for name in matches {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// Whether a target is a glob pattern, i.e. has `*`, `?` or `[`
+///
+/// # Arguments
+///
+/// * `target`
+///
+fn is_glob(target: &str) -> bool {
+    target.contains(['*', '?', '['])
 }
@@ -1,6 +1,9 @@
+use futures::{Stream, StreamExt};
 use zbus::Connection;
 use zbus::{dbus_proxy, zvariant};
 
+use super::scope::is_user_scope;
+
 /// Proxy object for `org.freedesktop.systemd1.Manager`.
 /// Taken from https://github.com/lucab/zbus_systemd/blob/main/src/systemd1/generated.rs
 #[dbus_proxy(
@@ -18,6 +21,18 @@ pub trait Manager {
     #[dbus_proxy(name = "StopUnit")]
     fn stop_unit(&self, name: String, mode: String) -> zbus::Result<zvariant::OwnedObjectPath>;
 
+    /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#RestartUnit()) Call interface method `RestartUnit`.
+    #[dbus_proxy(name = "RestartUnit")]
+    fn restart_unit(&self, name: String, mode: String) -> zbus::Result<zvariant::OwnedObjectPath>;
+
+    /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#TryRestartUnit()) Call interface method `TryRestartUnit`.
+    #[dbus_proxy(name = "TryRestartUnit")]
+    fn try_restart_unit(
+        &self,
+        name: String,
+        mode: String,
+    ) -> zbus::Result<zvariant::OwnedObjectPath>;
+
     /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#ReloadUnit()) Call interface method `ReloadUnit`.
     #[dbus_proxy(name = "ReloadUnit")]
     fn reload_unit(&self, name: String, mode: String) -> zbus::Result<zvariant::OwnedObjectPath>;
@@ -39,9 +54,40 @@ pub trait Manager {
         runtime: bool,
     ) -> zbus::Result<Vec<(String, String, String)>>;
 
+    /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#SetUnitProperties()) Call interface method `SetUnitProperties`.
+    #[dbus_proxy(name = "SetUnitProperties")]
+    fn set_unit_properties(
+        &self,
+        name: String,
+        runtime: bool,
+        properties: Vec<(String, zvariant::Value<'_>)>,
+    ) -> zbus::Result<()>;
+
     /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#Reload()) Call interface method `Reload`.
     #[dbus_proxy(name = "Reload")]
     fn reload(&self) -> zbus::Result<()>;
+
+    /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#Subscribe()) Call interface method `Subscribe`.
+    #[dbus_proxy(name = "Subscribe")]
+    fn subscribe(&self) -> zbus::Result<()>;
+
+    /// Get property `Version`.
+    #[dbus_proxy(property)]
+    fn version(&self) -> zbus::Result<String>;
+
+    /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#JobNew) Receive signal `JobNew`.
+    #[dbus_proxy(signal, name = "JobNew")]
+    fn job_new(&self, id: u32, job: zvariant::OwnedObjectPath, unit: String) -> zbus::Result<()>;
+
+    /// [📖](https://www.freedesktop.org/software/systemd/man/systemd.directives.html#JobRemoved) Receive signal `JobRemoved`.
+    #[dbus_proxy(signal, name = "JobRemoved")]
+    fn job_removed(
+        &self,
+        id: u32,
+        job: zvariant::OwnedObjectPath,
+        unit: String,
+        result: String,
+    ) -> zbus::Result<()>;
 }
 
 /// Proxy object for `org.freedesktop.systemd1.Unit`.
@@ -64,6 +110,18 @@ pub trait Unit {
     /// Get property `UnitFileState`.
     #[dbus_proxy(property)]
     fn unit_file_state(&self) -> zbus::Result<String>;
+
+    /// Get property `SubState`.
+    #[dbus_proxy(property)]
+    fn sub_state(&self) -> zbus::Result<String>;
+
+    /// Get property `ActiveEnterTimestamp`.
+    #[dbus_proxy(property)]
+    fn active_enter_timestamp(&self) -> zbus::Result<u64>;
+
+    /// Get property `InvocationID`.
+    #[dbus_proxy(property, name = "InvocationID")]
+    fn invocation_id(&self) -> zbus::Result<Vec<u8>>;
 }
 
 /// Proxy object for `org.freedesktop.systemd1.Service`.
@@ -78,6 +136,114 @@ trait Service {
     /// Get property `MainPID`.
     #[dbus_proxy(property, name = "MainPID")]
     fn main_pid(&self) -> zbus::Result<u32>;
+
+    /// Get property `NRestarts`.
+    #[dbus_proxy(property, name = "NRestarts")]
+    fn n_restarts(&self) -> zbus::Result<u32>;
+
+    /// Get property `Result`.
+    #[dbus_proxy(property)]
+    fn result(&self) -> zbus::Result<String>;
+
+    /// Get property `ExecMainStatus`.
+    #[dbus_proxy(property)]
+    fn exec_main_status(&self) -> zbus::Result<i32>;
+
+    /// Get property `ExecMainCode`.
+    #[dbus_proxy(property)]
+    fn exec_main_code(&self) -> zbus::Result<i32>;
+
+    /// Get property `ControlGroup`.
+    #[dbus_proxy(property)]
+    fn control_group(&self) -> zbus::Result<String>;
+
+    /// Get property `MemoryCurrent`.
+    #[dbus_proxy(property)]
+    fn memory_current(&self) -> zbus::Result<u64>;
+
+    /// Get property `CPUUsageNSec`.
+    #[dbus_proxy(property, name = "CPUUsageNSec")]
+    fn cpu_usage_nsec(&self) -> zbus::Result<u64>;
+
+    /// Get property `TasksCurrent`.
+    #[dbus_proxy(property)]
+    fn tasks_current(&self) -> zbus::Result<u64>;
+
+    /// Get property `IOReadBytes`.
+    #[dbus_proxy(property, name = "IOReadBytes")]
+    fn io_read_bytes(&self) -> zbus::Result<u64>;
+
+    /// Get property `IOWriteBytes`.
+    #[dbus_proxy(property, name = "IOWriteBytes")]
+    fn io_write_bytes(&self) -> zbus::Result<u64>;
+
+    /// Get property `ExecStart`.
+    #[dbus_proxy(property)]
+    fn exec_start(&self) -> zbus::Result<Vec<ExecCommand>>;
+
+    /// Get property `Environment`.
+    #[dbus_proxy(property)]
+    fn environment(&self) -> zbus::Result<Vec<String>>;
+}
+
+/// Proxy object for `org.freedesktop.systemd1.Timer`.
+/// Taken from https://github.com/lucab/zbus_systemd/blob/main/src/systemd1/generated.rs
+#[dbus_proxy(
+    interface = "org.freedesktop.systemd1.Timer",
+    default_service = "org.freedesktop.systemd1",
+    assume_defaults = false,
+    gen_blocking = false
+)]
+pub trait Timer {
+    /// Get property `NextElapseUSecRealtime`.
+    #[dbus_proxy(property, name = "NextElapseUSecRealtime")]
+    fn next_elapse_usec_realtime(&self) -> zbus::Result<u64>;
+
+    /// Get property `LastTriggerUSec`.
+    #[dbus_proxy(property, name = "LastTriggerUSec")]
+    fn last_trigger_usec(&self) -> zbus::Result<u64>;
+}
+
+/// Accounting values reported by systemd for a service's control group. A value is `None` if
+/// accounting is disabled for the unit.
+pub struct ServiceAccounting {
+    /// Path of the control group relative to the cgroup root, eg. `/system.slice/foo.service`
+    pub control_group: String,
+    pub memory_bytes: Option<u64>,
+    pub cpu_usage_nsec: Option<u64>,
+    pub tasks: Option<u64>,
+    pub io_read_bytes: Option<u64>,
+    pub io_write_bytes: Option<u64>,
+}
+
+/// A command of `ExecStart=` as reported by systemd. Holds the path, the argv, whether failure is
+/// ignored, start and exit timestamps, the PID, the exit code and the exit status.
+type ExecCommand = (String, Vec<String>, bool, u64, u64, u64, u64, u32, i32, i32);
+
+/// Command line and environment of a service as parsed by systemd from its unit file
+pub struct ExecSettings {
+    /// Args of each `ExecStart=` command, starting with the program
+    pub argv: Vec<Vec<String>>,
+
+    /// `KEY=VALUE` pairs set with `Environment=`
+    pub environment: Vec<String>,
+}
+
+/// Change to the active and sub-state of a unit, read from a `PropertiesChanged` signal. A state is
+/// `None` if it didn't change.
+pub struct UnitStateChange {
+    pub active_state: Option<String>,
+    pub sub_state: Option<String>,
+}
+
+/// Connects to the system bus, or to the session bus of the current user when managing user
+/// services
+pub async fn get_connection() -> zbus::Result<Connection> {
+    if is_user_scope() {
+        Connection::session().await
+    } else {
+        Connection::system().await
+    }
 }
 
 /// Returns the load state of a systemd unit
@@ -89,7 +255,7 @@ trait Service {
 /// * `connection`: zbus connection
 /// * `full_service_name`: Full name of the service name with '.service' in the end
 ///
-pub async fn get_active_state(connection: &Connection, full_service_name: &String) -> String {
+pub async fn get_active_state(connection: &Connection, full_service_name: &str) -> String {
     let object_path = get_unit_path(full_service_name);
 
     match zvariant::ObjectPath::try_from(object_path) {
@@ -113,7 +279,7 @@ pub async fn get_active_state(connection: &Connection, full_service_name: &Strin
 /// * `connection`: zbus connection
 /// * `full_service_name`: Full name of the service name with '.service' in the end
 ///
-pub async fn get_unit_file_state(connection: &Connection, full_service_name: &String) -> String {
+pub async fn get_unit_file_state(connection: &Connection, full_service_name: &str) -> String {
     let object_path = get_unit_path(full_service_name);
 
     match zvariant::ObjectPath::try_from(object_path) {
@@ -137,7 +303,7 @@ pub async fn get_unit_file_state(connection: &Connection, full_service_name: &St
 ///
 pub async fn get_main_pid(
     connection: &Connection,
-    full_service_name: &String,
+    full_service_name: &str,
 ) -> Result<u32, zbus::Error> {
     let object_path = get_unit_path(full_service_name);
 
@@ -148,6 +314,206 @@ pub async fn get_main_pid(
     service_proxy.main_pid().await
 }
 
+/// State details of a service, beyond its active state
+pub struct ServiceState {
+    /// Low level state, eg. `running`, `auto-restart` or `exited`
+    pub sub_state: String,
+
+    /// When the unit last entered the active state, in microseconds since the epoch. 0 if never
+    pub active_enter_timestamp: u64,
+
+    /// Number of automatic restarts
+    pub restarts: u32,
+
+    /// Result of the last run, eg. `success`, `exit-code`, `signal`, `oom-kill` or `timeout`
+    pub result: String,
+
+    /// Exit code or signal number of the main process
+    pub exec_main_status: i32,
+
+    /// How the main process ended, as a `CLD_*` code. 1 if it exited, 2 if it was killed and 3 if
+    /// it dumped core. 0 if it hasn't ended.
+    pub exec_main_code: i32,
+
+    /// ID of the current or last run in hex, as logged in the journal. Empty if never started.
+    pub invocation_id: String,
+}
+
+/// Returns the sub-state, start time, restart count, last result and invocation of a systemd
+/// service
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `full_service_name`: Full name of the service name with '.service' in the end
+///
+pub async fn get_service_state(
+    connection: &Connection,
+    full_service_name: &str,
+) -> Result<ServiceState, zbus::Error> {
+    let object_path = get_unit_path(full_service_name);
+
+    let validated_object_path = zvariant::ObjectPath::try_from(object_path)?;
+
+    let unit_proxy = UnitProxy::new(connection, validated_object_path.clone()).await?;
+    let service_proxy = ServiceProxy::new(connection, validated_object_path).await?;
+
+    Ok(ServiceState {
+        sub_state: unit_proxy.sub_state().await?,
+        active_enter_timestamp: unit_proxy.active_enter_timestamp().await?,
+        // Not available before systemd 235
+        restarts: service_proxy.n_restarts().await.unwrap_or(0),
+        result: service_proxy.result().await?,
+        exec_main_status: service_proxy.exec_main_status().await?,
+        exec_main_code: service_proxy.exec_main_code().await?,
+        invocation_id: unit_proxy
+            .invocation_id()
+            .await?
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect(),
+    })
+}
+
+/// Run times of a timer, in microseconds since the epoch. 0 if unknown.
+pub struct TimerState {
+    /// When the timer last started its service. 0 if never
+    pub last_trigger: u64,
+
+    /// When the timer starts its service next. 0 if the timer is stopped
+    pub next_elapse: u64,
+}
+
+/// Returns when a systemd timer last ran and when it runs next
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `full_timer_name`: Full name of the timer with '.timer' in the end
+///
+pub async fn get_timer_state(
+    connection: &Connection,
+    full_timer_name: &str,
+) -> Result<TimerState, zbus::Error> {
+    let object_path = get_unit_path(full_timer_name);
+
+    let validated_object_path = zvariant::ObjectPath::try_from(object_path)?;
+
+    let timer_proxy = TimerProxy::new(connection, validated_object_path).await?;
+
+    Ok(TimerState {
+        last_trigger: timer_proxy.last_trigger_usec().await?,
+        next_elapse: timer_proxy.next_elapse_usec_realtime().await?,
+    })
+}
+
+/// Returns resource accounting for the control group of a systemd service. The group includes the
+/// main process and every child it spawned.
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `full_service_name`: Full name of the service name with '.service' in the end
+///
+pub async fn get_service_accounting(
+    connection: &Connection,
+    full_service_name: &str,
+) -> Result<ServiceAccounting, zbus::Error> {
+    let object_path = get_unit_path(full_service_name);
+
+    let validated_object_path = zvariant::ObjectPath::try_from(object_path)?;
+
+    let service_proxy = ServiceProxy::new(connection, validated_object_path).await?;
+
+    // systemd reports u64::MAX when a value isn't tracked
+    let tracked = |value: u64| if value == u64::MAX { None } else { Some(value) };
+
+    Ok(ServiceAccounting {
+        control_group: service_proxy.control_group().await?,
+        memory_bytes: tracked(service_proxy.memory_current().await?),
+        cpu_usage_nsec: tracked(service_proxy.cpu_usage_nsec().await?),
+        tasks: tracked(service_proxy.tasks_current().await?),
+        io_read_bytes: tracked(service_proxy.io_read_bytes().await?),
+        io_write_bytes: tracked(service_proxy.io_write_bytes().await?),
+    })
+}
+
+/// Returns the command line and environment that systemd parsed from the unit file of a service
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `full_service_name`: Full name of the service name with '.service' in the end
+///
+pub async fn get_exec_settings(
+    connection: &Connection,
+    full_service_name: &str,
+) -> Result<ExecSettings, zbus::Error> {
+    let object_path = get_unit_path(full_service_name);
+
+    let validated_object_path = zvariant::ObjectPath::try_from(object_path)?;
+
+    let service_proxy = ServiceProxy::new(connection, validated_object_path).await?;
+
+    Ok(ExecSettings {
+        argv: service_proxy
+            .exec_start()
+            .await?
+            .into_iter()
+            .map(|command| command.1)
+            .collect(),
+        environment: service_proxy.environment().await?,
+    })
+}
+
+/// Streams changes to the active and sub-state of a systemd unit by listening to the unit's
+/// `PropertiesChanged` signal. systemd only emits the signal once a client calls
+/// `ManagerProxy::subscribe`.
+///
+/// # Arguments
+///
+/// * `connection`: zbus connection
+/// * `full_service_name`: Full name of the service name with '.service' in the end
+///
+pub async fn receive_unit_state_changes(
+    connection: &Connection,
+    full_service_name: &str,
+) -> Result<impl Stream<Item = UnitStateChange>, zbus::Error> {
+    let properties_proxy = zbus::fdo::PropertiesProxy::builder(connection)
+        .destination("org.freedesktop.systemd1")?
+        .path(get_unit_path(full_service_name))?
+        .build()
+        .await?;
+
+    let properties_changed = properties_proxy.receive_properties_changed().await?;
+
+    Ok(properties_changed.filter_map(|signal| async move {
+        let args = signal.args().ok()?;
+
+        if args.interface_name().as_str() != "org.freedesktop.systemd1.Unit" {
+            return None;
+        }
+
+        let get_string = |property: &str| {
+            args.changed_properties()
+                .get(property)
+                .and_then(|value| value.downcast_ref::<str>())
+                .map(|value| value.to_string())
+        };
+
+        let change = UnitStateChange {
+            active_state: get_string("ActiveState"),
+            sub_state: get_string("SubState"),
+        };
+
+        if change.active_state.is_none() && change.sub_state.is_none() {
+            None
+        } else {
+            Some(change)
+        }
+    }))
+}
+
 /// Encode into a valid dbus string
 ///
 /// # Arguments
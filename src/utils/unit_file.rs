@@ -0,0 +1,410 @@
+use super::service_names::get_service_file_path;
+
+/// Key in the `[Unit]` section holding the tags of a service, eg. `X-Servicer-Tags=backend payments`.
+/// systemd ignores keys starting with `X-`.
+pub const TAGS_KEY: &str = "X-Servicer-Tags";
+
+/// Values of a key in a unit file, in order of appearance. Comments are skipped.
+///
+/// # Arguments
+///
+/// * `body` - Contents of the unit file
+/// * `key` - The key, eg. `ExecStart`
+///
+pub fn get_unit_values(body: &str, key: &str) -> Vec<String> {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(|line| line.split_once('='))
+        .filter(|(line_key, _)| line_key.trim() == key)
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+/// Tags of a service read from its unit file. Empty if the service has no tags or the file can't
+/// be read.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn get_service_tags(full_service_name: &str) -> Vec<String> {
+    let body = tokio::fs::read_to_string(get_service_file_path(full_service_name))
+        .await
+        .unwrap_or_default();
+
+    get_unit_values(&body, TAGS_KEY)
+        .iter()
+        .flat_map(|value| value.split_whitespace())
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
+/// Checks that a tag only has letters, digits, `-`, `_` and `.`
+///
+/// # Arguments
+///
+/// * `tag`
+///
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    let is_valid = !tag.is_empty()
+        && tag
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_' | '.'));
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid tag `{tag}`. Tags may only have letters, digits, '-', '_' and '.'"
+        ))
+    }
+}
+
+/// Quotes a command line for `ExecStart=`, so that systemd passes each word to the program as is.
+///
+/// Words with whitespace, quotes, backslashes or control characters are wrapped in double quotes
+/// with C-style escapes. `%` specifiers are escaped as `%%` and `$` variables as `$$`.
+///
+/// # Arguments
+///
+/// * `argv` - The program followed by its args
+///
+pub fn quote_exec_args(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            let arg = arg.replace('$', "$$");
+
+            // A lone `;` separates commands
+            if arg == ";" {
+                "\\;".to_string()
+            } else {
+                quote_word(&arg)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Quotes a variable for `Environment=`, eg. `"GREETING=hello world"`. Values aren't expanded by
+/// systemd, so `$` is kept as is.
+///
+/// # Arguments
+///
+/// * `key` - The variable name
+/// * `value`
+///
+pub fn quote_environment(key: &str, value: &str) -> String {
+    quote_word(&format!("{key}={value}"))
+}
+
+/// Escapes `%` specifiers in a value that isn't split into words, eg. `WorkingDirectory=`
+///
+/// # Arguments
+///
+/// * `value`
+///
+pub fn escape_specifiers(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+/// Quotes a path for directives taking a list of paths, eg. `ReadWritePaths=`
+///
+/// # Arguments
+///
+/// * `path`
+///
+pub fn quote_path(path: &str) -> String {
+    quote_word(path)
+}
+
+/// Quotes a single word. Plain words are only specifier escaped.
+///
+/// # Arguments
+///
+/// * `word`
+///
+fn quote_word(word: &str) -> String {
+    let needs_quotes = word.is_empty()
+        || word
+            .chars()
+            .any(|char| char.is_whitespace() || char.is_control() || "\"'\\".contains(char));
+
+    if !needs_quotes {
+        return escape_specifiers(word);
+    }
+
+    let mut quoted = String::from("\"");
+
+    for char in word.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '%' => quoted.push_str("%%"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            char if char.is_control() => quoted.push_str(&format!("\\x{:02x}", char as u32)),
+            char => quoted.push(char),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Splits a `KEY=VALUE` pair. The key must be a valid variable name.
+///
+/// # Arguments
+///
+/// * `pair` - Eg. `GREETING=hello world`
+///
+pub fn parse_env_var(pair: &str) -> Result<(String, String), String> {
+    let (key, value) = pair.split_once('=').ok_or(format!(
+        "Invalid environment variable `{pair}`. Use KEY=VALUE"
+    ))?;
+
+    let is_valid_key = key
+        .chars()
+        .next()
+        .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+        && key
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_');
+
+    if !is_valid_key {
+        return Err(format!(
+            "Invalid environment variable name `{key}`. Names may only have letters, digits and '_'"
+        ));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Splits whitespace separated `KEY=VALUE` pairs. Values may be quoted like in a shell, eg.
+/// `GREETING="hello world" FOO=BAR`.
+///
+/// # Arguments
+///
+/// * `vars`
+///
+pub fn parse_env_vars(vars: &str) -> Result<Vec<(String, String)>, String> {
+    shell_words::split(vars)
+        .map_err(|error| format!("Invalid environment variables `{vars}`: {error}"))?
+        .iter()
+        .map(|pair| parse_env_var(pair))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Values that need quoting or escaping in unit files
+    const TRICKY_VALUES: &[&str] = &[
+        "plain",
+        "hello world",
+        "it's",
+        "say \"hi\"",
+        "50%",
+        "%n",
+        "$HOME",
+        "${HOME}",
+        ";",
+        "a;b",
+        "back\\slash",
+        "trailing\\",
+        "",
+        "tab\there",
+        "new\nline",
+        "carriage\rreturn",
+        "bell\x07",
+        "  padded  ",
+    ];
+
+    /// Resolves `%%` like systemd does before splitting a line. Other specifiers mean the value
+    /// wasn't escaped.
+    fn resolve_specifiers(line: &str) -> String {
+        let mut resolved = String::new();
+        let mut chars = line.chars();
+
+        while let Some(char) = chars.next() {
+            if char == '%' {
+                assert_eq!(chars.next(), Some('%'), "unescaped specifier in `{line}`");
+            }
+            resolved.push(char);
+        }
+
+        resolved
+    }
+
+    /// Decodes a C-style escape after a backslash like systemd's `cunescape_one`
+    fn unescape_one(chars: &mut std::iter::Peekable<std::str::Chars>) -> char {
+        match chars.next().expect("dangling backslash") {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'x' => {
+                let hex: String = chars.take(2).collect();
+                u8::from_str_radix(&hex, 16).unwrap() as char
+            }
+            char @ ('\\' | '"' | '\'' | ' ') => char,
+            char => panic!("unknown escape \\{char}"),
+        }
+    }
+
+    /// Splits a line into words like systemd's `extract_first_word` with `EXTRACT_UNQUOTE` and
+    /// `EXTRACT_CUNESCAPE`. In `ExecStart=`, a lone `\;` is the word `;`.
+    fn split_words(line: &str, exec: bool) -> Vec<String> {
+        let mut words = vec![];
+        let mut chars = line.chars().peekable();
+
+        loop {
+            while chars.next_if(|char| char.is_whitespace()).is_some() {}
+
+            if chars.peek().is_none() {
+                return words;
+            }
+
+            let rest: String = chars.clone().collect();
+            if exec && (rest == "\\;" || rest.starts_with("\\; ")) {
+                chars.nth(1);
+                words.push(";".to_string());
+                continue;
+            }
+
+            let mut word = String::new();
+
+            while let Some(char) = chars.next() {
+                match char {
+                    char if char.is_whitespace() => break,
+                    '\\' => word.push(unescape_one(&mut chars)),
+                    '"' => loop {
+                        match chars.next().expect("unterminated double quote") {
+                            '"' => break,
+                            '\\' => word.push(unescape_one(&mut chars)),
+                            char => word.push(char),
+                        }
+                    },
+                    '\'' => loop {
+                        match chars.next().expect("unterminated single quote") {
+                            '\'' => break,
+                            char => word.push(char),
+                        }
+                    },
+                    char => word.push(char),
+                }
+            }
+
+            words.push(word);
+        }
+    }
+
+    /// Parses `ExecStart=` like systemd: specifiers, then words, then `$$` when the command runs
+    fn parse_exec_start(line: &str) -> Vec<String> {
+        let resolved = resolve_specifiers(line);
+
+        // A lone `;` separates commands instead of being an argument
+        assert!(
+            !resolved.split(' ').any(|word| word == ";"),
+            "unescaped `;` in `{line}`"
+        );
+
+        split_words(&resolved, true)
+            .iter()
+            .map(|word| word.replace("$$", "$"))
+            .collect()
+    }
+
+    #[test]
+    fn exec_args_round_trip() {
+        for value in TRICKY_VALUES {
+            let argv = vec![
+                "/usr/bin/program".to_string(),
+                value.to_string(),
+                "last".to_string(),
+            ];
+
+            assert_eq!(
+                parse_exec_start(&quote_exec_args(&argv)),
+                argv,
+                "arg `{}`",
+                value.escape_debug()
+            );
+        }
+    }
+
+    #[test]
+    fn exec_args_keep_every_word() {
+        let argv: Vec<String> = TRICKY_VALUES
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+
+        assert_eq!(parse_exec_start(&quote_exec_args(&argv)), argv);
+    }
+
+    #[test]
+    fn environment_round_trip() {
+        for value in TRICKY_VALUES {
+            let line = quote_environment("KEY", value);
+            let words = split_words(&resolve_specifiers(&line), false);
+
+            assert_eq!(
+                words,
+                [format!("KEY={value}")],
+                "value `{}`",
+                value.escape_debug()
+            );
+        }
+    }
+
+    #[test]
+    fn specifiers_round_trip() {
+        for value in ["/srv/app", "/srv/100%", "/srv/%n%%"] {
+            assert_eq!(resolve_specifiers(&escape_specifiers(value)), value);
+        }
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        for value in ["/srv/app", "/srv/my app", "/srv/50%", "/srv/quote\"d"] {
+            assert_eq!(
+                split_words(&resolve_specifiers(&quote_path(value)), false),
+                [value]
+            );
+        }
+    }
+
+    #[test]
+    fn parses_env_vars() {
+        assert_eq!(
+            parse_env_vars(r#"GREETING="hello world" FOO=BAR EMPTY= QUOTE='it"s' _X=1"#).unwrap(),
+            [
+                ("GREETING".to_string(), "hello world".to_string()),
+                ("FOO".to_string(), "BAR".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+                ("QUOTE".to_string(), "it\"s".to_string()),
+                ("_X".to_string(), "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parsed_env_vars_round_trip() {
+        for (key, value) in parse_env_vars(r#"A="x y" B='50%' C=$HOME D="back\\slash""#).unwrap() {
+            assert_eq!(
+                split_words(&resolve_specifiers(&quote_environment(&key, &value)), false),
+                [format!("{key}={value}")]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_env_vars() {
+        assert!(parse_env_var("NO_EQUALS").is_err());
+        assert!(parse_env_var("1ABC=x").is_err());
+        assert!(parse_env_var("A-B=x").is_err());
+        assert!(parse_env_vars("A=\"unterminated").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use indoc::formatdoc;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use super::{
+    scope::{get_default_target, is_user_scope},
+    service_actions::{disable_service, enable_service, restart_service, stop_service},
+    service_names::{
+        get_full_path_unit_name, get_full_restarter_name, get_path_unit_file_path,
+        get_restarter_file_path,
+    },
+    systemd::ManagerProxy,
+    unit_file::{escape_specifiers, get_unit_values},
+};
+
+/// Resolves watched paths to absolute paths. The paths must exist.
+///
+/// # Arguments
+///
+/// * `paths` - Files or folders
+/// * `base` - Folder that relative paths start from
+///
+pub async fn resolve_watch_paths(paths: &[PathBuf], base: &Path) -> Result<Vec<String>, String> {
+    let mut resolved = vec![];
+
+    for path in paths {
+        let path = fs::canonicalize(base.join(path))
+            .await
+            .map_err(|error| format!("Invalid watch path {}: {error}", path.display()))?;
+
+        resolved.push(path.display().to_string());
+    }
+
+    Ok(resolved)
+}
+
+/// Generates the path unit restarting a service when its files change. Changes are picked up when a
+/// file is closed after writing, so a service isn't restarted halfway through a deploy. Folders
+/// aren't watched recursively.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `paths` - Absolute paths of the watched files and folders
+///
+pub fn get_path_unit_body(full_service_name: &str, paths: &[String]) -> String {
+    let path_directives = paths
+        .iter()
+        .map(|path| format!("PathChanged={}", escape_specifiers(path)))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let full_restarter_name = get_full_restarter_name(full_service_name);
+    let default_target = get_default_target();
+
+    formatdoc! {
+        r#"
+      # Generated with Servicer
+      [Unit]
+      Description=Restarts {full_service_name} when its files change
+
+      [Path]
+      {path_directives}
+      Unit={full_restarter_name}
+
+      [Install]
+      WantedBy={default_target}
+      "#
+    }
+}
+
+/// Generates the one-shot service started by the path unit. A stopped service isn't started.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+fn get_restarter_body(full_service_name: &str) -> String {
+    let scope_flag = if is_user_scope() { " --user" } else { "" };
+
+    formatdoc! {
+        r#"
+      # Generated with Servicer
+      [Unit]
+      Description=Restarts {full_service_name}
+
+      [Service]
+      Type=oneshot
+      ExecStart=systemctl{scope_flag} try-restart {full_service_name}
+      "#
+    }
+}
+
+/// Writes the path unit and the restarter of a watched service
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+/// * `path_unit_body` - Contents of the path unit
+///
+pub async fn write_watch_units(
+    full_service_name: &str,
+    path_unit_body: &str,
+) -> std::io::Result<()> {
+    fs::write(get_path_unit_file_path(full_service_name), path_unit_body).await?;
+    fs::write(
+        get_restarter_file_path(full_service_name),
+        get_restarter_body(full_service_name),
+    )
+    .await
+}
+
+/// Paths watched for a service, read from its path unit. Empty if the service isn't watched.
+///
+/// # Arguments
+///
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn get_watched_paths(full_service_name: &str) -> Vec<String> {
+    let body = fs::read_to_string(get_path_unit_file_path(full_service_name))
+        .await
+        .unwrap_or_default();
+
+    get_unit_values(&body, "PathChanged")
+        .iter()
+        .map(|path| path.replace("%%", "%"))
+        .collect()
+}
+
+/// Enables and restarts the path unit of a watched service, so that changed paths are picked up
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn activate_watch(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_path_unit_name = get_full_path_unit_name(full_service_name);
+
+    enable_service(manager_proxy, &full_path_unit_name).await?;
+
+    match restart_service(manager_proxy, &full_path_unit_name)
+        .await?
+        .as_str()
+    {
+        "done" => Ok(()),
+        result => Err(format!("Failed to start {full_path_unit_name}: {result}").into()),
+    }
+}
+
+/// Stops watching a service. The path unit is stopped and disabled, and it is deleted along with
+/// the restarter.
+///
+/// # Arguments
+///
+/// * `manager_proxy`: Manager proxy object
+/// * `full_service_name` - Full name of the service, having '.ser.service' at the end
+///
+pub async fn remove_watch(
+    manager_proxy: &ManagerProxy<'_>,
+    full_service_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_path_unit_name = get_full_path_unit_name(full_service_name);
+
+    stop_service(manager_proxy, &full_path_unit_name).await?;
+    disable_service(manager_proxy, &full_path_unit_name).await?;
+
+    for path in [
+        get_path_unit_file_path(full_service_name),
+        get_restarter_file_path(full_service_name),
+    ] {
+        if path.exists() {
+            fs::remove_file(&path).await?;
+        }
+    }
+
+    Ok(())
+}